@@ -0,0 +1,12 @@
+use std::process::exit;
+
+use rusty_chess::network::{self, server};
+
+fn main() {
+    let tcp_address = std::env::args().nth(1).unwrap_or_else(|| format!("0.0.0.0:{}", network::DEFAULT_PORT));
+    let websocket_address = std::env::args().nth(2).unwrap_or_else(|| format!("0.0.0.0:{}", network::DEFAULT_WEBSOCKET_PORT));
+    if let Err(error) = server::run(&tcp_address, &websocket_address) {
+        eprintln!("Could not start server on {} and {}: {}", tcp_address, websocket_address, error);
+        exit(1);
+    }
+}
@@ -1,6 +1,6 @@
 use super::{
     piece::{CaptureType, MoveType, PromotionPiece},
-    Color, Coordinate, Piece,
+    ChessMove, Color, Coordinate, Piece,
 };
 
 #[derive(Clone, Debug, PartialEq)]
@@ -13,18 +13,21 @@ impl ChessPosition {
     pub fn new(chess_board: &ChessBoard) -> Self {
         ChessPosition { squares: chess_board.squares, side_to_move: chess_board.side_to_move }
     }
+
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct ChessBoard {
     pub squares: [[Option<Piece>; 8]; 8],
-    side_to_move: Color,
+    pub(super) side_to_move: Color,
     pub white_king_position: Coordinate,
     pub black_king_position: Coordinate,
-    turn_number: u16,
+    pub(super) turn_number: u16,
     pub game_status: GameStatus,
     pub move_rule_counter: u8,
-    previous_positions: Vec<ChessPosition>,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -32,21 +35,35 @@ pub enum DrawType {
     Stalemate,
     MoveRule,
     Repetion,
+    /// Only known from imported games, the board never ends a game this way.
+    InsufficientMaterial,
+    /// Only known from imported games, the board never ends a game this way.
+    Agreement,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WinType {
+    Checkmate,
+    Resignation,
+    Timeout,
+    Abandonment,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum GameStatus {
     Ongoing,
     Draw(DrawType),
-    Win(Color),
+    Win(Color, WinType),
 }
 
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum MoveError {
     PieceMovedToSameSquare,
     OutOfBounds,
     NoPieceToMove,
     NotYourTurn,
     IllegalMove,
+    MissingPromotionPiece,
     GameHasEnded,
 }
 
@@ -58,28 +75,65 @@ impl ChessBoard {
         if moves.len() == 0 {
             if self.is_in_check(self.side_to_move) {
                 side_to_move.switch();
-                return GameStatus::Win(side_to_move);
+                return GameStatus::Win(side_to_move, WinType::Checkmate);
             }
             return GameStatus::Draw(DrawType::Stalemate);
         }
         if self.move_rule_counter >= 100 {
             return GameStatus::Draw(DrawType::MoveRule);
         }
+        GameStatus::Ongoing
+    }
 
-        let mut all_positions = self.previous_positions.clone();
-        all_positions.push(ChessPosition::new(self));
-        for i in 0..all_positions.len() - 1 {
-            let mut repetitions = 0;
-            for j in 1..all_positions.len() {
-                if all_positions.get(i) == all_positions.get(j) {
-                    repetitions += 1;
-                }
-                if repetitions >= 3 {
-                    return GameStatus::Draw(DrawType::Repetion);
-                }
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    /// The number of the current full move, starting at 1 and increasing after each black move.
+    pub fn fullmove_number(&self) -> u16 {
+        self.turn_number.div_ceil(2)
+    }
+
+    /// The positions the board goes through while `moves` are played from it, without the final one, oldest first.
+    pub fn line_history(&self, moves: &[ChessMove]) -> Vec<ChessPosition> {
+        let mut board = self.clone();
+        let mut history = Vec::new();
+        for chess_move in moves {
+            history.push(ChessPosition::new(&board));
+            if board.move_piece(chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion).is_err() {
+                break;
             }
         }
-        GameStatus::Ongoing
+        history
+    }
+
+    /// Plays a move like `move_piece`, and also ends the game in a draw when the move brings about a position for the
+    /// third time. The board keeps no history of its own, so `history` holds the positions before the current one,
+    /// oldest first.
+    pub fn move_piece_with_history(&mut self, history: &[ChessPosition], from: (usize, usize), to: (usize, usize), promotion: Option<PromotionPiece>) -> Result<GameStatus, MoveError> {
+        let position_before_move = ChessPosition::new(self);
+        if self.move_piece(from, to, promotion)? == GameStatus::Ongoing {
+            let position = ChessPosition::new(self);
+            let repetitions = history.iter().chain(std::iter::once(&position_before_move)).filter(|previous| **previous == position).count();
+            if repetitions >= 2 {
+                self.game_status = GameStatus::Draw(DrawType::Repetion);
+            }
+        }
+        Ok(self.game_status)
+    }
+
+    pub fn resign(&mut self, color: Color) {
+        self.forfeit(color, WinType::Resignation);
+    }
+
+    /// Ends an ongoing game as a loss for `color` for a reason other than the position on the board.
+    pub fn forfeit(&mut self, color: Color, win_type: WinType) {
+        if self.game_status != GameStatus::Ongoing {
+            return;
+        }
+        let mut winner = color;
+        winner.switch();
+        self.game_status = GameStatus::Win(winner, win_type);
     }
 
     pub fn get_legal_moves_for_piece_at(&self, piece_coordiante: Coordinate) -> Vec<Coordinate> {
@@ -190,7 +244,7 @@ impl ChessBoard {
             squares[x][y] = Some(piece);
         }
 
-        ChessBoard { squares, side_to_move: Color::White, white_king_position: Coordinate::new(4, 0), black_king_position: Coordinate::new(4, 7), turn_number: 1, game_status: GameStatus::Ongoing, move_rule_counter: 0, previous_positions: Vec::new() }
+        ChessBoard { squares, side_to_move: Color::White, white_king_position: Coordinate::new(4, 0), black_king_position: Coordinate::new(4, 7), turn_number: 1, game_status: GameStatus::Ongoing, move_rule_counter: 0 }
     }
 
     pub fn stalemate_start() -> ChessBoard {
@@ -207,7 +261,7 @@ impl ChessBoard {
             squares[x][y] = Some(piece);
         }
 
-        ChessBoard { squares, side_to_move: Color::White, white_king_position: Coordinate::new(4, 0), black_king_position: Coordinate::new(4, 7), turn_number: 1, game_status: GameStatus::Ongoing, move_rule_counter: 0, previous_positions: Vec::new() }
+        ChessBoard { squares, side_to_move: Color::White, white_king_position: Coordinate::new(4, 0), black_king_position: Coordinate::new(4, 7), turn_number: 1, game_status: GameStatus::Ongoing, move_rule_counter: 0 }
     }
 
     fn reset_enpassantable_flags(&mut self) {
@@ -224,6 +278,8 @@ impl ChessBoard {
         }
     }
 
+    /// Plays a move and judges the game by checkmate, stalemate, insufficient material and the move rule. Threefold
+    /// repetition needs the earlier positions of the game and is only detected by `move_piece_with_history`.
     pub fn move_piece(&mut self, from: (usize, usize), to: (usize, usize), promotion: Option<PromotionPiece>) -> Result<GameStatus, MoveError> {
         let from = Coordinate::from_tuple_usize(from);
         let to = Coordinate::from_tuple_usize(to);
@@ -237,14 +293,17 @@ impl ChessBoard {
         if self.game_status != GameStatus::Ongoing {
             return Err(MoveError::GameHasEnded);
         }
-        let position_before_move = ChessPosition::new(self);
         match self.squares[from.x][from.y] {
             None => Err(MoveError::NoPieceToMove),
             Some(piece) => {
                 if piece.get_color() != self.side_to_move {
                     return Err(MoveError::NotYourTurn);
                 }
-                match piece.is_legal_move(from, to, self, false) {
+                let move_type = piece.is_legal_move(from, to, self, false);
+                if promotion.is_none() && (move_type == MoveType::Promotion || move_type == MoveType::Capture(CaptureType::Promotion)) {
+                    return Err(MoveError::MissingPromotionPiece);
+                }
+                match move_type {
                     MoveType::CastleShort => {
                         self.move_rule_counter += 1;
                         self.squares[to.x][to.y] = self.squares[from.x][from.y].take();
@@ -313,7 +372,6 @@ impl ChessBoard {
                     }
                 }
 
-                self.previous_positions.push(position_before_move);
                 self.turn_number += 1;
                 self.side_to_move.switch();
 
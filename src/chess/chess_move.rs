@@ -0,0 +1,53 @@
+use super::{piece::PromotionPiece, Coordinate};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ChessMove {
+    pub from: Coordinate,
+    pub to: Coordinate,
+    pub promotion: Option<PromotionPiece>,
+}
+
+impl ChessMove {
+    pub fn new(from: Coordinate, to: Coordinate, promotion: Option<PromotionPiece>) -> Self {
+        ChessMove { from, to, promotion }
+    }
+
+    /// Parses a move in UCI long algebraic notation, e.g. `e2e4` or `e7e8q`.
+    pub fn from_uci(uci: &str) -> Option<ChessMove> {
+        let bytes = uci.as_bytes();
+        if bytes.len() != 4 && bytes.len() != 5 {
+            return None;
+        }
+        let from = Coordinate::from_algebraic(&uci[0..2])?;
+        let to = Coordinate::from_algebraic(&uci[2..4])?;
+        let promotion = match bytes.get(4) {
+            None => None,
+            Some(b'n') => Some(PromotionPiece::Knight),
+            Some(b'b') => Some(PromotionPiece::Bishop),
+            Some(b'r') => Some(PromotionPiece::Rook),
+            Some(b'q') => Some(PromotionPiece::Queen),
+            Some(_) => return None,
+        };
+        Some(ChessMove { from, to, promotion })
+    }
+
+    pub fn to_uci(&self) -> String {
+        let mut uci = format!("{}{}", self.from.to_algebraic(), self.to.to_algebraic());
+        match self.promotion {
+            Some(PromotionPiece::Knight) => uci.push('n'),
+            Some(PromotionPiece::Bishop) => uci.push('b'),
+            Some(PromotionPiece::Rook) => uci.push('r'),
+            Some(PromotionPiece::Queen) => uci.push('q'),
+            None => (),
+        }
+        uci
+    }
+
+    pub fn from_tuple(&self) -> (usize, usize) {
+        (self.from.x, self.from.y)
+    }
+
+    pub fn to_tuple(&self) -> (usize, usize) {
+        (self.to.x, self.to.y)
+    }
+}
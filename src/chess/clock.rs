@@ -0,0 +1,100 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::Color;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TimeControl {
+    pub initial_ms: u64,
+    pub increment_ms: u64,
+}
+
+impl TimeControl {
+    pub fn new(minutes: u64, increment_seconds: u64) -> Self {
+        TimeControl { initial_ms: minutes * 60_000, increment_ms: increment_seconds * 1000 }
+    }
+}
+
+/// A chess clock where only the side to move loses time.
+#[derive(Clone, Debug)]
+pub struct ChessClock {
+    pub time_control: TimeControl,
+    white_remaining: Duration,
+    black_remaining: Duration,
+    running: Option<(Color, Instant)>,
+}
+
+impl ChessClock {
+    pub fn new(time_control: TimeControl) -> Self {
+        let initial = Duration::from_millis(time_control.initial_ms);
+        ChessClock { time_control, white_remaining: initial, black_remaining: initial, running: None }
+    }
+
+    pub fn with_remaining(time_control: TimeControl, white_ms: u64, black_ms: u64) -> Self {
+        ChessClock { time_control, white_remaining: Duration::from_millis(white_ms), black_remaining: Duration::from_millis(black_ms), running: None }
+    }
+
+    pub fn start(&mut self, color: Color) {
+        self.stop();
+        self.running = Some((color, Instant::now()));
+    }
+
+    /// Stops the clock of the side that just moved, adds the increment and starts the opponent's clock.
+    pub fn press(&mut self, color: Color) {
+        self.stop();
+        let increment = Duration::from_millis(self.time_control.increment_ms);
+        *self.remaining_mut(color) += increment;
+        let mut opponent = color;
+        opponent.switch();
+        self.running = Some((opponent, Instant::now()));
+    }
+
+    pub fn stop(&mut self) {
+        if let Some((color, started)) = self.running.take() {
+            let remaining = self.remaining_mut(color);
+            *remaining = remaining.saturating_sub(started.elapsed());
+        }
+    }
+
+    pub fn running(&self) -> Option<Color> {
+        self.running.map(|(color, _)| color)
+    }
+
+    pub fn remaining(&self, color: Color) -> Duration {
+        let remaining = match color {
+            Color::White => self.white_remaining,
+            Color::Black => self.black_remaining,
+        };
+        match self.running {
+            Some((running, started)) if running == color => remaining.saturating_sub(started.elapsed()),
+            _ => remaining,
+        }
+    }
+
+    pub fn remaining_ms(&self, color: Color) -> u64 {
+        self.remaining(color).as_millis() as u64
+    }
+
+    /// The side whose time has run out, if any.
+    pub fn flagged(&self) -> Option<Color> {
+        [Color::White, Color::Black].into_iter().find(|color| self.remaining(*color).is_zero())
+    }
+
+    fn remaining_mut(&mut self, color: Color) -> &mut Duration {
+        match color {
+            Color::White => &mut self.white_remaining,
+            Color::Black => &mut self.black_remaining,
+        }
+    }
+}
+
+/// Formats a remaining time as `m:ss`, with tenths of a second once below ten seconds.
+pub fn format_clock(remaining: Duration) -> String {
+    let total_seconds = remaining.as_secs();
+    if total_seconds < 10 {
+        format!("0:0{}.{}", total_seconds, remaining.subsec_millis() / 100)
+    } else {
+        format!("{}:{:02}", total_seconds / 60, total_seconds % 60)
+    }
+}
@@ -13,6 +13,19 @@ impl Coordinate {
         Coordinate::new(tuple.0, tuple.1)
     }
 
+    /// Parses a square name such as `e4`.
+    pub fn from_algebraic(square: &str) -> Option<Self> {
+        let bytes = square.as_bytes();
+        if bytes.len() != 2 || !(b'a'..=b'h').contains(&bytes[0]) || !(b'1'..=b'8').contains(&bytes[1]) {
+            return None;
+        }
+        Some(Coordinate::new((bytes[0] - b'a') as usize, (bytes[1] - b'1') as usize))
+    }
+
+    pub fn to_algebraic(&self) -> String {
+        format!("{}{}", (b'a' + self.x as u8) as char, self.y + 1)
+    }
+
     pub fn vector(&self) -> Vector {
         Vector::new(self.x_i32(), self.y_i32())
     }
@@ -0,0 +1,239 @@
+use super::{
+    chess_board::{ChessBoard, GameStatus},
+    Color, Coordinate, Piece,
+};
+
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FenError {
+    WrongNumberOfFields,
+    InvalidPiecePlacement,
+    InvalidSideToMove,
+    InvalidCastlingRights,
+    InvalidEnPassantSquare,
+    InvalidMoveCounter,
+    /// A pawn stands on the first or the last rank.
+    PawnOnBackRank,
+    /// A side has no king or more than one.
+    WrongKingCount,
+}
+
+impl ChessBoard {
+    /// Builds a board from Forsyth-Edwards Notation. The halfmove clock and fullmove number may be omitted.
+    pub fn from_fen(fen: &str) -> Result<ChessBoard, FenError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 4 || fields.len() > 6 {
+            return Err(FenError::WrongNumberOfFields);
+        }
+
+        let mut squares = [[None; 8]; 8];
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::InvalidPiecePlacement);
+        }
+        for (rank_index, rank) in ranks.iter().enumerate() {
+            let y = 7 - rank_index;
+            let mut x = 0;
+            for symbol in rank.chars() {
+                if let Some(empty) = symbol.to_digit(10) {
+                    x += empty as usize;
+                    continue;
+                }
+                if x >= 8 {
+                    return Err(FenError::InvalidPiecePlacement);
+                }
+                squares[x][y] = Some(piece_from_fen_symbol(symbol).ok_or(FenError::InvalidPiecePlacement)?);
+                x += 1;
+            }
+            if x != 8 {
+                return Err(FenError::InvalidPiecePlacement);
+            }
+        }
+
+        let side_to_move = match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err(FenError::InvalidSideToMove),
+        };
+
+        let castling = fields[2];
+        if castling != "-" && !castling.chars().all(|right| "KQkq".contains(right)) {
+            return Err(FenError::InvalidCastlingRights);
+        }
+        for (color, rank, short, long) in [(Color::White, 0, 'K', 'Q'), (Color::Black, 7, 'k', 'q')] {
+            let can_short = castling.contains(short);
+            let can_long = castling.contains(long);
+            if let Some(Piece::King { has_moved, .. }) = squares[4][rank].as_mut() {
+                *has_moved = !(can_short || can_long);
+            }
+            for (rook_x, can_castle) in [(7, can_short), (0, can_long)] {
+                if let Some(Piece::Rook { color: rook_color, has_moved }) = squares[rook_x][rank].as_mut() {
+                    if *rook_color == color {
+                        *has_moved = !can_castle;
+                    }
+                }
+            }
+        }
+
+        let move_rule_counter = match fields.get(4) {
+            Some(halfmoves) => halfmoves.parse::<u8>().map_err(|_| FenError::InvalidMoveCounter)?,
+            None => 0,
+        };
+        let fullmove_number = match fields.get(5) {
+            Some(fullmoves) => fullmoves.parse::<u16>().map_err(|_| FenError::InvalidMoveCounter)?.max(1),
+            None => 1,
+        };
+        // turn_number is odd whenever white is to move
+        let turn_number = (fullmove_number - 1).checked_mul(2).and_then(|turns| turns.checked_add(if side_to_move == Color::White { 1 } else { 2 })).ok_or(FenError::InvalidMoveCounter)?;
+
+        if fields[3] != "-" {
+            let target = Coordinate::from_algebraic(fields[3]).ok_or(FenError::InvalidEnPassantSquare)?;
+            let pawn_y = match target.y {
+                2 => 3,
+                5 => 4,
+                _ => return Err(FenError::InvalidEnPassantSquare),
+            };
+            match squares[target.x][pawn_y].as_mut() {
+                Some(Piece::Pawn { enpassantable_turn, .. }) => *enpassantable_turn = Some(turn_number),
+                _ => return Err(FenError::InvalidEnPassantSquare),
+            }
+        }
+
+        let mut white_kings = Vec::new();
+        let mut black_kings = Vec::new();
+        for (x, file) in squares.iter().enumerate() {
+            for (y, square) in file.iter().enumerate() {
+                match square {
+                    Some(Piece::King { color: Color::White, .. }) => white_kings.push(Coordinate::new(x, y)),
+                    Some(Piece::King { color: Color::Black, .. }) => black_kings.push(Coordinate::new(x, y)),
+                    Some(Piece::Pawn { .. }) if y == 0 || y == 7 => return Err(FenError::PawnOnBackRank),
+                    _ => (),
+                }
+            }
+        }
+        let (white_king_position, black_king_position) = match (white_kings.as_slice(), black_kings.as_slice()) {
+            ([white_king], [black_king]) => (*white_king, *black_king),
+            _ => return Err(FenError::WrongKingCount),
+        };
+
+        let mut board = ChessBoard { squares, side_to_move, white_king_position, black_king_position, turn_number, game_status: GameStatus::Ongoing, move_rule_counter };
+        board.game_status = board.check_game_status();
+        Ok(board)
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut placement = String::new();
+        for y in (0..8).rev() {
+            let mut empty = 0;
+            for x in 0..8 {
+                match self.squares[x][y] {
+                    Some(piece) => {
+                        if empty > 0 {
+                            placement.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        placement.push(fen_symbol(piece));
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                placement.push_str(&empty.to_string());
+            }
+            if y > 0 {
+                placement.push('/');
+            }
+        }
+
+        let side_to_move = match self.side_to_move {
+            Color::White => "w",
+            Color::Black => "b",
+        };
+
+        let mut castling = String::new();
+        for (color, rank, short, long) in [(Color::White, 0, 'K', 'Q'), (Color::Black, 7, 'k', 'q')] {
+            if let Some(Piece::King { color: king_color, has_moved: false }) = self.squares[4][rank] {
+                if king_color == color {
+                    for (rook_x, right) in [(7, short), (0, long)] {
+                        if self.squares[rook_x][rank] == Some(Piece::Rook { color, has_moved: false }) {
+                            castling.push(right);
+                        }
+                    }
+                }
+            }
+        }
+        if castling.is_empty() {
+            castling.push('-');
+        }
+
+        let mut en_passant = "-".to_string();
+        for x in 0..8 {
+            for (pawn_y, target_y) in [(3, 2), (4, 5)] {
+                if let Some(Piece::Pawn { enpassantable_turn: Some(turn), .. }) = self.squares[x][pawn_y] {
+                    if turn == self.turn_number {
+                        en_passant = Coordinate::new(x, target_y).to_algebraic();
+                    }
+                }
+            }
+        }
+
+        format!("{} {} {} {} {} {}", placement, side_to_move, castling, en_passant, self.move_rule_counter, self.turn_number.div_ceil(2))
+    }
+}
+
+fn fen_symbol(piece: Piece) -> char {
+    let symbol = match piece {
+        Piece::Pawn { .. } => 'p',
+        Piece::Knight { .. } => 'n',
+        Piece::Bishop { .. } => 'b',
+        Piece::Rook { .. } => 'r',
+        Piece::Queen { .. } => 'q',
+        Piece::King { .. } => 'k',
+    };
+    match piece.get_color() {
+        Color::White => symbol.to_ascii_uppercase(),
+        Color::Black => symbol,
+    }
+}
+
+fn piece_from_fen_symbol(symbol: char) -> Option<Piece> {
+    let color = if symbol.is_ascii_uppercase() { Color::White } else { Color::Black };
+    match symbol.to_ascii_lowercase() {
+        'p' => Some(Piece::Pawn { color, enpassantable_turn: None }),
+        'n' => Some(Piece::Knight { color }),
+        'b' => Some(Piece::Bishop { color }),
+        'r' => Some(Piece::Rook { color, has_moved: true }),
+        'q' => Some(Piece::Queen { color }),
+        'k' => Some(Piece::King { color, has_moved: true }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fen_round_trip() {
+        for fen in [STARTING_FEN, "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2", "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 12 40", "8/8/4k3/8/8/3K4/8/8 w - - 0 1"] {
+            assert_eq!(ChessBoard::from_fen(fen).unwrap().to_fen(), fen);
+        }
+    }
+
+    #[test]
+    fn fen_counters_may_be_omitted() {
+        let board = ChessBoard::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -").unwrap();
+        assert_eq!(board.to_fen(), STARTING_FEN);
+    }
+
+    #[test]
+    fn fen_rejects_invalid_fields() {
+        assert_eq!(ChessBoard::from_fen("8/8/8 w - - 0 1").err(), Some(FenError::InvalidPiecePlacement));
+        assert_eq!(ChessBoard::from_fen("8/8/4k3/8/8/8/8/8 w - - 0 1").err(), Some(FenError::WrongKingCount));
+        assert_eq!(ChessBoard::from_fen("8/8/4k3/8/8/3K4/8/3K4 w - - 0 1").err(), Some(FenError::WrongKingCount));
+        assert_eq!(ChessBoard::from_fen("8/8/4k3/8/8/3K4/8/3P4 w - - 0 1").err(), Some(FenError::PawnOnBackRank));
+        assert_eq!(ChessBoard::from_fen("3p4/8/4k3/8/8/3K4/8/8 w - - 0 1").err(), Some(FenError::PawnOnBackRank));
+        assert_eq!(ChessBoard::from_fen("8/8/4k3/8/8/3K4/8/8 w - - 0 40000").err(), Some(FenError::InvalidMoveCounter));
+    }
+}
@@ -0,0 +1,506 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{
+    chess_board::{ChessPosition, DrawType, GameStatus, WinType},
+    eco,
+    fen::STARTING_FEN,
+    game_tree::GameTree,
+    pgn::{PgnGame, PgnMove},
+    ChessBoard, ChessMove, Color, Coordinate,
+};
+
+/// Numeric Annotation Glyphs for the usual move suffixes.
+pub const NAG_GOOD: u8 = 1;
+pub const NAG_MISTAKE: u8 = 2;
+pub const NAG_BRILLIANT: u8 = 3;
+pub const NAG_BLUNDER: u8 = 4;
+pub const NAG_INTERESTING: u8 = 5;
+pub const NAG_DUBIOUS: u8 = 6;
+/// The move suffixes with their NAGs, in the order the annotation buttons show them.
+pub const MOVE_SUFFIXES: [(&str, u8); 6] = [("!", NAG_GOOD), ("?", NAG_MISTAKE), ("!!", NAG_BRILLIANT), ("??", NAG_BLUNDER), ("!?", NAG_INTERESTING), ("?!", NAG_DUBIOUS)];
+
+/// The suffix a NAG is usually shown as, e.g. `??` for `NAG_BLUNDER`, or `None` for NAGs without one.
+pub fn nag_symbol(nag: u8) -> Option<&'static str> {
+    MOVE_SUFFIXES.iter().find(|(_, suffix_nag)| *suffix_nag == nag).map(|(symbol, _)| *symbol)
+}
+
+/// The colours of square highlights and arrows, as the letters used by the `[%csl]` and `[%cal]` comment commands.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShapeColor {
+    Green,
+    Red,
+    Yellow,
+    Blue,
+}
+
+impl ShapeColor {
+    pub const ALL: [ShapeColor; 4] = [ShapeColor::Green, ShapeColor::Red, ShapeColor::Yellow, ShapeColor::Blue];
+
+    pub fn letter(&self) -> char {
+        match self {
+            ShapeColor::Green => 'G',
+            ShapeColor::Red => 'R',
+            ShapeColor::Yellow => 'Y',
+            ShapeColor::Blue => 'B',
+        }
+    }
+
+    pub fn from_letter(letter: char) -> Option<ShapeColor> {
+        ShapeColor::ALL.into_iter().find(|color| color.letter() == letter)
+    }
+}
+
+/// A highlighted square, or an arrow when `from` and `to` differ.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Shape {
+    pub from: Coordinate,
+    pub to: Coordinate,
+    pub color: ShapeColor,
+}
+
+impl Shape {
+    pub fn is_arrow(&self) -> bool {
+        self.from != self.to
+    }
+
+    /// Reads a shape as written in `[%csl]` or `[%cal]`, e.g. `Rd4` or `Ge2e4`.
+    fn parse(text: &str) -> Option<Shape> {
+        let color = ShapeColor::from_letter(text.chars().next()?)?;
+        let (from, to) = match text.len() {
+            3 => (text.get(1..3)?, text.get(1..3)?),
+            5 => (text.get(1..3)?, text.get(3..5)?),
+            _ => return None,
+        };
+        Some(Shape { from: Coordinate::from_algebraic(from)?, to: Coordinate::from_algebraic(to)?, color })
+    }
+
+    fn to_pgn(self) -> String {
+        match self.is_arrow() {
+            true => format!("{}{}{}", self.color.letter(), self.from.to_algebraic(), self.to.to_algebraic()),
+            false => format!("{}{}", self.color.letter(), self.from.to_algebraic()),
+        }
+    }
+}
+
+/// Remarks attached to a single move, written after it in PGN.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MoveAnnotation {
+    /// Numeric Annotation Glyphs, e.g. `NAG_BLUNDER` for `??`.
+    pub nags: Vec<u8>,
+    pub comment: Option<String>,
+    /// Highlighted squares and arrows, kept in the comment as `[%csl]` and `[%cal]` commands.
+    pub shapes: Vec<Shape>,
+}
+
+impl MoveAnnotation {
+    /// The NAGs and the comment of a move read from PGN, with the shape commands taken out of the comment.
+    pub fn from_pgn_move(pgn_move: &PgnMove) -> MoveAnnotation {
+        let mut annotation = MoveAnnotation { nags: pgn_move.nags.clone(), ..Default::default() };
+        let Some(mut text) = pgn_move.comment.clone() else {
+            return annotation;
+        };
+        for command in ["[%csl ", "[%cal "] {
+            while let Some(start) = text.find(command) {
+                let Some(length) = text[start..].find(']') else {
+                    break;
+                };
+                annotation.shapes.extend(text[start + command.len()..start + length].split(',').filter_map(|shape| Shape::parse(shape.trim())));
+                text.replace_range(start..=start + length, "");
+            }
+        }
+        let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+        annotation.comment = (!text.is_empty()).then_some(text);
+        annotation
+    }
+
+    /// The comment as written in PGN, with the shapes in front of the text.
+    pub fn pgn_comment(&self) -> Option<String> {
+        let (arrows, squares): (Vec<Shape>, Vec<Shape>) = self.shapes.iter().partition(|shape| shape.is_arrow());
+        let mut comment = String::new();
+        for (command, shapes) in [("csl", squares), ("cal", arrows)] {
+            if !shapes.is_empty() {
+                comment.push_str(&format!("[%{} {}]", command, shapes.into_iter().map(Shape::to_pgn).collect::<Vec<String>>().join(",")));
+            }
+        }
+        if let Some(text) = self.comment.as_ref() {
+            if !comment.is_empty() {
+                comment.push(' ');
+            }
+            comment.push_str(text);
+        }
+        (!comment.is_empty()).then_some(comment)
+    }
+
+    /// The comment as shown to the player, without commands such as `[%clk 0:01:00]`.
+    pub fn comment_text(&self) -> String {
+        let mut text = self.comment.clone().unwrap_or_default();
+        while let Some(start) = text.find("[%") {
+            match text[start..].find(']') {
+                Some(length) => text.replace_range(start..=start + length, ""),
+                None => break,
+            }
+        }
+        text.split_whitespace().collect::<Vec<&str>>().join(" ")
+    }
+
+    /// Sets `nag` as the move's suffix in place of any other suffix, or takes it away when it is already set.
+    pub fn toggle_suffix(&mut self, nag: u8) {
+        let was_set = self.nags.contains(&nag);
+        self.nags.retain(|set| nag_symbol(*set).is_none());
+        if !was_set {
+            self.nags.insert(0, nag);
+        }
+    }
+
+    /// Draws `shape`, or erases it when the same shape is already drawn. A shape in another colour is replaced.
+    pub fn toggle_shape(&mut self, shape: Shape) {
+        toggle_shape(&mut self.shapes, shape);
+    }
+}
+
+/// Adds `shape` to `shapes`, or removes it when the same shape is already there. A shape in another colour is replaced.
+pub fn toggle_shape(shapes: &mut Vec<Shape>, shape: Shape) {
+    match shapes.iter().position(|drawn| drawn.from == shape.from && drawn.to == shape.to) {
+        Some(index) if shapes[index].color == shape.color => {
+            shapes.remove(index);
+        }
+        Some(index) => shapes[index].color = shape.color,
+        None => shapes.push(shape),
+    }
+}
+
+/// How well one side played according to a game review.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Accuracy {
+    /// From 0 to 100, where 100 means every move kept the evaluation.
+    pub accuracy: f32,
+    pub average_centipawn_loss: f32,
+}
+
+/// Everything needed to save a game: the players, the moves and how it ended.
+#[derive(Clone, Debug)]
+pub struct GameRecord {
+    /// Identifies the game in the game store, the time it was created in milliseconds since the Unix epoch.
+    pub id: u64,
+    pub event: String,
+    pub site: String,
+    /// The date the game started, as `YYYY.MM.DD`.
+    pub date: String,
+    pub white: String,
+    pub black: String,
+    /// The position the game started from when it is not the usual starting position.
+    pub start_fen: Option<String>,
+    pub moves: Vec<ChessMove>,
+    /// Annotations of the moves, always as many as there are moves.
+    pub annotations: Vec<MoveAnnotation>,
+    pub status: GameStatus,
+    /// Hints used by white and black, indexed by `Color as usize`.
+    pub hints: [u32; 2],
+    /// Accuracy of white and black, indexed by `Color as usize`, once the game has been reviewed.
+    pub accuracy: Option<[Accuracy; 2]>,
+}
+
+impl GameRecord {
+    pub fn new(event: &str, white: &str, black: &str) -> Self {
+        GameRecord { id: SystemTime::now().duration_since(UNIX_EPOCH).map(|since_epoch| since_epoch.as_millis() as u64).unwrap_or(0), event: event.to_string(), site: "Rusty Chess".to_string(), date: today(), white: white.to_string(), black: black.to_string(), start_fen: None, moves: Vec::new(), annotations: Vec::new(), status: GameStatus::Ongoing, hints: [0; 2], accuracy: None }
+    }
+
+    pub fn push_move(&mut self, chess_move: ChessMove, status: GameStatus) {
+        self.moves.push(chess_move);
+        self.annotations.push(MoveAnnotation::default());
+        self.status = status;
+    }
+
+    pub fn add_hint(&mut self, color: Color) {
+        self.hints[color as usize] += 1;
+    }
+
+    /// Reads a game from the games directory or another PGN file. Returns `None` when a move cannot be played. The
+    /// status is taken from the final position when it ends the game, and otherwise from the result and the
+    /// `TerminationReason` or `Termination` tag.
+    pub fn from_pgn(game: &PgnGame, id: u64) -> Option<GameRecord> {
+        let tag = |name: &str| game.tag(name).unwrap_or_default().to_string();
+        let mut record = GameRecord { id, event: tag("Event"), site: tag("Site"), date: tag("Date"), white: tag("White"), black: tag("Black"), start_fen: game.tag("FEN").map(str::to_string), moves: Vec::new(), annotations: Vec::new(), status: GameStatus::Ongoing, hints: [0; 2], accuracy: None };
+        record.hints = ["WhiteHints", "BlackHints"].map(|name| game.tag(name).and_then(|hints| hints.parse().ok()).unwrap_or(0));
+        let number = |name: &str| game.tag(name).and_then(|value| value.parse::<f32>().ok());
+        if let (Some(white), Some(black)) = (number("WhiteAccuracy"), number("BlackAccuracy")) {
+            record.accuracy = Some([(white, "WhiteACPL"), (black, "BlackACPL")].map(|(accuracy, acpl)| Accuracy { accuracy, average_centipawn_loss: number(acpl).unwrap_or(0.0) }));
+        }
+        let mut board = ChessBoard::from_fen(game.tag("FEN").unwrap_or(STARTING_FEN)).ok()?;
+        let mut history = Vec::new();
+        for pgn_move in game.moves.iter() {
+            let chess_move = board.san_to_move(&pgn_move.san)?;
+            let position = ChessPosition::new(&board);
+            let status = board.move_piece_with_history(&history, chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion).ok()?;
+            history.push(position);
+            record.push_move(chess_move, status);
+            *record.annotations.last_mut().unwrap() = MoveAnnotation::from_pgn_move(pgn_move);
+        }
+        if record.status == GameStatus::Ongoing {
+            record.status = status_from_result(&game.result, game.tag("TerminationReason").or(game.tag("Termination")).unwrap_or_default());
+        }
+        Some(record)
+    }
+
+    /// The board the game started from.
+    pub fn starting_board(&self) -> ChessBoard {
+        self.start_fen.as_deref().and_then(|fen| ChessBoard::from_fen(fen).ok()).unwrap_or_else(ChessBoard::starting_positions)
+    }
+
+    /// The board after the first `plies` moves of the game.
+    pub fn board_after(&self, plies: usize) -> ChessBoard {
+        let mut board = self.starting_board();
+        for chess_move in self.moves.iter().take(plies) {
+            let _ = board.move_piece(chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion);
+        }
+        board
+    }
+
+    /// The positions before the final one, oldest first, which repetitions are judged against.
+    pub fn history(&self) -> Vec<ChessPosition> {
+        self.starting_board().line_history(&self.moves)
+    }
+
+    /// The moves of the game in SAN.
+    pub fn san_moves(&self) -> Vec<String> {
+        self.starting_board().line_to_san(&self.moves)
+    }
+
+    /// Writes the game in Portable Game Notation. Hint counts are kept in the `WhiteHints` and `BlackHints` tags, review
+    /// results in `WhiteAccuracy`, `BlackAccuracy`, `WhiteACPL` and `BlackACPL`, and why the game ended in
+    /// `TerminationReason`, since the standard `Termination` tag does not tell a checkmate from a resignation.
+    pub fn to_pgn(&self) -> String {
+        self.to_pgn_with_tree(&GameTree::from_record(self))
+    }
+
+    /// Writes the tags of the record with the moves, variations and annotations of `tree`, whose main line should be the
+    /// record's moves.
+    pub fn to_pgn_with_tree(&self, tree: &GameTree) -> String {
+        let result = pgn_result(self.status);
+        let mut pgn = String::new();
+        let mut tags = vec![("Event", self.event.clone()), ("Site", self.site.clone()), ("Date", self.date.clone()), ("Round", "-".to_string()), ("White", self.white.clone()), ("Black", self.black.clone()), ("Result", result.to_string())];
+        if let Some(start_fen) = self.start_fen.as_ref().filter(|fen| fen.as_str() != STARTING_FEN) {
+            tags.push(("SetUp", "1".to_string()));
+            tags.push(("FEN", start_fen.clone()));
+        }
+        if self.start_fen.as_deref().is_none_or(|fen| fen == STARTING_FEN) {
+            if let Some(opening) = eco::classify(&ChessBoard::starting_positions().line_to_san(&self.moves)) {
+                tags.push(("ECO", opening.eco.to_string()));
+                tags.push(("Opening", opening.name.to_string()));
+            }
+        }
+        if self.status != GameStatus::Ongoing {
+            tags.push(("Termination", termination(self.status).to_string()));
+            tags.push(("TerminationReason", termination_reason(self.status).to_string()));
+        }
+        tags.push(("WhiteHints", self.hints[Color::White as usize].to_string()));
+        tags.push(("BlackHints", self.hints[Color::Black as usize].to_string()));
+        if let Some([white, black]) = self.accuracy {
+            tags.push(("WhiteAccuracy", format!("{:.1}", white.accuracy)));
+            tags.push(("BlackAccuracy", format!("{:.1}", black.accuracy)));
+            tags.push(("WhiteACPL", format!("{:.0}", white.average_centipawn_loss)));
+            tags.push(("BlackACPL", format!("{:.0}", black.average_centipawn_loss)));
+        }
+        for (name, value) in tags {
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, value.replace('\\', "\\\\").replace('"', "\\\"")));
+        }
+        pgn.push('\n');
+
+        let movetext = format!("{} {}", tree.movetext(), result);
+        pgn.push_str(&wrap(movetext.trim_start(), 80));
+        pgn.push('\n');
+        pgn
+    }
+}
+
+/// The name of the person using this computer, from the `USER` or `USERNAME` environment variable.
+pub fn local_player_name() -> String {
+    std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_else(|_| "Player".to_string())
+}
+
+/// The PGN result token for a game status.
+pub fn pgn_result(status: GameStatus) -> &'static str {
+    match status {
+        GameStatus::Ongoing => "*",
+        GameStatus::Win(Color::White, _) => "1-0",
+        GameStatus::Win(Color::Black, _) => "0-1",
+        GameStatus::Draw(_) => "1/2-1/2",
+    }
+}
+
+/// The value of the PGN `Termination` tag, which says why a game ended.
+pub fn termination(status: GameStatus) -> &'static str {
+    match status {
+        GameStatus::Ongoing => "unterminated",
+        GameStatus::Win(_, WinType::Checkmate | WinType::Resignation) | GameStatus::Draw(_) => "normal",
+        GameStatus::Win(_, WinType::Timeout) => "time forfeit",
+        GameStatus::Win(_, WinType::Abandonment) => "abandoned",
+    }
+}
+
+/// Why a game ended in more detail than `termination`, e.g. `checkmate` or `repetition`.
+pub fn termination_reason(status: GameStatus) -> &'static str {
+    match status {
+        GameStatus::Ongoing => "unterminated",
+        GameStatus::Win(_, WinType::Checkmate) => "checkmate",
+        GameStatus::Win(_, WinType::Resignation) => "resignation",
+        GameStatus::Win(_, WinType::Timeout) => "timeout",
+        GameStatus::Win(_, WinType::Abandonment) => "abandonment",
+        GameStatus::Draw(DrawType::Stalemate) => "stalemate",
+        GameStatus::Draw(DrawType::MoveRule) => "fifty-move rule",
+        GameStatus::Draw(DrawType::Repetion) => "repetition",
+        GameStatus::Draw(DrawType::InsufficientMaterial) => "insufficient material",
+        GameStatus::Draw(DrawType::Agreement) => "agreement",
+    }
+}
+
+/// The status of a game that did not end on the board, from its PGN result and the reason it ended. Wins without a known
+/// reason count as resignations, and draws without one as agreed draws.
+pub(super) fn status_from_result(result: &str, reason: &str) -> GameStatus {
+    let reason = reason.to_lowercase();
+    let win_type = if reason.contains("time") {
+        WinType::Timeout
+    } else if reason.contains("abandon") {
+        WinType::Abandonment
+    } else if reason.contains("checkmate") {
+        WinType::Checkmate
+    } else {
+        WinType::Resignation
+    };
+    match result {
+        "1-0" => GameStatus::Win(Color::White, win_type),
+        "0-1" => GameStatus::Win(Color::Black, win_type),
+        "1/2-1/2" if reason.contains("stalemate") => GameStatus::Draw(DrawType::Stalemate),
+        "1/2-1/2" if reason.contains("fifty") => GameStatus::Draw(DrawType::MoveRule),
+        "1/2-1/2" if reason.contains("repetition") => GameStatus::Draw(DrawType::Repetion),
+        "1/2-1/2" if reason.contains("insufficient") => GameStatus::Draw(DrawType::InsufficientMaterial),
+        "1/2-1/2" => GameStatus::Draw(DrawType::Agreement),
+        _ => GameStatus::Ongoing,
+    }
+}
+
+/// Breaks text into lines of at most `width` characters at spaces.
+fn wrap(text: &str, width: usize) -> String {
+    let mut wrapped = String::new();
+    let mut line_length = 0;
+    for word in text.split_whitespace() {
+        if line_length > 0 && line_length + 1 + word.len() > width {
+            wrapped.push('\n');
+            line_length = 0;
+        } else if line_length > 0 {
+            wrapped.push(' ');
+            line_length += 1;
+        }
+        wrapped.push_str(word);
+        line_length += word.len();
+    }
+    wrapped
+}
+
+/// Today's date in UTC as `YYYY.MM.DD`.
+pub fn today() -> String {
+    let days = SystemTime::now().duration_since(UNIX_EPOCH).map(|since_epoch| since_epoch.as_secs() / 86_400).unwrap_or(0) as i64;
+    let (year, month, day) = civil_from_days(days);
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// Converts days since 1970-01-01 into a Gregorian calendar date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::pgn;
+
+    fn read(text: &str) -> GameRecord {
+        GameRecord::from_pgn(&pgn::parse_pgn(text).into_iter().next().unwrap(), 1).unwrap()
+    }
+
+    #[test]
+    fn accuracy_tags_round_trip() {
+        let record = read("[WhiteAccuracy \"87.5\"]\n[BlackAccuracy \"92.1\"]\n[WhiteACPL \"31\"]\n[BlackACPL \"18\"]\n\n1. e4 e5 *\n");
+        assert_eq!(record.accuracy, Some([Accuracy { accuracy: 87.5, average_centipawn_loss: 31.0 }, Accuracy { accuracy: 92.1, average_centipawn_loss: 18.0 }]));
+        assert_eq!(read(&record.to_pgn()).accuracy, record.accuracy);
+        assert_eq!(read("1. e4 e5 *\n").accuracy, None);
+    }
+
+    #[test]
+    fn draws_keep_their_reason() {
+        assert_eq!(status_from_result("1/2-1/2", "repetition"), GameStatus::Draw(DrawType::Repetion));
+        assert_eq!(status_from_result("1/2-1/2", "insufficient material"), GameStatus::Draw(DrawType::InsufficientMaterial));
+        assert_eq!(status_from_result("1/2-1/2", "normal"), GameStatus::Draw(DrawType::Agreement));
+        assert_eq!(status_from_result("1-0", "time forfeit"), GameStatus::Win(Color::White, WinType::Timeout));
+        assert_eq!(status_from_result("*", ""), GameStatus::Ongoing);
+    }
+
+    const ANNOTATED_PGN: &str = r#"[Event "Test"]
+[White "A"]
+[Black "B"]
+[Result "0-1"]
+[Termination "normal"]
+[TerminationReason "resignation"]
+
+1. e4 {[%csl Gd4][%cal Re2e4] Best by test} 1... e5 $1 (1... c5 2. Nf3 (2. c3 d5) 2... d6) 2. Nf3 Nc6 $6 3. Bb5 0-1
+"#;
+
+    fn read_with_tree(text: &str) -> (GameRecord, GameTree) {
+        let game = pgn::parse_pgn(text).into_iter().next().unwrap();
+        (GameRecord::from_pgn(&game, 1).unwrap(), GameTree::from_pgn(&game).unwrap())
+    }
+
+    #[test]
+    fn pgn_annotations_are_read() {
+        let (record, tree) = read_with_tree(ANNOTATED_PGN);
+        assert_eq!(record.moves.len(), 5);
+        assert_eq!(record.annotations[0].comment.as_deref(), Some("Best by test"));
+        assert_eq!(record.annotations[0].shapes, vec![Shape::parse("Gd4").unwrap(), Shape::parse("Re2e4").unwrap()]);
+        assert_eq!(record.annotations[1].nags, vec![NAG_GOOD]);
+        assert_eq!(record.annotations[3].nags, vec![NAG_DUBIOUS]);
+        assert!(tree.movetext().contains("(1... c5 2. Nf3 (2. c3 d5) 2... d6)"));
+    }
+
+    #[test]
+    fn pgn_round_trip_keeps_variations_and_annotations() {
+        let (record, tree) = read_with_tree(ANNOTATED_PGN);
+        let written = record.to_pgn_with_tree(&tree);
+        let (read_again, tree_again) = read_with_tree(&written);
+        assert_eq!(tree_again.movetext(), tree.movetext());
+        assert_eq!(read_again.moves, record.moves);
+        assert_eq!(read_again.annotations, record.annotations);
+        assert_eq!(read_again.status, GameStatus::Win(Color::Black, WinType::Resignation));
+        assert_eq!(read_again.to_pgn_with_tree(&tree_again), written);
+    }
+
+    #[test]
+    fn shape_parse() {
+        assert_eq!(Shape::parse("Rd4"), Some(Shape { from: Coordinate::new(3, 3), to: Coordinate::new(3, 3), color: ShapeColor::Red }));
+        assert_eq!(Shape::parse("Ge2e4"), Some(Shape { from: Coordinate::new(4, 1), to: Coordinate::new(4, 3), color: ShapeColor::Green }));
+        for text in ["", "G", "Gd", "Xd4", "Gd9", "Gd4e", "Gaé1", "Gé1", "Ge2é"] {
+            assert_eq!(Shape::parse(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn toggle_shape_adds_recolors_and_removes() {
+        let green = Shape::parse("Ge2e4").unwrap();
+        let red = Shape { color: ShapeColor::Red, ..green };
+        let mut shapes = Vec::new();
+        toggle_shape(&mut shapes, green);
+        assert_eq!(shapes, vec![green]);
+        toggle_shape(&mut shapes, red);
+        assert_eq!(shapes, vec![red]);
+        toggle_shape(&mut shapes, red);
+        assert!(shapes.is_empty());
+    }
+}
@@ -0,0 +1,325 @@
+use super::{
+    chess_board::{ChessPosition, GameStatus, MoveError},
+    fen::STARTING_FEN,
+    game_record::{self, GameRecord, MoveAnnotation},
+    notation,
+    pgn::{PgnGame, PgnMove},
+    ChessBoard, ChessMove, Color,
+};
+
+/// A position in the game tree and the move that led to it.
+#[derive(Clone, Debug)]
+pub struct GameNode {
+    /// `None` for the root, the position the game started from.
+    pub chess_move: Option<ChessMove>,
+    pub san: String,
+    /// The board after the move. Repetitions are judged against the positions on the path from the root, see
+    /// `GameTree::history`.
+    pub board: ChessBoard,
+    pub annotation: MoveAnnotation,
+    pub parent: Option<usize>,
+    /// The moves played from this position. The first one continues the line this node is on, the others are variations.
+    pub children: Vec<usize>,
+    /// How many moves lead from the root to this node.
+    pub ply: usize,
+}
+
+/// A game with variations: every line that was tried from every position, with the main line first.
+///
+/// Nodes are kept in one list and refer to each other by index. Deleting a variation only unlinks it, so the indices
+/// of the remaining nodes stay valid.
+#[derive(Clone, Debug)]
+pub struct GameTree {
+    nodes: Vec<GameNode>,
+    /// The last node of the game the tree was read from and how that game ended, for endings the board cannot tell
+    /// such as a resignation. It applies while that node is still the end of the main line.
+    ending: Option<(usize, GameStatus)>,
+}
+
+impl GameTree {
+    pub const ROOT: usize = 0;
+
+    pub fn new(board: ChessBoard) -> Self {
+        GameTree { nodes: vec![GameNode { chess_move: None, san: String::new(), board, annotation: MoveAnnotation::default(), parent: None, children: Vec::new(), ply: 0 }], ending: None }
+    }
+
+    /// The tree with only the main line of the record, annotations included.
+    pub fn from_record(record: &GameRecord) -> Self {
+        let mut tree = GameTree::new(record.starting_board());
+        let mut node = GameTree::ROOT;
+        let mut history = Vec::new();
+        for (chess_move, annotation) in record.moves.iter().zip(record.annotations.iter()) {
+            match tree.play_with_history(node, *chess_move, &history) {
+                Ok(child) => {
+                    history.push(ChessPosition::new(&tree.nodes[node].board));
+                    node = child;
+                }
+                Err(_) => break,
+            }
+            tree.nodes[node].annotation = annotation.clone();
+        }
+        tree.ending = Some((node, record.status));
+        tree
+    }
+
+    /// Reads a game with its variations and annotations. A variation stops at its first move that cannot be played.
+    pub fn from_pgn(game: &PgnGame) -> Option<Self> {
+        let board = ChessBoard::from_fen(game.tag("FEN").unwrap_or(STARTING_FEN)).ok()?;
+        let mut tree = GameTree::new(board);
+        tree.add_line(GameTree::ROOT, &game.moves, Vec::new());
+        let last = tree.mainline().last().copied().unwrap_or(GameTree::ROOT);
+        let status = match tree.nodes[last].board.game_status {
+            GameStatus::Ongoing => game_record::status_from_result(&game.result, game.tag("TerminationReason").or(game.tag("Termination")).unwrap_or_default()),
+            status => status,
+        };
+        tree.ending = Some((last, status));
+        Some(tree)
+    }
+
+    /// Plays `line` from `node`. `history` holds the positions on the path to `node`, so it is not walked again for
+    /// every move.
+    fn add_line(&mut self, mut node: usize, line: &[PgnMove], mut history: Vec<ChessPosition>) {
+        for pgn_move in line {
+            let Some(child) = self.nodes[node].board.san_to_move(&pgn_move.san).and_then(|chess_move| self.play_with_history(node, chess_move, &history).ok()) else {
+                return;
+            };
+            self.nodes[child].annotation = MoveAnnotation::from_pgn_move(pgn_move);
+            // Variations are alternatives to this move, so they start from the same position
+            for variation in pgn_move.variations.iter() {
+                self.add_line(node, variation, history.clone());
+            }
+            history.push(ChessPosition::new(&self.nodes[node].board));
+            node = child;
+        }
+    }
+
+    pub fn node(&self, node: usize) -> &GameNode {
+        &self.nodes[node]
+    }
+
+    pub fn node_mut(&mut self, node: usize) -> &mut GameNode {
+        &mut self.nodes[node]
+    }
+
+    pub fn board(&self, node: usize) -> &ChessBoard {
+        &self.nodes[node].board
+    }
+
+    /// The positions on the path from the root to `node`, without its own, oldest first.
+    pub fn history(&self, node: usize) -> Vec<ChessPosition> {
+        let mut history: Vec<ChessPosition> = std::iter::successors(self.nodes[node].parent, |node| self.nodes[*node].parent).map(|node| ChessPosition::new(&self.nodes[node].board)).collect();
+        history.reverse();
+        history
+    }
+
+    /// Plays a move from `node` and returns the node after it. A move that was already tried from there is reused,
+    /// a new one becomes the last variation, or the main continuation when there is none yet.
+    pub fn play(&mut self, node: usize, chess_move: ChessMove) -> Result<usize, MoveError> {
+        let history = self.history(node);
+        self.play_with_history(node, chess_move, &history)
+    }
+
+    /// Like `play`, for callers that already know `history(node)`.
+    fn play_with_history(&mut self, node: usize, chess_move: ChessMove, history: &[ChessPosition]) -> Result<usize, MoveError> {
+        if let Some(existing) = self.nodes[node].children.iter().copied().find(|child| self.nodes[*child].chess_move == Some(chess_move)) {
+            return Ok(existing);
+        }
+        let mut board = self.nodes[node].board.clone();
+        let san = board.move_to_san(chess_move).ok_or(MoveError::IllegalMove)?;
+        board.move_piece_with_history(history, chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion)?;
+        let child = self.nodes.len();
+        self.nodes.push(GameNode { chess_move: Some(chess_move), san, board, annotation: MoveAnnotation::default(), parent: Some(node), children: Vec::new(), ply: self.nodes[node].ply + 1 });
+        self.nodes[node].children.push(child);
+        Ok(child)
+    }
+
+    /// The nodes from the first move to `node`.
+    pub fn path(&self, node: usize) -> Vec<usize> {
+        let mut path: Vec<usize> = std::iter::successors(Some(node), |node| self.nodes[*node].parent).filter(|node| *node != GameTree::ROOT).collect();
+        path.reverse();
+        path
+    }
+
+    /// The moves of the main line, from the root to the end of the game.
+    pub fn mainline(&self) -> Vec<usize> {
+        std::iter::successors(self.nodes[GameTree::ROOT].children.first().copied(), |node| self.nodes[*node].children.first().copied()).collect()
+    }
+
+    pub fn is_mainline(&self, node: usize) -> bool {
+        self.variation_start(node).is_none()
+    }
+
+    /// The first move of the innermost variation `node` is in, or `None` when it is on the main line.
+    pub fn variation_start(&self, mut node: usize) -> Option<usize> {
+        while let Some(parent) = self.nodes[node].parent {
+            if self.nodes[parent].children.first() != Some(&node) {
+                return Some(node);
+            }
+            node = parent;
+        }
+        None
+    }
+
+    /// Moves the variation `node` is in one place up among the alternatives of its first move. Promoting the first
+    /// alternative makes it the continuation of the line it branched from.
+    pub fn promote(&mut self, node: usize) {
+        let Some(start) = self.variation_start(node) else {
+            return;
+        };
+        let parent = self.nodes[start].parent.unwrap();
+        let children = &mut self.nodes[parent].children;
+        let index = children.iter().position(|child| *child == start).unwrap();
+        children.swap(index - 1, index);
+    }
+
+    /// Puts every move from the root to `node` first among its alternatives, so that they form the main line.
+    pub fn make_mainline(&mut self, node: usize) {
+        for node in self.path(node) {
+            let parent = self.nodes[node].parent.unwrap();
+            let children = &mut self.nodes[parent].children;
+            let index = children.iter().position(|child| *child == node).unwrap();
+            children[..=index].rotate_right(1);
+        }
+    }
+
+    /// Removes `node` and every move after it. Returns the position before it, or `None` for the root, which stays.
+    pub fn delete(&mut self, node: usize) -> Option<usize> {
+        let parent = self.nodes[node].parent?;
+        self.nodes[parent].children.retain(|child| *child != node);
+        Some(parent)
+    }
+
+    /// The moves with their numbers, annotations and variations in parentheses, as in PGN. Black's move number is
+    /// repeated after a comment and at the start and after the end of a variation.
+    pub fn movetext(&self) -> String {
+        self.movetext_words().into_iter().map(|(_, word)| word).collect::<Vec<String>>().join(" ")
+    }
+
+    /// The movetext split at the spaces between moves, each part with the node it belongs to. A move keeps its number
+    /// and NAGs, a comment is a part of its own, and parentheses stay attached to the moves they enclose.
+    pub fn movetext_words(&self) -> Vec<(usize, String)> {
+        let mut words = Vec::new();
+        self.write_continuation(GameTree::ROOT, true, &mut words);
+        words
+    }
+
+    fn write_continuation(&self, node: usize, number_needed: bool, words: &mut Vec<(usize, String)>) {
+        let Some((&main, variations)) = self.nodes[node].children.split_first() else {
+            return;
+        };
+        self.write_move(main, number_needed, words);
+        for &variation in variations {
+            let start = words.len();
+            self.write_move(variation, true, words);
+            self.write_continuation(variation, self.nodes[variation].annotation.pgn_comment().is_some(), words);
+            words[start].1.insert(0, '(');
+            words.last_mut().unwrap().1.push(')');
+        }
+        self.write_continuation(main, !variations.is_empty() || self.nodes[main].annotation.pgn_comment().is_some(), words);
+    }
+
+    fn write_move(&self, node: usize, number_needed: bool, words: &mut Vec<(usize, String)>) {
+        let root = &self.nodes[GameTree::ROOT].board;
+        let game_node = &self.nodes[node];
+        let label = notation::move_number_label(game_node.ply - 1, root.fullmove_number(), root.side_to_move() == Color::White);
+        let mut word = if !label.ends_with("...") || number_needed { format!("{} {}", label, game_node.san) } else { game_node.san.clone() };
+        for nag in game_node.annotation.nags.iter() {
+            word.push_str(&format!(" ${}", nag));
+        }
+        words.push((node, word));
+        if let Some(comment) = game_node.annotation.pgn_comment() {
+            words.push((node, format!("{{{}}}", comment.replace('}', ")"))));
+        }
+    }
+
+    /// A copy of `record`, which only provides the tags, with the main line of the tree as its moves. The status is how
+    /// the game the tree was read from ended while its last move is still the end of the main line, and otherwise the
+    /// status of the final position.
+    pub fn mainline_record(&self, record: &GameRecord) -> GameRecord {
+        let mut mainline_record = record.clone();
+        mainline_record.moves.clear();
+        mainline_record.annotations.clear();
+        for node in self.mainline() {
+            let game_node = &self.nodes[node];
+            mainline_record.push_move(game_node.chess_move.unwrap(), game_node.board.game_status);
+            *mainline_record.annotations.last_mut().unwrap() = game_node.annotation.clone();
+        }
+        let last = self.mainline().last().copied().unwrap_or(GameTree::ROOT);
+        mainline_record.status = match self.ending {
+            Some((node, status)) if node == last => status,
+            _ => self.nodes[last].board.game_status,
+        };
+        mainline_record
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::{chess_board::DrawType, pgn};
+
+    fn tree(text: &str) -> GameTree {
+        GameTree::from_pgn(&pgn::parse_pgn(text).into_iter().next().unwrap()).unwrap()
+    }
+
+    fn node_of(tree: &GameTree, parent: usize, san: &str) -> usize {
+        *tree.node(parent).children.iter().find(|child| tree.node(**child).san == san).unwrap()
+    }
+
+    #[test]
+    fn promote_moves_a_variation_up() {
+        let mut tree = tree("1. e4 (1. d4) (1. c4 c5) e5 *");
+        let c4 = node_of(&tree, GameTree::ROOT, "c4");
+        let c5 = node_of(&tree, c4, "c5");
+        tree.promote(c5);
+        assert_eq!(tree.movetext(), "1. e4 (1. c4 c5) (1. d4) 1... e5");
+        tree.promote(c5);
+        assert_eq!(tree.movetext(), "1. c4 (1. e4 e5) (1. d4) 1... c5");
+        assert!(tree.is_mainline(c5));
+        tree.promote(c5);
+        assert_eq!(tree.movetext(), "1. c4 (1. e4 e5) (1. d4) 1... c5");
+    }
+
+    #[test]
+    fn make_mainline_and_delete() {
+        let mut tree = tree("1. e4 e5 (1... c5 2. Nf3 (2. c3)) 2. Nf3 *");
+        let c5 = node_of(&tree, node_of(&tree, GameTree::ROOT, "e4"), "c5");
+        let c3 = node_of(&tree, c5, "c3");
+        tree.make_mainline(c3);
+        assert_eq!(tree.movetext(), "1. e4 c5 (1... e5 2. Nf3) 2. c3 (2. Nf3)");
+        assert_eq!(tree.delete(c5), Some(tree.node(c5).parent.unwrap()));
+        assert_eq!(tree.movetext(), "1. e4 e5 2. Nf3");
+        assert_eq!(tree.delete(GameTree::ROOT), None);
+    }
+
+    #[test]
+    fn repetitions_follow_the_path_of_the_node() {
+        let mut tree = tree("1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3 Nf6 4. Ng1 (4. e3) *");
+        let last = *tree.mainline().last().unwrap();
+        let ng8 = tree.play(last, ChessMove::from_uci("f6g8").unwrap()).unwrap();
+        assert_eq!(tree.board(ng8).game_status, GameStatus::Draw(DrawType::Repetion));
+
+        let e3 = node_of(&tree, tree.node(last).parent.unwrap(), "e3");
+        let ng8 = tree.play(e3, ChessMove::from_uci("f6g8").unwrap()).unwrap();
+        assert_eq!(tree.board(ng8).game_status, GameStatus::Ongoing);
+    }
+
+    #[test]
+    fn repetitions_are_found_while_reading() {
+        let tree = tree("1. Nf3 Nf6 2. Ng1 (2. e3 Ng8 3. Ng1 Nf6 4. Nf3) 2... Ng8 3. Nf3 Nf6 4. Ng1 Ng8 *");
+        let last = *tree.mainline().last().unwrap();
+        assert_eq!(tree.board(last).game_status, GameStatus::Draw(DrawType::Repetion));
+        let record = tree.mainline_record(&GameRecord::new("", "", ""));
+        let from_record = GameTree::from_record(&record);
+        assert_eq!(from_record.board(*from_record.mainline().last().unwrap()).game_status, GameStatus::Draw(DrawType::Repetion));
+    }
+
+    #[test]
+    fn variations_survive_writing_and_reading() {
+        let text = "1. e4 e5 (1... c5 2. Nf3 (2. c3 d5) 2... d6) 2. Nf3 Nc6 *";
+        let tree = tree(text);
+        let written = tree.mainline_record(&GameRecord::new("", "", "")).to_pgn_with_tree(&tree);
+        assert_eq!(GameTree::from_pgn(&pgn::parse_pgn(&written).into_iter().next().unwrap()).unwrap().movetext(), tree.movetext());
+        assert_eq!(tree.movetext(), "1. e4 e5 (1... c5 2. Nf3 (2. c3 d5) 2... d6) 2. Nf3 Nc6");
+    }
+}
@@ -1,7 +1,17 @@
 pub mod chess_board;
 pub mod piece;
 pub mod coordinate;
+pub mod chess_move;
+pub mod clock;
+pub mod eco;
+pub mod fen;
+pub mod game_record;
+pub mod game_tree;
+pub mod notation;
+pub mod pgn;
+pub mod puzzle;
 
 pub use  piece::{Piece, Color};
 pub use chess_board::ChessBoard;
-pub use coordinate::Coordinate;
\ No newline at end of file
+pub use coordinate::Coordinate;
+pub use chess_move::ChessMove;
\ No newline at end of file
@@ -0,0 +1,217 @@
+use super::{
+    chess_board::{GameStatus, WinType},
+    piece::{CaptureType, MoveType, PromotionPiece},
+    ChessBoard, ChessMove, Color, Coordinate, Piece,
+};
+
+impl ChessBoard {
+    /// Writes a legal move in Standard Algebraic Notation, e.g. `Nbd7`, `exd5`, `O-O` or `e8=Q+`. Returns `None` for illegal moves.
+    pub fn move_to_san(&self, chess_move: ChessMove) -> Option<String> {
+        let piece = self.squares[chess_move.from.x][chess_move.from.y]?;
+        if piece.get_color() != self.side_to_move() {
+            return None;
+        }
+        let move_type = piece.is_legal_move(chess_move.from, chess_move.to, self, false);
+
+        let mut san = match move_type {
+            MoveType::Illegal => return None,
+            MoveType::CastleShort => "O-O".to_string(),
+            MoveType::CastleLong => "O-O-O".to_string(),
+            _ => {
+                let is_capture = matches!(move_type, MoveType::Capture(_));
+                let mut san = String::new();
+                if let Piece::Pawn { .. } = piece {
+                    if is_capture {
+                        san.push(chess_move.from.to_algebraic().remove(0));
+                    }
+                } else {
+                    san.push(piece_letter(piece));
+                    san.push_str(&self.disambiguation(piece, chess_move.from, chess_move.to));
+                }
+                if is_capture {
+                    san.push('x');
+                }
+                san.push_str(&chess_move.to.to_algebraic());
+                if matches!(move_type, MoveType::Promotion | MoveType::Capture(CaptureType::Promotion)) {
+                    san.push('=');
+                    san.push(promotion_letter(chess_move.promotion?));
+                }
+                san
+            }
+        };
+
+        let mut board_after_move = self.clone();
+        match board_after_move.move_piece(chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion).ok()? {
+            GameStatus::Win(_, WinType::Checkmate) => san.push('#'),
+            _ if board_after_move.is_in_check(board_after_move.side_to_move()) => san.push('+'),
+            _ => (),
+        }
+        Some(san)
+    }
+
+    /// Reads a move in Standard Algebraic Notation. Check marks and `!`/`?` suffixes are optional, and castling may be
+    /// written with zeros. Returns `None` when the move is not legal here.
+    pub fn san_to_move(&self, san: &str) -> Option<ChessMove> {
+        let wanted = normalize_san(san);
+        let rank = if self.side_to_move() == Color::White { 0 } else { 7 };
+        let to = match wanted.as_str() {
+            "O-O" => Coordinate::new(6, rank),
+            "O-O-O" => Coordinate::new(2, rank),
+            _ => {
+                let square = wanted.split('=').next()?;
+                Coordinate::from_algebraic(square.get(square.len().checked_sub(2)?..)?)?
+            }
+        };
+        let promotions = [None, Some(PromotionPiece::Queen), Some(PromotionPiece::Rook), Some(PromotionPiece::Bishop), Some(PromotionPiece::Knight)];
+        for x in 0..8 {
+            for y in 0..8 {
+                let from = Coordinate::new(x, y);
+                if !self.squares[x][y].is_some_and(|piece| piece.get_color() == self.side_to_move()) {
+                    continue;
+                }
+                for promotion in promotions {
+                    let chess_move = ChessMove::new(from, to, promotion);
+                    if self.move_to_san(chess_move).is_some_and(|san| normalize_san(&san) == wanted) {
+                        return Some(chess_move);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Writes a sequence of moves played from this position in SAN, stopping at the first illegal move.
+    pub fn line_to_san(&self, moves: &[ChessMove]) -> Vec<String> {
+        let mut board = self.clone();
+        let mut line = Vec::new();
+        for chess_move in moves {
+            let Some(san) = board.move_to_san(*chess_move) else {
+                break;
+            };
+            if board.move_piece(chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion).is_err() {
+                break;
+            }
+            line.push(san);
+        }
+        line
+    }
+
+    /// The file, rank or square needed to tell `from` apart from other pieces of the same kind that can also reach `to`.
+    fn disambiguation(&self, piece: Piece, from: Coordinate, to: Coordinate) -> String {
+        let mut same_file = false;
+        let mut same_rank = false;
+        let mut is_ambiguous = false;
+        for x in 0..8 {
+            for y in 0..8 {
+                let other = Coordinate::new(x, y);
+                if other == from {
+                    continue;
+                }
+                if let Some(other_piece) = self.squares[x][y] {
+                    if piece_letter(other_piece) == piece_letter(piece) && other_piece.get_color() == piece.get_color() && other_piece.is_legal_move(other, to, self, false) != MoveType::Illegal {
+                        is_ambiguous = true;
+                        same_file |= x == from.x;
+                        same_rank |= y == from.y;
+                    }
+                }
+            }
+        }
+
+        let square = from.to_algebraic();
+        match (is_ambiguous, same_file, same_rank) {
+            (false, _, _) => String::new(),
+            (true, false, _) => square[0..1].to_string(),
+            (true, true, false) => square[1..2].to_string(),
+            (true, true, true) => square,
+        }
+    }
+}
+
+/// Drops check marks and annotation suffixes and writes castling with letters, so that SAN can be compared.
+fn normalize_san(san: &str) -> String {
+    san.trim().trim_end_matches(['+', '#', '!', '?']).replace('0', "O")
+}
+
+/// Formats a line of SAN moves with move numbers, e.g. `12. Nf3 Nc6 13. Bb5` or `12... Nc6 13. Bb5` when black moves first.
+pub fn numbered_line(san_moves: &[String], first_move_number: u16, white_moves_first: bool) -> String {
+    let mut text = String::new();
+    let mut move_number = first_move_number;
+    let mut white_to_move = white_moves_first;
+    for (i, san) in san_moves.iter().enumerate() {
+        if white_to_move {
+            text.push_str(&format!("{}. ", move_number));
+        } else if i == 0 {
+            text.push_str(&format!("{}... ", move_number));
+        }
+        text.push_str(san);
+        text.push(' ');
+        if !white_to_move {
+            move_number += 1;
+        }
+        white_to_move = !white_to_move;
+    }
+    text.trim_end().to_string()
+}
+
+/// The number written before the move at `index` of a line, `"12."` for white and `"12..."` for black.
+pub fn move_number_label(index: usize, first_move_number: u16, white_moves_first: bool) -> String {
+    let ply = index + if white_moves_first { 0 } else { 1 };
+    let move_number = first_move_number as usize + ply / 2;
+    if ply.is_multiple_of(2) {
+        format!("{}.", move_number)
+    } else {
+        format!("{}...", move_number)
+    }
+}
+
+fn piece_letter(piece: Piece) -> char {
+    match piece {
+        Piece::Pawn { .. } => 'P',
+        Piece::Knight { .. } => 'N',
+        Piece::Bishop { .. } => 'B',
+        Piece::Rook { .. } => 'R',
+        Piece::Queen { .. } => 'Q',
+        Piece::King { .. } => 'K',
+    }
+}
+
+fn promotion_letter(promotion: PromotionPiece) -> char {
+    match promotion {
+        PromotionPiece::Knight => 'N',
+        PromotionPiece::Bishop => 'B',
+        PromotionPiece::Rook => 'R',
+        PromotionPiece::Queen => 'Q',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn san_round_trip() {
+        for fen in ["rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "r3k2r/ppp2ppp/2n1bn2/3pp3/1b1PP3/2N1BN2/PPP2PPP/R3KB1R w KQkq - 0 8", "4k3/8/8/3N1N2/8/8/8/4K3 w - - 0 1"] {
+            let board = ChessBoard::from_fen(fen).unwrap();
+            for (from, to) in board.all_legal_moves() {
+                let chess_move = ChessMove::new(from, to, None);
+                let san = board.move_to_san(chess_move).unwrap();
+                assert_eq!(board.san_to_move(&san), Some(chess_move), "{} in {}", san, fen);
+            }
+        }
+    }
+
+    #[test]
+    fn san_of_special_moves() {
+        let board = ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(board.move_to_san(ChessMove::from_uci("e1g1").unwrap()).as_deref(), Some("O-O"));
+        assert_eq!(board.move_to_san(ChessMove::from_uci("e1c1").unwrap()).as_deref(), Some("O-O-O"));
+
+        let board = ChessBoard::from_fen("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let promotion = ChessMove::from_uci("b7b8q").unwrap();
+        assert_eq!(board.move_to_san(promotion).as_deref(), Some("b8=Q+"));
+        assert_eq!(board.san_to_move("b8=Q+"), Some(promotion));
+
+        let board = ChessBoard::from_fen("4k3/8/8/3N1N2/8/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(board.move_to_san(ChessMove::from_uci("d5e3").unwrap()).as_deref(), Some("Nde3"));
+    }
+}
@@ -0,0 +1,314 @@
+use std::io::BufRead;
+
+use super::{fen::STARTING_FEN, game_record::MOVE_SUFFIXES, ChessBoard};
+
+/// A move of the movetext in SAN, with its annotations and the variations that could have been played instead of it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PgnMove {
+    /// The move without a suffix such as `!?`, which is read into `nags`.
+    pub san: String,
+    pub nags: Vec<u8>,
+    /// The comments after the move, joined by spaces.
+    pub comment: Option<String>,
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+/// A game read from a PGN file: its tags and the moves of the main line.
+#[derive(Clone, Debug, Default)]
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    pub moves: Vec<PgnMove>,
+    /// The result at the end of the movetext, `1-0`, `0-1`, `1/2-1/2` or `*`.
+    pub result: String,
+}
+
+impl PgnGame {
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reads the games of a PGN file one after another, so that large collections do not have to be held in memory.
+/// Comments and NAGs are skipped.
+pub struct PgnReader<R: BufRead> {
+    lines: std::io::Lines<R>,
+    /// The first tag line of the next game, read while looking for the end of the previous one.
+    pending_line: Option<String>,
+}
+
+impl<R: BufRead> PgnReader<R> {
+    pub fn new(reader: R) -> Self {
+        PgnReader { lines: reader.lines(), pending_line: None }
+    }
+}
+
+impl<R: BufRead> Iterator for PgnReader<R> {
+    type Item = PgnGame;
+
+    fn next(&mut self) -> Option<PgnGame> {
+        let mut game = PgnGame::default();
+        let mut movetext = String::new();
+        loop {
+            let line = match self.pending_line.take() {
+                Some(line) => line,
+                None => match self.lines.next() {
+                    Some(Ok(line)) => line,
+                    Some(Err(_)) | None => break,
+                },
+            };
+            let line = line.trim();
+            if line.starts_with('[') && !movetext.trim().is_empty() {
+                // The tags of the next game start before this one has a result
+                self.pending_line = Some(line.to_string());
+                break;
+            }
+            if line.starts_with('[') {
+                if let Some(tag) = parse_tag(line) {
+                    game.tags.push(tag);
+                }
+                continue;
+            }
+            if line.starts_with('%') {
+                continue;
+            }
+            movetext.push_str(line);
+            movetext.push('\n');
+            if ["1-0", "0-1", "1/2-1/2", "*"].iter().any(|result| line.ends_with(result)) && is_complete(&movetext) {
+                break;
+            }
+        }
+        if game.tags.is_empty() && movetext.trim().is_empty() {
+            return None;
+        }
+        (game.moves, game.result) = parse_movetext(&movetext);
+        if game.result.is_empty() {
+            game.result = game.tag("Result").unwrap_or("*").to_string();
+        }
+        Some(game)
+    }
+}
+
+/// What to look for in a game collection. Empty fields match every game.
+#[derive(Clone, Debug, Default)]
+pub struct GameQuery {
+    /// Part of the name of either player, in any case.
+    pub player: String,
+    /// The start of the date, e.g. `2026` or `2026.10`.
+    pub date: String,
+    /// `1-0`, `0-1` or `1/2-1/2`, or `white`, `black` or `draw`.
+    pub result: String,
+    /// The start of the ECO code, e.g. `B` or `B90`, or part of the opening name.
+    pub eco: String,
+    /// Part of the `Termination` or `TerminationReason` tag, e.g. `time` or `checkmate`.
+    pub termination: String,
+    /// A position reached in the game, compared by piece placement and, when given, side to move.
+    pub fen: String,
+}
+
+impl GameQuery {
+    pub fn is_empty(&self) -> bool {
+        [&self.player, &self.date, &self.result, &self.eco, &self.termination, &self.fen].iter().all(|field| field.trim().is_empty())
+    }
+
+    pub fn matches(&self, game: &PgnGame) -> bool {
+        let contains = |value: Option<&str>, part: &str| value.is_some_and(|value| value.to_lowercase().contains(&part.to_lowercase()));
+        let player = self.player.trim();
+        if !player.is_empty() && !contains(game.tag("White"), player) && !contains(game.tag("Black"), player) {
+            return false;
+        }
+        let date = self.date.trim().replace(['-', '/'], ".");
+        if !date.is_empty() && !game.tag("Date").is_some_and(|game_date| game_date.starts_with(&date)) {
+            return false;
+        }
+        let result = match self.result.trim().to_lowercase().as_str() {
+            "white" => "1-0".to_string(),
+            "black" => "0-1".to_string(),
+            "draw" | "1/2" | "½-½" => "1/2-1/2".to_string(),
+            result => result.to_string(),
+        };
+        if !result.is_empty() && game.result != result {
+            return false;
+        }
+        let eco = self.eco.trim();
+        if !eco.is_empty() && !game.tag("ECO").is_some_and(|code| code.to_uppercase().starts_with(&eco.to_uppercase())) && !contains(game.tag("Opening"), eco) {
+            return false;
+        }
+        let termination = self.termination.trim();
+        if !termination.is_empty() && !contains(game.tag("Termination"), termination) && !contains(game.tag("TerminationReason"), termination) {
+            return false;
+        }
+        self.fen.trim().is_empty() || contains_position(game, &self.fen)
+    }
+}
+
+/// Whether the position of `fen` comes up in the game. Castling rights, en passant and move counters are not compared,
+/// so positions copied from other programs still match.
+fn contains_position(game: &PgnGame, fen: &str) -> bool {
+    let mut fields = fen.split_whitespace();
+    let (Some(placement), side_to_move) = (fields.next(), fields.next()) else {
+        return false;
+    };
+    let is_match = |board: &ChessBoard| {
+        let board_fen = board.to_fen();
+        let mut board_fields = board_fen.split_whitespace();
+        board_fields.next() == Some(placement) && side_to_move.is_none_or(|side| board_fields.next() == Some(side))
+    };
+    let Ok(mut board) = ChessBoard::from_fen(game.tag("FEN").unwrap_or(STARTING_FEN)) else {
+        return false;
+    };
+    if is_match(&board) {
+        return true;
+    }
+    for pgn_move in game.moves.iter() {
+        let Some(chess_move) = board.san_to_move(&pgn_move.san) else {
+            return false;
+        };
+        if board.move_piece(chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion).is_err() {
+            return false;
+        }
+        if is_match(&board) {
+            return true;
+        }
+    }
+    false
+}
+
+/// Reads all games of a PGN text.
+pub fn parse_pgn(text: &str) -> Vec<PgnGame> {
+    PgnReader::new(text.as_bytes()).collect()
+}
+
+/// Reads a tag pair such as `[White "Carlsen, Magnus"]`.
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = inner.split_once(' ')?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((name.to_string(), value.replace("\\\"", "\"").replace("\\\\", "\\")))
+}
+
+/// Whether all comments and variations of the movetext are closed, so a result token at the end of a line really ends the game.
+fn is_complete(movetext: &str) -> bool {
+    let mut depth = 0i32;
+    let mut in_comment = false;
+    for character in movetext.chars() {
+        match character {
+            '{' if !in_comment => in_comment = true,
+            '}' if in_comment => in_comment = false,
+            '(' if !in_comment => depth += 1,
+            ')' if !in_comment => depth -= 1,
+            _ => (),
+        }
+    }
+    depth <= 0 && !in_comment
+}
+
+/// The moves of the main line with their annotations and variations, and the result token. Comments before the first
+/// move of a line are dropped.
+fn parse_movetext(movetext: &str) -> (Vec<PgnMove>, String) {
+    // The main line at the bottom and every variation that is still open above it
+    let mut lines: Vec<Vec<PgnMove>> = vec![Vec::new()];
+    let mut result = String::new();
+    let mut characters = movetext.chars().peekable();
+    let mut token = String::new();
+    while let Some(character) = characters.next() {
+        match character {
+            '{' => {
+                let comment: String = characters.by_ref().take_while(|character| *character != '}').collect();
+                let comment = comment.split_whitespace().collect::<Vec<&str>>().join(" ");
+                if let Some(commented) = lines.last_mut().unwrap().last_mut().filter(|_| !comment.is_empty()) {
+                    commented.comment = Some(match commented.comment.take() {
+                        Some(earlier) => format!("{} {}", earlier, comment),
+                        None => comment,
+                    });
+                }
+            }
+            ';' => {
+                for character in characters.by_ref() {
+                    if character == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' => lines.push(Vec::new()),
+            ')' => close_variation(&mut lines),
+            _ if character.is_whitespace() => (),
+            _ => {
+                token.clear();
+                token.push(character);
+                while let Some(next) = characters.peek().filter(|next| !next.is_whitespace() && !"{}();".contains(**next)) {
+                    token.push(*next);
+                    characters.next();
+                }
+                if let Some(nag) = token.strip_prefix('$') {
+                    if let (Ok(nag), Some(annotated)) = (nag.parse(), lines.last_mut().unwrap().last_mut()) {
+                        annotated.nags.push(nag);
+                    }
+                    continue;
+                }
+                if ["1-0", "0-1", "1/2-1/2", "*"].contains(&token.as_str()) {
+                    if lines.len() == 1 {
+                        result = token.clone();
+                    }
+                    continue;
+                }
+                // Move numbers may be glued to the move, as in `12.Nf3` or `12...Nf3`
+                let san = token.rsplit('.').next().unwrap_or_default();
+                let suffix_start = san.find(['!', '?']).unwrap_or(san.len());
+                let (san, suffix) = san.split_at(suffix_start);
+                if !san.is_empty() && !san.chars().all(|character| character.is_ascii_digit()) {
+                    let nags = MOVE_SUFFIXES.iter().filter(|(symbol, _)| *symbol == suffix).map(|(_, nag)| *nag).collect();
+                    lines.last_mut().unwrap().push(PgnMove { san: san.to_string(), nags, ..Default::default() });
+                }
+            }
+        }
+    }
+    while lines.len() > 1 {
+        close_variation(&mut lines);
+    }
+    (lines.pop().unwrap_or_default(), result)
+}
+
+/// Ends the innermost open variation and attaches it to the move it replaces, the last move of the line around it.
+fn close_variation(lines: &mut Vec<Vec<PgnMove>>) {
+    if lines.len() < 2 {
+        return;
+    }
+    let variation = lines.pop().unwrap();
+    if let Some(replaced) = lines.last_mut().unwrap().last_mut() {
+        if !variation.is_empty() {
+            replaced.variations.push(variation);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAME: &str = "[White \"Magnus\"]\n[Black \"Hikaru\"]\n[Date \"2026.10.18\"]\n[Result \"0-1\"]\n[ECO \"C20\"]\n[Opening \"King's Pawn Game\"]\n[Termination \"normal\"]\n[TerminationReason \"checkmate\"]\n\n1. f3 e5 2. g4 Qh4# 0-1\n";
+
+    fn matches(query: GameQuery) -> bool {
+        query.matches(&parse_pgn(GAME)[0])
+    }
+
+    #[test]
+    fn query_fields_match_the_game() {
+        assert!(matches(GameQuery::default()));
+        assert!(matches(GameQuery { player: "hika".to_string(), ..Default::default() }));
+        assert!(matches(GameQuery { date: "2026-10".to_string(), result: "black".to_string(), ..Default::default() }));
+        assert!(matches(GameQuery { eco: "c".to_string(), termination: "mate".to_string(), ..Default::default() }));
+        assert!(matches(GameQuery { eco: "pawn game".to_string(), ..Default::default() }));
+        assert!(!matches(GameQuery { player: "Fabiano".to_string(), ..Default::default() }));
+        assert!(!matches(GameQuery { date: "2025".to_string(), ..Default::default() }));
+        assert!(!matches(GameQuery { result: "draw".to_string(), ..Default::default() }));
+        assert!(!matches(GameQuery { termination: "time".to_string(), ..Default::default() }));
+    }
+
+    #[test]
+    fn query_finds_positions_reached_in_the_game() {
+        assert!(matches(GameQuery { fen: "rnbqkbnr/pppp1ppp/8/4p3/8/5P2/PPPPP1PP/RNBQKBNR w KQkq e6 0 2".to_string(), ..Default::default() }));
+        assert!(matches(GameQuery { fen: "rnbqkbnr/pppp1ppp/8/4p3/8/5P2/PPPPP1PP/RNBQKBNR".to_string(), ..Default::default() }));
+        assert!(!matches(GameQuery { fen: "rnbqkbnr/pppp1ppp/8/4p3/8/5P2/PPPPP1PP/RNBQKBNR b".to_string(), ..Default::default() }));
+        assert!(!matches(GameQuery { fen: "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1".to_string(), ..Default::default() }));
+    }
+}
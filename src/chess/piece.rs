@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 use super::{
     coordinate::{Coordinate, Vector},
     ChessBoard,
@@ -56,7 +58,8 @@ pub enum CastleType {
     Long,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Hash, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Hash, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Color {
     White,
     Black,
@@ -79,7 +82,17 @@ impl Piece {
             }
         }
 
-        if !ignore_checks {
+        let move_type = match self {
+            Piece::Pawn { color, .. } => Piece::is_legal_pawn_move(from, to, board, *color),
+            Piece::Knight { color } => Piece::is_legal_knight_move(from, to, board, *color),
+            Piece::Bishop { color } => Piece::is_legal_bishop_move(from, to, board, *color),
+            Piece::Rook { color, .. } => Piece::is_legal_rook_move(from, to, board, *color),
+            Piece::Queen { color } => Piece::is_legal_queen_move(from, to, board, *color),
+            Piece::King { color, has_moved } => Piece::is_legal_king_move(from, to, board, *color, *has_moved),
+        };
+
+        // The check test clones the board, so it only runs for moves the piece can make at all
+        if !ignore_checks && move_type != MoveType::Illegal {
             let mut board_after_move = board.clone();
             if let Some(Piece::King { color, .. }) = board.squares[from.x][from.y] {
                 match color {
@@ -88,19 +101,16 @@ impl Piece {
                 }
             }
             board_after_move.squares[to.x][to.y] = board_after_move.squares[from.x][from.y].take();
+            // The pawn taken en passant leaves the rank of the capturing pawn, which can open a line to the king
+            if move_type == MoveType::Capture(CaptureType::EnPassant) {
+                board_after_move.squares[to.x][from.y] = None;
+            }
             if board_after_move.is_in_check(self.get_color()) {
                 return MoveType::Illegal;
             }
         }
 
-        match self {
-            Piece::Pawn { color, .. } => Piece::is_legal_pawn_move(from, to, board, *color),
-            Piece::Knight { color } => Piece::is_legal_knight_move(from, to, board, *color),
-            Piece::Bishop { color } => Piece::is_legal_bishop_move(from, to, board, *color),
-            Piece::Rook { color, .. } => Piece::is_legal_rook_move(from, to, board, *color),
-            Piece::Queen { color } => Piece::is_legal_queen_move(from, to, board, *color),
-            Piece::King { color, has_moved } => Piece::is_legal_king_move(from, to, board, *color, *has_moved),
-        }
+        move_type
     }
 
     fn is_legal_pawn_move(from: Coordinate, to: Coordinate, board: &ChessBoard, color: Color) -> MoveType {
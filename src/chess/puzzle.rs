@@ -0,0 +1,305 @@
+use std::collections::HashSet;
+
+use super::{ChessBoard, ChessMove};
+
+/// Rating used for puzzles without one and for players who have not solved any yet.
+pub const DEFAULT_PUZZLE_RATING: f32 = 1500.0;
+/// How far a single puzzle can move the player's rating.
+const RATING_K_FACTOR: f32 = 32.0;
+/// How many best puzzle rush scores are kept for each duration.
+const KEPT_RUSH_SCORES: usize = 5;
+
+/// A position with a forced line to find.
+#[derive(Clone, Debug)]
+pub struct Puzzle {
+    pub id: String,
+    pub fen: String,
+    /// A move of the opponent that is played before the player's turn, as in the Lichess puzzle database.
+    pub opening_move: Option<ChessMove>,
+    /// The player's moves and the opponent's replies, starting with a move of the player.
+    pub solution: Vec<ChessMove>,
+    pub rating: Option<u32>,
+    pub themes: Vec<String>,
+}
+
+impl Puzzle {
+    /// The board the puzzle starts from, before the opening move.
+    pub fn board(&self) -> Option<ChessBoard> {
+        ChessBoard::from_fen(&self.fen).ok()
+    }
+
+    pub fn rating_or_default(&self) -> f32 {
+        self.rating.map(|rating| rating as f32).unwrap_or(DEFAULT_PUZZLE_RATING)
+    }
+}
+
+/// Reads puzzles in the layout of the Lichess puzzle database: `PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,
+/// NbPlays,Themes,...` with the moves in UCI notation, the first of them being the opponent's opening move. The header
+/// line is optional, and when present its column names are used instead of the usual order. Lines that cannot be read
+/// are skipped.
+pub fn parse_csv(text: &str) -> Vec<Puzzle> {
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty()).peekable();
+    let mut columns = [Some(0), Some(1), Some(2), Some(3), Some(7)];
+    if let Some(header) = lines.peek().filter(|line| line.contains("FEN")) {
+        let header: Vec<&str> = header.split(',').map(str::trim).collect();
+        columns = ["PuzzleId", "FEN", "Moves", "Rating", "Themes"].map(|name| header.iter().position(|column| column.eq_ignore_ascii_case(name)));
+        lines.next();
+    }
+    let [id_column, fen_column, moves_column, rating_column, themes_column] = columns;
+
+    let mut puzzles = Vec::new();
+    for (line_number, line) in lines.enumerate() {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let field = |column: Option<usize>| column.and_then(|column| fields.get(column)).copied().filter(|field| !field.is_empty());
+        let (Some(fen), Some(moves)) = (field(fen_column), field(moves_column)) else {
+            continue;
+        };
+        let Some(mut moves) = moves.split_whitespace().map(ChessMove::from_uci).collect::<Option<Vec<ChessMove>>>() else {
+            continue;
+        };
+        if moves.len() < 2 {
+            continue;
+        }
+        let opening_move = Some(moves.remove(0));
+        let id = field(id_column).map(str::to_string).unwrap_or_else(|| format!("csv-{}", line_number + 1));
+        let themes = field(themes_column).map(|themes| themes.split_whitespace().map(str::to_string).collect()).unwrap_or_default();
+        let puzzle = Puzzle { id, fen: fen.to_string(), opening_move, solution: moves, rating: field(rating_column).and_then(|rating| rating.parse().ok()), themes };
+        if is_playable(&puzzle) {
+            puzzles.push(puzzle);
+        }
+    }
+    puzzles
+}
+
+/// Reads puzzles from Extended Position Description lines. The solution is taken from the `pv` operation, or from `bm`
+/// when there is no `pv`, both in SAN. The optional `id`, `rating` and `themes` operations fill in the rest, e.g.
+/// `r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - bm Qxf7#; id "mate1"; rating 600; themes "mateIn1";`
+pub fn parse_epd(text: &str) -> Vec<Puzzle> {
+    let mut puzzles = Vec::new();
+    for (line_number, line) in text.lines().enumerate() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 5 {
+            continue;
+        }
+        let fen = fields[..4].join(" ");
+        let Ok(board) = ChessBoard::from_fen(&fen) else {
+            continue;
+        };
+
+        let mut id = format!("epd-{}", line_number + 1);
+        let (mut rating, mut themes, mut best_move, mut principal_variation) = (None, Vec::new(), None, None);
+        for operation in fields[4..].join(" ").split(';') {
+            let operation = operation.trim();
+            let (opcode, operand) = operation.split_once(' ').unwrap_or((operation, ""));
+            let operand = operand.trim().trim_matches('"');
+            match opcode {
+                "id" => id = operand.to_string(),
+                "rating" => rating = operand.parse().ok(),
+                "themes" => themes = operand.split_whitespace().map(str::to_string).collect(),
+                "bm" => best_move = operand.split_whitespace().next().map(str::to_string),
+                "pv" => principal_variation = Some(operand.to_string()),
+                _ => (),
+            }
+        }
+
+        let san_moves: Vec<String> = match (principal_variation, best_move) {
+            (Some(principal_variation), _) => principal_variation.split_whitespace().map(str::to_string).collect(),
+            (None, Some(best_move)) => vec![best_move],
+            (None, None) => continue,
+        };
+        let mut line_board = board.clone();
+        let mut solution = Vec::new();
+        for san in san_moves.iter() {
+            let Some(chess_move) = line_board.san_to_move(san) else {
+                break;
+            };
+            if line_board.move_piece(chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion).is_err() {
+                break;
+            }
+            solution.push(chess_move);
+        }
+        if solution.len() == san_moves.len() {
+            puzzles.push(Puzzle { id, fen, opening_move: None, solution, rating, themes });
+        }
+    }
+    puzzles
+}
+
+/// Whether the opening move and the solution can be played from the puzzle's position.
+fn is_playable(puzzle: &Puzzle) -> bool {
+    let Some(mut board) = puzzle.board() else {
+        return false;
+    };
+    puzzle.opening_move.iter().chain(puzzle.solution.iter()).all(|chess_move| board.move_piece(chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion).is_ok())
+}
+
+/// A finished puzzle rush: how many puzzles were solved in the time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RushScore {
+    pub minutes: u32,
+    pub score: u32,
+    /// The day of the rush, as `YYYY.MM.DD`.
+    pub date: String,
+}
+
+/// The puzzle closest to `rating` that is not in `used`.
+pub fn closest_puzzle(puzzles: &[Puzzle], rating: f32, used: &HashSet<usize>) -> Option<usize> {
+    puzzles.iter().enumerate().filter(|(i, _)| !used.contains(i)).min_by(|(_, a), (_, b)| (a.rating_or_default() - rating).abs().total_cmp(&(b.rating_or_default() - rating).abs())).map(|(i, _)| i)
+}
+
+/// The player's puzzle rating, which puzzles they have tried and their best puzzle rush scores, kept between sessions.
+#[derive(Clone, Debug)]
+pub struct PuzzleStats {
+    pub rating: f32,
+    pub solved: HashSet<String>,
+    pub failed: HashSet<String>,
+    /// The puzzle file that was loaded last.
+    pub puzzle_file: Option<String>,
+    /// The best rush scores of every duration, best first.
+    pub rush_scores: Vec<RushScore>,
+}
+
+impl Default for PuzzleStats {
+    fn default() -> Self {
+        PuzzleStats { rating: DEFAULT_PUZZLE_RATING, solved: HashSet::new(), failed: HashSet::new(), puzzle_file: None, rush_scores: Vec::new() }
+    }
+}
+
+impl PuzzleStats {
+    /// Counts a solved or failed puzzle. Only the first try of a puzzle changes the rating.
+    pub fn record_result(&mut self, puzzle: &Puzzle, solved: bool) {
+        if !self.solved.contains(&puzzle.id) && !self.failed.contains(&puzzle.id) {
+            let expected = 1.0 / (1.0 + 10f32.powf((puzzle.rating_or_default() - self.rating) / 400.0));
+            self.rating += RATING_K_FACTOR * (if solved { 1.0 } else { 0.0 } - expected);
+        }
+        if solved {
+            self.failed.remove(&puzzle.id);
+            self.solved.insert(puzzle.id.clone());
+        } else if !self.solved.contains(&puzzle.id) {
+            self.failed.insert(puzzle.id.clone());
+        }
+    }
+
+    /// The untried puzzle closest to the player's rating, or the closest of all puzzles once every one has been tried.
+    /// The puzzle at `skip` is only picked when there is no other.
+    pub fn pick_puzzle(&self, puzzles: &[Puzzle], skip: Option<usize>) -> Option<usize> {
+        let distance = |puzzle: &Puzzle| (puzzle.rating_or_default() - self.rating).abs();
+        let closest = |untried_only: bool| puzzles.iter().enumerate().filter(|(i, puzzle)| Some(*i) != skip && (!untried_only || !self.solved.contains(&puzzle.id) && !self.failed.contains(&puzzle.id))).min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b))).map(|(i, _)| i);
+        closest(true).or_else(|| closest(false)).or(skip.filter(|skip| *skip < puzzles.len()))
+    }
+
+    /// Adds the score to the best scores of its duration if it is good enough, and returns whether it was.
+    pub fn add_rush_score(&mut self, rush_score: RushScore) -> bool {
+        let rank = self.best_rush_scores(rush_score.minutes).iter().filter(|best| best.score >= rush_score.score).count();
+        if rank >= KEPT_RUSH_SCORES {
+            return false;
+        }
+        self.rush_scores.push(rush_score.clone());
+        self.rush_scores.sort_by_key(|best| std::cmp::Reverse(best.score));
+        let mut kept = 0;
+        self.rush_scores.retain(|best| {
+            if best.minutes != rush_score.minutes {
+                return true;
+            }
+            kept += 1;
+            kept <= KEPT_RUSH_SCORES
+        });
+        true
+    }
+
+    pub fn best_rush_scores(&self, minutes: u32) -> Vec<&RushScore> {
+        self.rush_scores.iter().filter(|best| best.minutes == minutes).collect()
+    }
+
+    /// One setting per line, e.g. `rating 1523.4`, `solved 00sHx` or `rush 3 14 2026.10.18`.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("rating {}\n", self.rating);
+        if let Some(puzzle_file) = self.puzzle_file.as_ref() {
+            text.push_str(&format!("file {}\n", puzzle_file));
+        }
+        for id in self.solved.iter() {
+            text.push_str(&format!("solved {}\n", id));
+        }
+        for id in self.failed.iter() {
+            text.push_str(&format!("failed {}\n", id));
+        }
+        for rush_score in self.rush_scores.iter() {
+            text.push_str(&format!("rush {} {} {}\n", rush_score.minutes, rush_score.score, rush_score.date));
+        }
+        text
+    }
+
+    pub fn from_text(text: &str) -> Self {
+        let mut stats = PuzzleStats::default();
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once(' ') else {
+                continue;
+            };
+            match key {
+                "rating" => stats.rating = value.parse().unwrap_or(DEFAULT_PUZZLE_RATING),
+                "file" => stats.puzzle_file = Some(value.to_string()),
+                "solved" => {
+                    stats.solved.insert(value.to_string());
+                }
+                "failed" => {
+                    stats.failed.insert(value.to_string());
+                }
+                "rush" => {
+                    let fields: Vec<&str> = value.split_whitespace().collect();
+                    if let [minutes, score, date] = fields[..] {
+                        if let (Ok(minutes), Ok(score)) = (minutes.parse(), score.parse()) {
+                            stats.rush_scores.push(RushScore { minutes, score, date: date.to_string() });
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_with_and_without_header() {
+        let line = "00sHx,q3k1nr/1pp1nQpp/3p4/1P2p3/4P3/B1PP1b2/B5PP/5K2 b k - 0 17,e8d7 a2e6 d7d8 f7f8,1760,80,83,72,mate mateIn2 middlegame short,https://lichess.org/yyznGmXs/black#34,Italian_Game";
+        let puzzles = parse_csv(line);
+        assert_eq!(puzzles.len(), 1);
+        let puzzle = &puzzles[0];
+        assert_eq!(puzzle.id, "00sHx");
+        assert_eq!(puzzle.opening_move, ChessMove::from_uci("e8d7"));
+        assert_eq!(puzzle.solution, ["a2e6", "d7d8", "f7f8"].map(|uci| ChessMove::from_uci(uci).unwrap()));
+        assert_eq!(puzzle.rating, Some(1760));
+        assert_eq!(puzzle.themes, ["mate", "mateIn2", "middlegame", "short"]);
+
+        let with_header = format!("Moves,FEN,PuzzleId\n{},{},custom\n", "e8d7 a2e6 d7d8 f7f8", puzzle.fen);
+        let puzzles = parse_csv(&with_header);
+        assert_eq!(puzzles.len(), 1);
+        assert_eq!(puzzles[0].id, "custom");
+        assert_eq!(puzzles[0].rating, None);
+    }
+
+    #[test]
+    fn csv_skips_unplayable_lines() {
+        assert!(parse_csv("bad,8/8/4k3/8/8/3K4/8/8 w - - 0 1,e2e4 e7e5,1500").is_empty());
+        assert!(parse_csv("short,8/8/4k3/8/8/3K4/8/8 w - - 0 1,d3d4,1500").is_empty());
+    }
+
+    #[test]
+    fn epd_reads_best_move_and_principal_variation() {
+        let text = "r1bqkb1r/pppp1ppp/2n2n2/4p2Q/2B1P3/8/PPPP1PPP/RNB1K1NR w KQkq - bm Qxf7#; id \"mate1\"; rating 600; themes \"mateIn1\";\n\
+                    6k1/5ppp/8/8/8/8/8/R5K1 w - - pv Ra8#;\n\
+                    6k1/5ppp/8/8/8/8/8/R5K1 w - - bm Rb9;";
+        let puzzles = parse_epd(text);
+        assert_eq!(puzzles.len(), 2);
+        assert_eq!(puzzles[0].id, "mate1");
+        assert_eq!(puzzles[0].solution, vec![ChessMove::from_uci("h5f7").unwrap()]);
+        assert_eq!(puzzles[0].rating, Some(600));
+        assert_eq!(puzzles[0].themes, ["mateIn1"]);
+        assert_eq!(puzzles[1].id, "epd-2");
+        assert_eq!(puzzles[1].solution, vec![ChessMove::from_uci("a1a8").unwrap()]);
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread,
+};
+
+use crate::chess::{
+    chess_board::{ChessPosition, GameStatus},
+    notation, ChessBoard, Color,
+};
+
+use super::search::{SearchInfo, SearchLimits, Searcher};
+
+/// A search result together with its principal variation written in SAN with move numbers.
+#[derive(Clone, Debug)]
+pub struct AnalysisLine {
+    pub info: SearchInfo,
+    pub pv_san: String,
+}
+
+/// The lines found at the latest completed depth, best first. There is always at least one.
+#[derive(Clone, Debug)]
+pub struct Analysis {
+    pub lines: Vec<AnalysisLine>,
+}
+
+impl Analysis {
+    pub fn best(&self) -> &AnalysisLine {
+        &self.lines[0]
+    }
+}
+
+/// Searches `board` on a background thread within `limits`, see `Searcher::search`. The receiver gets the final result
+/// once, or `None` when the side to move has no legal moves.
+pub fn search_in_background(board: &ChessBoard, history: Vec<ChessPosition>, limits: SearchLimits) -> Receiver<Option<SearchInfo>> {
+    let (sender, receiver) = mpsc::channel();
+    let board = board.clone();
+    thread::spawn(move || {
+        let mut searcher = Searcher::new(Arc::new(AtomicBool::new(false)));
+        let _ = sender.send(searcher.search(&board, &history, limits, |_| ()));
+    });
+    receiver
+}
+
+/// Like `search_in_background`, but searches for the opponent's main threat, see `Searcher::search_threat`.
+pub fn search_threat_in_background(board: &ChessBoard, limits: SearchLimits) -> Receiver<Option<SearchInfo>> {
+    let (sender, receiver) = mpsc::channel();
+    let board = board.clone();
+    thread::spawn(move || {
+        let mut searcher = Searcher::new(Arc::new(AtomicBool::new(false)));
+        let _ = sender.send(searcher.search_threat(&board, limits));
+    });
+    receiver
+}
+
+/// Searches a position on a background thread until it is told to look at another one.
+pub struct Analyzer {
+    stop: Arc<AtomicBool>,
+    receiver: Option<Receiver<Analysis>>,
+    latest: Option<Analysis>,
+}
+
+impl Default for Analyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Analyzer {
+    pub fn new() -> Self {
+        Analyzer { stop: Arc::new(AtomicBool::new(false)), receiver: None, latest: None }
+    }
+
+    /// Stops the current search and starts analysing the best `line_count` lines of `board`, reached after the positions
+    /// in `history`. Finished games are not searched.
+    pub fn analyze(&mut self, board: &ChessBoard, history: Vec<ChessPosition>, line_count: usize) {
+        self.stop();
+        if board.game_status != GameStatus::Ongoing {
+            return;
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        let board = board.clone();
+        let thread_stop = Arc::clone(&stop);
+        thread::spawn(move || {
+            let mut searcher = Searcher::new(thread_stop);
+            searcher.search_lines(&board, &history, SearchLimits::infinite(), line_count, |lines| {
+                let lines = lines.iter().map(|info| AnalysisLine { info: info.clone(), pv_san: notation::numbered_line(&board.line_to_san(&info.pv), board.fullmove_number(), board.side_to_move() == Color::White) }).collect();
+                let _ = sender.send(Analysis { lines });
+            });
+        });
+        self.stop = stop;
+        self.receiver = Some(receiver);
+    }
+
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.receiver = None;
+        self.latest = None;
+    }
+
+    /// The deepest result reported so far for the current position.
+    pub fn poll(&mut self) -> Option<&Analysis> {
+        if let Some(receiver) = self.receiver.as_ref() {
+            while let Ok(analysis) = receiver.try_recv() {
+                self.latest = Some(analysis);
+            }
+        }
+        self.latest.as_ref()
+    }
+}
+
+impl Drop for Analyzer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::ChessMove;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn background_search_sends_its_result() {
+        let board = ChessBoard::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+        let info = search_in_background(&board, Vec::new(), SearchLimits::depth(2)).recv_timeout(Duration::from_secs(10)).unwrap().unwrap();
+        assert_eq!(info.best_move(), ChessMove::from_uci("a1a8"));
+    }
+
+    #[test]
+    fn analyzer_reports_lines_until_stopped() {
+        let mut analyzer = Analyzer::new();
+        analyzer.analyze(&ChessBoard::starting_positions(), Vec::new(), 2);
+        let started = Instant::now();
+        while analyzer.poll().is_none() {
+            assert!(started.elapsed() < Duration::from_secs(10), "no analysis arrived");
+            thread::sleep(Duration::from_millis(10));
+        }
+        let analysis = analyzer.poll().unwrap();
+        assert_eq!(analysis.lines.len(), 2);
+        assert!(analysis.best().pv_san.starts_with("1. "));
+
+        analyzer.stop();
+        assert!(analyzer.poll().is_none());
+    }
+}
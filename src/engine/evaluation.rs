@@ -0,0 +1,158 @@
+//! Static evaluation: material, piece-square tables and a bishop pair bonus, blended between middlegame and
+//! endgame by the amount of material left.
+
+use crate::chess::Color;
+
+use super::position::{PieceKind, Position};
+
+const PIECE_VALUES: [i32; 6] = [100, 320, 330, 500, 900, 0];
+const BISHOP_PAIR_BONUS: i32 = 30;
+/// Game phase contributed by each piece kind, 24 with all pieces on the board.
+const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
+const FULL_PHASE: i32 = 24;
+
+// The tables are written from white's point of view with a8 in the top left corner.
+#[rustfmt::skip]
+const PAWN_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i32; 64] = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [i32; 64] = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: [i32; 64] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: [i32; 64] = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const KING_MIDDLEGAME_TABLE: [i32; 64] = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: [i32; 64] = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+pub fn piece_value(kind: PieceKind) -> i32 {
+    PIECE_VALUES[kind as usize]
+}
+
+/// Evaluates the position in centipawns from the point of view of the side to move.
+pub fn evaluate(position: &Position) -> i32 {
+    let mut middlegame = [0; 2];
+    let mut endgame = [0; 2];
+    let mut bishops = [0; 2];
+    let mut phase = 0;
+
+    for square in 0..64u8 {
+        let Some((color, kind)) = position.piece_at(square) else {
+            continue;
+        };
+        let side = color as usize;
+        // Flip the rank for white so that the tables can be read top to bottom
+        let table_index = match color {
+            Color::White => (7 - square as usize / 8) * 8 + square as usize % 8,
+            Color::Black => square as usize,
+        };
+        let value = PIECE_VALUES[kind as usize];
+        phase += PHASE_WEIGHTS[kind as usize];
+        match kind {
+            PieceKind::King => {
+                middlegame[side] += KING_MIDDLEGAME_TABLE[table_index];
+                endgame[side] += KING_ENDGAME_TABLE[table_index];
+            }
+            _ => {
+                let table = match kind {
+                    PieceKind::Pawn => &PAWN_TABLE,
+                    PieceKind::Knight => &KNIGHT_TABLE,
+                    PieceKind::Bishop => &BISHOP_TABLE,
+                    PieceKind::Rook => &ROOK_TABLE,
+                    _ => &QUEEN_TABLE,
+                };
+                middlegame[side] += value + table[table_index];
+                endgame[side] += value + table[table_index];
+                if kind == PieceKind::Bishop {
+                    bishops[side] += 1;
+                }
+            }
+        }
+    }
+    for side in 0..2 {
+        if bishops[side] >= 2 {
+            middlegame[side] += BISHOP_PAIR_BONUS;
+            endgame[side] += BISHOP_PAIR_BONUS;
+        }
+    }
+
+    let phase = phase.min(FULL_PHASE);
+    let white = Color::White as usize;
+    let black = Color::Black as usize;
+    let score = ((middlegame[white] - middlegame[black]) * phase + (endgame[white] - endgame[black]) * (FULL_PHASE - phase)) / FULL_PHASE;
+    match position.side_to_move() {
+        Color::White => score,
+        Color::Black => -score,
+    }
+}
@@ -0,0 +1,12 @@
+//! The chess engine behind analysis and hints. It searches its own copy of a `ChessBoard` position and reports
+//! moves back as `ChessMove`s.
+
+pub mod analysis;
+pub mod evaluation;
+pub mod position;
+pub mod review;
+pub mod search;
+
+pub use analysis::{Analysis, AnalysisLine, Analyzer};
+pub use review::{GameReview, GameReviewer, MoveClassification, MoveReview};
+pub use search::{Score, SearchInfo, SearchLimits, Searcher};
@@ -0,0 +1,525 @@
+//! A compact board used by the search. It mirrors the rules of `ChessBoard` but is cheap to copy, so moves are
+//! played by copying the position instead of undoing them.
+
+use crate::chess::{chess_board::ChessPosition, piece::PromotionPiece, ChessBoard, ChessMove, Color, Coordinate, Piece};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PieceKind {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+impl PieceKind {
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn from_piece(piece: Piece) -> PieceKind {
+        match piece {
+            Piece::Pawn { .. } => PieceKind::Pawn,
+            Piece::Knight { .. } => PieceKind::Knight,
+            Piece::Bishop { .. } => PieceKind::Bishop,
+            Piece::Rook { .. } => PieceKind::Rook,
+            Piece::Queen { .. } => PieceKind::Queen,
+            Piece::King { .. } => PieceKind::King,
+        }
+    }
+}
+
+/// A move between two squares numbered `y * 8 + x`, so a1 is 0 and h8 is 63.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Move {
+    pub from: u8,
+    pub to: u8,
+    pub promotion: Option<PieceKind>,
+}
+
+impl Move {
+    pub fn to_chess_move(self) -> ChessMove {
+        let promotion = match self.promotion {
+            Some(PieceKind::Knight) => Some(PromotionPiece::Knight),
+            Some(PieceKind::Bishop) => Some(PromotionPiece::Bishop),
+            Some(PieceKind::Rook) => Some(PromotionPiece::Rook),
+            Some(PieceKind::Queen) => Some(PromotionPiece::Queen),
+            _ => None,
+        };
+        ChessMove::new(square_coordinate(self.from), square_coordinate(self.to), promotion)
+    }
+
+    pub fn from_chess_move(chess_move: ChessMove) -> Move {
+        let promotion = chess_move.promotion.map(|promotion| match promotion {
+            PromotionPiece::Knight => PieceKind::Knight,
+            PromotionPiece::Bishop => PieceKind::Bishop,
+            PromotionPiece::Rook => PieceKind::Rook,
+            PromotionPiece::Queen => PieceKind::Queen,
+        });
+        Move { from: coordinate_square(chess_move.from), to: coordinate_square(chess_move.to), promotion }
+    }
+}
+
+fn square_coordinate(square: u8) -> Coordinate {
+    Coordinate::new(square as usize % 8, square as usize / 8)
+}
+
+fn coordinate_square(coordinate: Coordinate) -> u8 {
+    (coordinate.y * 8 + coordinate.x) as u8
+}
+
+const WHITE_SHORT: u8 = 1;
+const WHITE_LONG: u8 = 2;
+const BLACK_SHORT: u8 = 4;
+const BLACK_LONG: u8 = 8;
+
+const KNIGHT_STEPS: [(i8, i8); 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const KING_STEPS: [(i8, i8); 8] = [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+const BISHOP_DIRECTIONS: [(i8, i8); 4] = [(1, 1), (-1, 1), (-1, -1), (1, -1)];
+const ROOK_DIRECTIONS: [(i8, i8); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+const fn split_mix(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+const fn zobrist_keys<const N: usize>(seed: u64) -> [u64; N] {
+    let mut keys = [0; N];
+    let mut i = 0;
+    while i < N {
+        keys[i] = split_mix(seed.wrapping_mul(1_000_003).wrapping_add(i as u64));
+        i += 1;
+    }
+    keys
+}
+
+/// One key per color, piece kind and square.
+const PIECE_KEYS: [u64; 2 * 6 * 64] = zobrist_keys(1);
+const BLACK_TO_MOVE_KEY: u64 = split_mix(2);
+const CASTLING_KEYS: [u64; 16] = zobrist_keys(3);
+const EN_PASSANT_KEYS: [u64; 8] = zobrist_keys(4);
+
+fn piece_key(color: Color, kind: PieceKind, square: usize) -> u64 {
+    PIECE_KEYS[(color as usize * 6 + kind.index()) * 64 + square]
+}
+
+/// Hashes the pieces and the side to move, the same things `ChessPosition` compares when `ChessBoard` looks for repetitions.
+pub fn repetition_key(squares: &[[Option<Piece>; 8]; 8], side_to_move: Color) -> u64 {
+    let mut key = if side_to_move == Color::Black { BLACK_TO_MOVE_KEY } else { 0 };
+    for (x, file) in squares.iter().enumerate() {
+        for (y, square) in file.iter().enumerate() {
+            if let Some(piece) = square {
+                key ^= piece_key(piece.get_color(), PieceKind::from_piece(*piece), y * 8 + x);
+            }
+        }
+    }
+    key
+}
+
+pub fn opponent(color: Color) -> Color {
+    match color {
+        Color::White => Color::Black,
+        Color::Black => Color::White,
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Position {
+    squares: [Option<(Color, PieceKind)>; 64],
+    side_to_move: Color,
+    castling: u8,
+    /// The square a pawn can capture onto en passant.
+    en_passant: Option<u8>,
+    halfmove_clock: u8,
+    king_squares: [u8; 2],
+    repetition_key: u64,
+}
+
+impl Position {
+    pub fn from_board(board: &ChessBoard) -> Position {
+        let mut squares = [None; 64];
+        for (x, file) in board.squares.iter().enumerate() {
+            for (y, square) in file.iter().enumerate() {
+                squares[y * 8 + x] = square.map(|piece| (piece.get_color(), PieceKind::from_piece(piece)));
+            }
+        }
+
+        // Castling rights and the en passant square are read back from the FEN, which already derives them from the piece flags
+        let fen = board.to_fen();
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        let mut castling = 0;
+        for (right, flag) in [('K', WHITE_SHORT), ('Q', WHITE_LONG), ('k', BLACK_SHORT), ('q', BLACK_LONG)] {
+            if fields[2].contains(right) {
+                castling |= flag;
+            }
+        }
+        let en_passant = Coordinate::from_algebraic(fields[3]).map(coordinate_square);
+
+        let king_square = |king: &Coordinate| coordinate_square(*king);
+        Position { squares, side_to_move: board.side_to_move(), castling, en_passant, halfmove_clock: board.move_rule_counter, king_squares: [king_square(&board.white_king_position), king_square(&board.black_king_position)], repetition_key: repetition_key(&board.squares, board.side_to_move()) }
+    }
+
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    pub fn piece_at(&self, square: u8) -> Option<(Color, PieceKind)> {
+        self.squares[square as usize]
+    }
+
+    pub fn halfmove_clock(&self) -> u8 {
+        self.halfmove_clock
+    }
+
+    pub fn repetition_key(&self) -> u64 {
+        self.repetition_key
+    }
+
+    /// A key for the full position, including castling rights and en passant, for the transposition table.
+    pub fn key(&self) -> u64 {
+        let en_passant = self.en_passant.map(|square| EN_PASSANT_KEYS[square as usize % 8]).unwrap_or(0);
+        self.repetition_key ^ CASTLING_KEYS[self.castling as usize] ^ en_passant
+    }
+
+    pub fn is_in_check(&self) -> bool {
+        self.is_attacked(self.king_squares[self.side_to_move as usize], opponent(self.side_to_move))
+    }
+
+    pub fn is_attacked(&self, square: u8, by: Color) -> bool {
+        let (x, y) = ((square % 8) as i8, (square / 8) as i8);
+        let pawn_row = if by == Color::White { y - 1 } else { y + 1 };
+        for pawn_x in [x - 1, x + 1] {
+            if on_board(pawn_x, pawn_row) && self.squares[index(pawn_x, pawn_row)] == Some((by, PieceKind::Pawn)) {
+                return true;
+            }
+        }
+        for (steps, kind) in [(&KNIGHT_STEPS, PieceKind::Knight), (&KING_STEPS, PieceKind::King)] {
+            for (dx, dy) in steps.iter() {
+                if on_board(x + dx, y + dy) && self.squares[index(x + dx, y + dy)] == Some((by, kind)) {
+                    return true;
+                }
+            }
+        }
+        for (directions, slider) in [(&BISHOP_DIRECTIONS, PieceKind::Bishop), (&ROOK_DIRECTIONS, PieceKind::Rook)] {
+            for (dx, dy) in directions.iter() {
+                let (mut to_x, mut to_y) = (x + dx, y + dy);
+                while on_board(to_x, to_y) {
+                    if let Some((color, kind)) = self.squares[index(to_x, to_y)] {
+                        if color == by && (kind == slider || kind == PieceKind::Queen) {
+                            return true;
+                        }
+                        break;
+                    }
+                    to_x += dx;
+                    to_y += dy;
+                }
+            }
+        }
+        false
+    }
+
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = Vec::with_capacity(48);
+        self.pseudo_legal_moves(&mut moves, false);
+        moves.retain(|chess_move| self.is_legal(*chess_move));
+        moves
+    }
+
+    /// Whether a pseudo-legal move leaves the own king safe.
+    pub fn is_legal(&self, chess_move: Move) -> bool {
+        !self.make_move(chess_move).left_king_in_check()
+    }
+
+    /// Whether the side that just moved left its own king in check, which makes the move that led here illegal.
+    pub fn left_king_in_check(&self) -> bool {
+        let mover = opponent(self.side_to_move);
+        self.is_attacked(self.king_squares[mover as usize], self.side_to_move)
+    }
+
+    /// Moves that follow the piece movement rules but may leave the own king in check. With `captures_only` only
+    /// captures and promotions are generated.
+    pub fn pseudo_legal_moves(&self, moves: &mut Vec<Move>, captures_only: bool) {
+        let color = self.side_to_move;
+        for from in 0..64u8 {
+            let Some((piece_color, kind)) = self.squares[from as usize] else {
+                continue;
+            };
+            if piece_color != color {
+                continue;
+            }
+            let (x, y) = ((from % 8) as i8, (from / 8) as i8);
+            match kind {
+                PieceKind::Pawn => self.pawn_moves(moves, from, x, y, captures_only),
+                PieceKind::Knight => self.step_moves(moves, from, x, y, &KNIGHT_STEPS, captures_only),
+                PieceKind::King => {
+                    self.step_moves(moves, from, x, y, &KING_STEPS, captures_only);
+                    if !captures_only {
+                        self.castling_moves(moves, from);
+                    }
+                }
+                PieceKind::Bishop => self.slider_moves(moves, from, x, y, &BISHOP_DIRECTIONS, captures_only),
+                PieceKind::Rook => self.slider_moves(moves, from, x, y, &ROOK_DIRECTIONS, captures_only),
+                PieceKind::Queen => {
+                    self.slider_moves(moves, from, x, y, &BISHOP_DIRECTIONS, captures_only);
+                    self.slider_moves(moves, from, x, y, &ROOK_DIRECTIONS, captures_only);
+                }
+            }
+        }
+    }
+
+    fn pawn_moves(&self, moves: &mut Vec<Move>, from: u8, x: i8, y: i8, captures_only: bool) {
+        let color = self.side_to_move;
+        let (forward, start_row, last_row) = if color == Color::White { (1, 1, 7) } else { (-1, 6, 0) };
+        let push = |moves: &mut Vec<Move>, to: u8| {
+            if (to / 8) as i8 == last_row {
+                for promotion in [PieceKind::Queen, PieceKind::Knight, PieceKind::Rook, PieceKind::Bishop] {
+                    moves.push(Move { from, to, promotion: Some(promotion) });
+                }
+            } else {
+                moves.push(Move { from, to, promotion: None });
+            }
+        };
+
+        // Only reachable from a board with a pawn on its last rank, which `from_fen` refuses
+        if !on_board(x, y + forward) {
+            return;
+        }
+        let one_step = index(x, y + forward);
+        if self.squares[one_step].is_none() {
+            if !captures_only || y + forward == last_row {
+                push(moves, one_step as u8);
+            }
+            let two_steps = y + 2 * forward;
+            if !captures_only && y == start_row && self.squares[index(x, two_steps)].is_none() {
+                moves.push(Move { from, to: index(x, two_steps) as u8, promotion: None });
+            }
+        }
+        for to_x in [x - 1, x + 1] {
+            if !on_board(to_x, y + forward) {
+                continue;
+            }
+            let to = index(to_x, y + forward);
+            let is_capture = matches!(self.squares[to], Some((target, _)) if target != color);
+            if is_capture || self.en_passant == Some(to as u8) {
+                push(moves, to as u8);
+            }
+        }
+    }
+
+    fn step_moves(&self, moves: &mut Vec<Move>, from: u8, x: i8, y: i8, steps: &[(i8, i8)], captures_only: bool) {
+        for (dx, dy) in steps {
+            let (to_x, to_y) = (x + dx, y + dy);
+            if !on_board(to_x, to_y) {
+                continue;
+            }
+            let to = index(to_x, to_y);
+            match self.squares[to] {
+                None if !captures_only => moves.push(Move { from, to: to as u8, promotion: None }),
+                Some((color, _)) if color != self.side_to_move => moves.push(Move { from, to: to as u8, promotion: None }),
+                _ => (),
+            }
+        }
+    }
+
+    fn slider_moves(&self, moves: &mut Vec<Move>, from: u8, x: i8, y: i8, directions: &[(i8, i8)], captures_only: bool) {
+        for (dx, dy) in directions {
+            let (mut to_x, mut to_y) = (x + dx, y + dy);
+            while on_board(to_x, to_y) {
+                let to = index(to_x, to_y);
+                match self.squares[to] {
+                    None => {
+                        if !captures_only {
+                            moves.push(Move { from, to: to as u8, promotion: None });
+                        }
+                    }
+                    Some((color, _)) => {
+                        if color != self.side_to_move {
+                            moves.push(Move { from, to: to as u8, promotion: None });
+                        }
+                        break;
+                    }
+                }
+                to_x += dx;
+                to_y += dy;
+            }
+        }
+    }
+
+    fn castling_moves(&self, moves: &mut Vec<Move>, from: u8) {
+        let (short, long, rank) = match self.side_to_move {
+            Color::White => (WHITE_SHORT, WHITE_LONG, 0),
+            Color::Black => (BLACK_SHORT, BLACK_LONG, 56),
+        };
+        if from != rank + 4 || self.castling & (short | long) == 0 {
+            return;
+        }
+        let enemy = opponent(self.side_to_move);
+        if self.is_attacked(from, enemy) {
+            return;
+        }
+        let is_empty = |squares: &[u8]| squares.iter().all(|square| self.squares[(rank + square) as usize].is_none());
+        let is_safe = |squares: &[u8]| squares.iter().all(|square| !self.is_attacked(rank + square, enemy));
+        if self.castling & short != 0 && is_empty(&[5, 6]) && is_safe(&[5, 6]) {
+            moves.push(Move { from, to: rank + 6, promotion: None });
+        }
+        if self.castling & long != 0 && is_empty(&[1, 2, 3]) && is_safe(&[2, 3]) {
+            moves.push(Move { from, to: rank + 2, promotion: None });
+        }
+    }
+
+    /// Plays a pseudo-legal move on a copy of the position.
+    pub fn make_move(&self, chess_move: Move) -> Position {
+        let mut position = *self;
+        let (from, to) = (chess_move.from as usize, chess_move.to as usize);
+        let Some((color, kind)) = self.squares[from] else {
+            return position;
+        };
+
+        position.halfmove_clock = self.halfmove_clock.saturating_add(1);
+        if let Some((captured_color, captured_kind)) = self.squares[to] {
+            position.repetition_key ^= piece_key(captured_color, captured_kind, to);
+            position.halfmove_clock = 0;
+        }
+        position.remove_piece(from);
+        position.en_passant = None;
+
+        let mut placed_kind = kind;
+        match kind {
+            PieceKind::Pawn => {
+                position.halfmove_clock = 0;
+                if self.en_passant == Some(to as u8) {
+                    let captured = if color == Color::White { to - 8 } else { to + 8 };
+                    position.remove_piece(captured);
+                }
+                if from.abs_diff(to) == 16 {
+                    position.en_passant = Some(((from + to) / 2) as u8);
+                }
+                if let Some(promotion) = chess_move.promotion {
+                    placed_kind = promotion;
+                }
+            }
+            PieceKind::King => {
+                position.king_squares[color as usize] = to as u8;
+                if from.abs_diff(to) == 2 {
+                    let (rook_from, rook_to) = if to > from { (from + 3, from + 1) } else { (from - 4, from - 1) };
+                    position.remove_piece(rook_from);
+                    position.place_piece(rook_to, color, PieceKind::Rook);
+                }
+            }
+            _ => (),
+        }
+        position.squares[to] = None;
+        position.place_piece(to, color, placed_kind);
+
+        for (square, rights) in [(0, WHITE_LONG), (4, WHITE_SHORT | WHITE_LONG), (7, WHITE_SHORT), (56, BLACK_LONG), (60, BLACK_SHORT | BLACK_LONG), (63, BLACK_SHORT)] {
+            if from == square || to == square {
+                position.castling &= !rights;
+            }
+        }
+
+        position.side_to_move = opponent(color);
+        position.repetition_key ^= BLACK_TO_MOVE_KEY;
+        position
+    }
+
+    /// Hands the move to the opponent without moving, used to ask what the opponent would do if it were their turn.
+    pub fn pass(&self) -> Position {
+        let mut position = *self;
+        position.en_passant = None;
+        position.side_to_move = opponent(self.side_to_move);
+        position.repetition_key ^= BLACK_TO_MOVE_KEY;
+        position
+    }
+
+    fn remove_piece(&mut self, square: usize) {
+        if let Some((color, kind)) = self.squares[square].take() {
+            self.repetition_key ^= piece_key(color, kind, square);
+        }
+    }
+
+    fn place_piece(&mut self, square: usize, color: Color, kind: PieceKind) {
+        self.squares[square] = Some((color, kind));
+        self.repetition_key ^= piece_key(color, kind, square);
+    }
+}
+
+fn on_board(x: i8, y: i8) -> bool {
+    (0..8).contains(&x) && (0..8).contains(&y)
+}
+
+fn index(x: i8, y: i8) -> usize {
+    (y * 8 + x) as usize
+}
+
+/// Repetition keys of the positions a game went through, oldest first.
+pub fn history_keys(history: &[ChessPosition]) -> Vec<u64> {
+    history.iter().map(|position| repetition_key(&position.squares, position.side_to_move())).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sort_key(chess_move: &Move) -> (u8, u8, Option<usize>) {
+        (chess_move.from, chess_move.to, chess_move.promotion.map(PieceKind::index))
+    }
+
+    /// The moves of `ChessBoard`, with one move per promotion piece like the generator of `Position`.
+    fn board_moves(board: &ChessBoard) -> Vec<Move> {
+        let mut moves = Vec::new();
+        for (from, to) in board.all_legal_moves() {
+            let from_square = coordinate_square(from);
+            let to_square = coordinate_square(to);
+            if matches!(board.squares[from.x][from.y], Some(Piece::Pawn { .. })) && (to.y == 0 || to.y == 7) {
+                for promotion in [PieceKind::Queen, PieceKind::Knight, PieceKind::Rook, PieceKind::Bishop] {
+                    moves.push(Move { from: from_square, to: to_square, promotion: Some(promotion) });
+                }
+            } else {
+                moves.push(Move { from: from_square, to: to_square, promotion: None });
+            }
+        }
+        moves.sort_by_key(sort_key);
+        moves
+    }
+
+    /// Counts the leaves `depth` moves deep, checking at every node that both move generators agree.
+    fn perft(board: &ChessBoard, position: &Position, depth: u32) -> u64 {
+        let expected = board_moves(board);
+        let mut moves = position.legal_moves();
+        moves.sort_by_key(sort_key);
+        assert_eq!(moves, expected, "{}", board.to_fen());
+        if depth == 1 {
+            return moves.len() as u64;
+        }
+        moves.iter().map(|chess_move| {
+            let mut next_board = board.clone();
+            let chess_move_on_board = chess_move.to_chess_move();
+            next_board.move_piece(chess_move_on_board.from_tuple(), chess_move_on_board.to_tuple(), chess_move_on_board.promotion).unwrap();
+            perft(&next_board, &position.make_move(*chess_move), depth - 1)
+        }).sum()
+    }
+
+    fn perft_of(fen: &str, depth: u32) -> u64 {
+        let board = ChessBoard::from_fen(fen).unwrap();
+        perft(&board, &Position::from_board(&board), depth)
+    }
+
+    #[test]
+    fn perft_of_the_starting_position() {
+        assert_eq!(perft_of("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 3), 8902);
+    }
+
+    #[test]
+    fn perft_of_kiwipete() {
+        assert_eq!(perft_of("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", 2), 2039);
+    }
+
+    #[test]
+    fn perft_with_en_passant_and_promotions() {
+        assert_eq!(perft_of("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 3), 2812);
+        assert_eq!(perft_of("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 2), 264);
+    }
+}
@@ -0,0 +1,344 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::chess::{
+    chess_board::{ChessPosition, GameStatus},
+    game_record::{Accuracy, GameRecord, MoveAnnotation, NAG_BLUNDER, NAG_DUBIOUS, NAG_MISTAKE},
+    ChessBoard, ChessMove, Color,
+};
+
+use super::search::{Score, SearchLimits, Searcher};
+
+/// How long each position of a game is searched during a review.
+pub const REVIEW_LIMITS: SearchLimits = SearchLimits { max_depth: 12, max_time: Some(Duration::from_millis(300)) };
+/// Evaluations are capped at this many centipawns before comparing them, so that giving away part of a won position is not a blunder.
+const EVALUATION_CAP: i32 = 1000;
+const INACCURACY_DROP: i32 = 50;
+const MISTAKE_DROP: i32 = 100;
+const BLUNDER_DROP: i32 = 300;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MoveClassification {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl MoveClassification {
+    /// Classifies a move by how many centipawns the mover lost with it.
+    pub fn from_drop(drop: i32) -> Option<MoveClassification> {
+        match drop {
+            drop if drop >= BLUNDER_DROP => Some(MoveClassification::Blunder),
+            drop if drop >= MISTAKE_DROP => Some(MoveClassification::Mistake),
+            drop if drop >= INACCURACY_DROP => Some(MoveClassification::Inaccuracy),
+            _ => None,
+        }
+    }
+
+    pub fn nag(self) -> u8 {
+        match self {
+            MoveClassification::Inaccuracy => NAG_DUBIOUS,
+            MoveClassification::Mistake => NAG_MISTAKE,
+            MoveClassification::Blunder => NAG_BLUNDER,
+        }
+    }
+
+    /// The move suffix, e.g. `??` for a blunder.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            MoveClassification::Inaccuracy => "?!",
+            MoveClassification::Mistake => "?",
+            MoveClassification::Blunder => "??",
+        }
+    }
+}
+
+impl fmt::Display for MoveClassification {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            MoveClassification::Inaccuracy => "Inaccuracy",
+            MoveClassification::Mistake => "Mistake",
+            MoveClassification::Blunder => "Blunder",
+        };
+        write!(formatter, "{}", name)
+    }
+}
+
+/// How one move of a game compares to the engine's choice.
+#[derive(Clone, Debug)]
+pub struct MoveReview {
+    pub played: ChessMove,
+    pub played_san: String,
+    pub color: Color,
+    /// Evaluation of the position before the move, the best the mover could reach.
+    pub evaluation_before: Score,
+    pub evaluation_after: Score,
+    /// The engine's choice, when it differs from the played move.
+    pub best_move: Option<ChessMove>,
+    pub best_move_san: Option<String>,
+    /// Centipawns the mover lost with the move, never negative.
+    pub drop: i32,
+    pub classification: Option<MoveClassification>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GameReview {
+    pub moves: Vec<MoveReview>,
+    /// The index of the first move that could not be played. The review stops before it.
+    pub unplayable_move: Option<usize>,
+}
+
+impl GameReview {
+    /// How many moves of `color` got the classification.
+    pub fn count(&self, color: Color, classification: MoveClassification) -> usize {
+        self.moves.iter().filter(|review| review.color == color && review.classification == Some(classification)).count()
+    }
+
+    /// The evaluation of every position of the game from white's point of view, starting with the position before the first move.
+    pub fn evaluations(&self) -> Vec<Score> {
+        self.moves.first().map(|review| review.evaluation_before).into_iter().chain(self.moves.iter().map(|review| review.evaluation_after)).collect()
+    }
+
+    /// The accuracy and average centipawn loss of `color`'s moves. Each move scores by how much it lowered the mover's
+    /// winning chances, and the accuracy is the average of those scores.
+    pub fn accuracy(&self, color: Color) -> Accuracy {
+        let moves: Vec<&MoveReview> = self.moves.iter().filter(|review| review.color == color).collect();
+        if moves.is_empty() {
+            return Accuracy { accuracy: 100.0, average_centipawn_loss: 0.0 };
+        }
+        let move_accuracy = |review: &MoveReview| {
+            if review.drop == 0 {
+                return 100.0;
+            }
+            let winning_chance = |score: Score| 100.0 * match color {
+                Color::White => score.white_expectation(),
+                Color::Black => 1.0 - score.white_expectation(),
+            };
+            let lost = (winning_chance(review.evaluation_before) - winning_chance(review.evaluation_after)).max(0.0);
+            (103.1668 * (-0.04354 * lost).exp() - 3.1669).clamp(0.0, 100.0)
+        };
+        let accuracy = moves.iter().map(|review| move_accuracy(review)).sum::<f32>() / moves.len() as f32;
+        let average_centipawn_loss = moves.iter().map(|review| review.drop as f32).sum::<f32>() / moves.len() as f32;
+        Accuracy { accuracy, average_centipawn_loss }
+    }
+
+    /// Adds the classifications as NAGs and the better moves as comments to the moves of `record`, and stores the
+    /// accuracy of both sides.
+    pub fn annotate(&self, record: &mut GameRecord) {
+        record.accuracy = Some([self.accuracy(Color::White), self.accuracy(Color::Black)]);
+        record.annotations.resize(record.moves.len(), MoveAnnotation::default());
+        for (annotation, review) in record.annotations.iter_mut().zip(self.moves.iter()) {
+            let Some(classification) = review.classification else {
+                continue;
+            };
+            annotation.nags.retain(|nag| ![NAG_MISTAKE, NAG_BLUNDER, NAG_DUBIOUS].contains(nag));
+            annotation.nags.push(classification.nag());
+            annotation.comment = Some(match review.best_move_san.as_ref() {
+                Some(best_move) => format!("{}. {} was best ({}).", classification, best_move, review.evaluation_before),
+                None => format!("{}.", classification),
+            });
+        }
+    }
+}
+
+/// Reviews a game on a background thread, one position after another.
+pub struct GameReviewer {
+    stop: Arc<AtomicBool>,
+    receiver: Receiver<ReviewProgress>,
+    reviewed: usize,
+    total: usize,
+    review: Option<GameReview>,
+}
+
+enum ReviewProgress {
+    Reviewed(usize),
+    Done(GameReview),
+}
+
+impl GameReviewer {
+    pub fn start(record: &GameRecord) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        let (board, moves) = (record.starting_board(), record.moves.clone());
+        let total = moves.len();
+        let thread_stop = Arc::clone(&stop);
+        thread::spawn(move || {
+            let review = review_game(board, &moves, thread_stop, |reviewed| {
+                let _ = sender.send(ReviewProgress::Reviewed(reviewed));
+            });
+            if let Some(review) = review {
+                let _ = sender.send(ReviewProgress::Done(review));
+            }
+        });
+        GameReviewer { stop, receiver, reviewed: 0, total, review: None }
+    }
+
+    /// How many moves have been reviewed and how many there are.
+    pub fn progress(&mut self) -> (usize, usize) {
+        self.poll();
+        (self.reviewed, self.total)
+    }
+
+    /// The finished review.
+    pub fn review(&mut self) -> Option<&GameReview> {
+        self.poll();
+        self.review.as_ref()
+    }
+
+    fn poll(&mut self) {
+        while let Ok(progress) = self.receiver.try_recv() {
+            match progress {
+                ReviewProgress::Reviewed(reviewed) => self.reviewed = reviewed,
+                ReviewProgress::Done(review) => {
+                    self.reviewed = self.total;
+                    self.review = Some(review);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for GameReviewer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Evaluates every position of the game and compares each move with the engine's best move. Calls `on_progress`
+/// with the number of reviewed moves. Stops at the first move that cannot be played and returns `None` when stopped.
+fn review_game(mut board: ChessBoard, moves: &[ChessMove], stop: Arc<AtomicBool>, mut on_progress: impl FnMut(usize)) -> Option<GameReview> {
+    let mut searcher = Searcher::new(Arc::clone(&stop));
+    let mut history = Vec::new();
+    let (mut evaluation, mut best_move) = evaluate(&mut searcher, &board, &history);
+    let mut reviews = Vec::new();
+    for (i, chess_move) in moves.iter().enumerate() {
+        let color = board.side_to_move();
+        let Some(played_san) = board.move_to_san(*chess_move) else {
+            return Some(GameReview { moves: reviews, unplayable_move: Some(i) });
+        };
+        let best_move_san = best_move.filter(|best_move| best_move != chess_move).and_then(|best_move| board.move_to_san(best_move));
+        let position = ChessPosition::new(&board);
+        if board.move_piece_with_history(&history, chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion).is_err() {
+            return Some(GameReview { moves: reviews, unplayable_move: Some(i) });
+        }
+        history.push(position);
+
+        let (evaluation_after, next_best_move) = evaluate(&mut searcher, &board, &history);
+        if stop.load(Ordering::Relaxed) {
+            return None;
+        }
+        // Playing the engine's own move never counts as a loss, even when a deeper search of the next position disagrees
+        let drop = if best_move_san.is_none() { 0 } else { evaluation_drop(color, evaluation, evaluation_after) };
+        reviews.push(MoveReview { played: *chess_move, played_san, color, evaluation_before: evaluation, evaluation_after, best_move: best_move_san.as_ref().and(best_move), best_move_san, drop, classification: MoveClassification::from_drop(drop) });
+        (evaluation, best_move) = (evaluation_after, next_best_move);
+        on_progress(i + 1);
+    }
+    Some(GameReview { moves: reviews, unplayable_move: None })
+}
+
+/// The evaluation of the board from white's point of view and the best move, if the game is not over.
+fn evaluate(searcher: &mut Searcher, board: &ChessBoard, history: &[ChessPosition]) -> (Score, Option<ChessMove>) {
+    match board.game_status {
+        GameStatus::Win(Color::White, _) => (Score::Centipawns(EVALUATION_CAP), None),
+        GameStatus::Win(Color::Black, _) => (Score::Centipawns(-EVALUATION_CAP), None),
+        GameStatus::Draw(_) => (Score::Centipawns(0), None),
+        GameStatus::Ongoing => match searcher.search(board, history, REVIEW_LIMITS, |_| ()) {
+            Some(info) => (info.score, info.best_move()),
+            None => (Score::Centipawns(0), None),
+        },
+    }
+}
+
+/// How many centipawns `color` lost by going from `before` to `after`.
+fn evaluation_drop(color: Color, before: Score, after: Score) -> i32 {
+    let capped = |score: Score| score.centipawns().clamp(-EVALUATION_CAP, EVALUATION_CAP);
+    let drop = capped(before) - capped(after);
+    match color {
+        Color::White => drop.max(0),
+        Color::Black => (-drop).max(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::game_record::NAG_GOOD;
+
+    fn move_review(uci: &str, color: Color, evaluation_before: i32, evaluation_after: i32, best_move_san: Option<&str>) -> MoveReview {
+        let drop = evaluation_drop(color, Score::Centipawns(evaluation_before), Score::Centipawns(evaluation_after));
+        MoveReview { played: ChessMove::from_uci(uci).unwrap(), played_san: String::new(), color, evaluation_before: Score::Centipawns(evaluation_before), evaluation_after: Score::Centipawns(evaluation_after), best_move: None, best_move_san: best_move_san.map(str::to_string), drop, classification: MoveClassification::from_drop(drop) }
+    }
+
+    #[test]
+    fn classification_thresholds() {
+        assert_eq!(MoveClassification::from_drop(0), None);
+        assert_eq!(MoveClassification::from_drop(INACCURACY_DROP - 1), None);
+        assert_eq!(MoveClassification::from_drop(INACCURACY_DROP), Some(MoveClassification::Inaccuracy));
+        assert_eq!(MoveClassification::from_drop(MISTAKE_DROP - 1), Some(MoveClassification::Inaccuracy));
+        assert_eq!(MoveClassification::from_drop(MISTAKE_DROP), Some(MoveClassification::Mistake));
+        assert_eq!(MoveClassification::from_drop(BLUNDER_DROP - 1), Some(MoveClassification::Mistake));
+        assert_eq!(MoveClassification::from_drop(BLUNDER_DROP), Some(MoveClassification::Blunder));
+    }
+
+    #[test]
+    fn drops_are_seen_from_the_mover() {
+        assert_eq!(evaluation_drop(Color::White, Score::Centipawns(100), Score::Centipawns(-200)), 300);
+        assert_eq!(evaluation_drop(Color::White, Score::Centipawns(-200), Score::Centipawns(100)), 0);
+        assert_eq!(evaluation_drop(Color::Black, Score::Centipawns(100), Score::Centipawns(-200)), 0);
+        assert_eq!(evaluation_drop(Color::Black, Score::Centipawns(-100), Score::Centipawns(250)), 350);
+        // Giving away part of a won position is capped
+        assert_eq!(evaluation_drop(Color::White, Score::Mate(3), Score::Centipawns(900)), 100);
+        assert_eq!(evaluation_drop(Color::White, Score::Centipawns(2500), Score::Centipawns(1200)), 0);
+    }
+
+    #[test]
+    fn annotate_marks_classified_moves() {
+        let mut record = GameRecord::new("Test", "A", "B");
+        record.moves = ["e2e4", "e7e5", "g1f3"].map(|uci| ChessMove::from_uci(uci).unwrap()).to_vec();
+        record.annotations = vec![MoveAnnotation { nags: vec![NAG_GOOD], comment: None, shapes: Vec::new() }, MoveAnnotation { nags: vec![NAG_DUBIOUS], comment: None, shapes: Vec::new() }];
+        let review = GameReview { moves: vec![move_review("e2e4", Color::White, 20, 30, None), move_review("e7e5", Color::Black, 30, 400, Some("c5")), move_review("g1f3", Color::White, 400, 280, None)], unplayable_move: None };
+
+        review.annotate(&mut record);
+        assert_eq!(record.annotations.len(), 3);
+        assert_eq!(record.annotations[0], MoveAnnotation { nags: vec![NAG_GOOD], comment: None, shapes: Vec::new() });
+        assert_eq!(record.annotations[1].nags, vec![NAG_BLUNDER]);
+        assert_eq!(record.annotations[1].comment.as_deref(), Some("Blunder. c5 was best (+0.30)."));
+        assert_eq!(record.annotations[2].nags, vec![NAG_MISTAKE]);
+        assert_eq!(record.annotations[2].comment.as_deref(), Some("Mistake."));
+        assert!(record.accuracy.is_some());
+        let pgn = record.to_pgn();
+        assert!(pgn.contains("[BlackACPL \"370\"]"));
+        assert!(pgn.contains("1. e4 $1 e5 $4 {Blunder. c5 was best (+0.30).} 2. Nf3 $2 {Mistake.} *"));
+    }
+
+    #[test]
+    fn review_stops_at_an_unplayable_move() {
+        let moves = ["e2e4", "e7e5", "e2e4", "g1f3"].map(|uci| ChessMove::from_uci(uci).unwrap());
+        let review = review_game(ChessBoard::starting_positions(), &moves, Arc::new(AtomicBool::new(false)), |_| ()).unwrap();
+        assert_eq!(review.unplayable_move, Some(2));
+        assert_eq!(review.moves.len(), 2);
+        assert_eq!(review.moves[1].played_san, "e5");
+    }
+
+    #[test]
+    fn accuracy_averages_the_centipawn_loss() {
+        let review = GameReview { moves: vec![move_review("e2e4", Color::White, 20, 30, None), move_review("c7c5", Color::Black, 30, 330, Some("e5")), move_review("g1f3", Color::White, 330, 210, Some("d4")), move_review("d7d6", Color::Black, 210, 210, None), move_review("d2d4", Color::White, 210, 180, Some("Bb5"))], unplayable_move: None };
+        let white = review.accuracy(Color::White);
+        let black = review.accuracy(Color::Black);
+        assert_eq!(white.average_centipawn_loss, 50.0);
+        assert_eq!(black.average_centipawn_loss, 150.0);
+        assert!(white.accuracy > black.accuracy && black.accuracy > 0.0 && white.accuracy < 100.0);
+
+        let perfect = GameReview { moves: vec![move_review("e2e4", Color::White, 20, 30, None)], unplayable_move: None };
+        assert_eq!(perfect.accuracy(Color::White), Accuracy { accuracy: 100.0, average_centipawn_loss: 0.0 });
+        assert_eq!(perfect.accuracy(Color::Black), Accuracy { accuracy: 100.0, average_centipawn_loss: 0.0 });
+    }
+}
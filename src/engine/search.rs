@@ -0,0 +1,511 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::chess::{chess_board::ChessPosition, ChessBoard, ChessMove, Color};
+
+use super::{
+    evaluation::{evaluate, piece_value},
+    position::{self, Move, PieceKind, Position},
+};
+
+/// Score of a side that is mated right now. Mates further away score a little less per ply.
+pub const MATE: i32 = 30_000;
+const INFINITY: i32 = 32_000;
+const MAX_PLY: usize = 128;
+pub const MAX_DEPTH: u8 = 64;
+const TABLE_SIZE: usize = 1 << 18;
+/// How many nodes are searched between checks of the stop flag and the deadline.
+const STOP_CHECK_INTERVAL: u64 = 1024;
+
+/// An evaluation from white's point of view.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Score {
+    Centipawns(i32),
+    /// Moves until mate, negative when white is getting mated.
+    Mate(i32),
+}
+
+impl Score {
+    fn from_search(score: i32, side_to_move: Color) -> Score {
+        let score = match side_to_move {
+            Color::White => score,
+            Color::Black => -score,
+        };
+        if score.abs() >= MATE - MAX_PLY as i32 {
+            let plies = MATE - score.abs();
+            let moves = (plies + 1) / 2;
+            Score::Mate(if score > 0 { moves } else { -moves })
+        } else {
+            Score::Centipawns(score)
+        }
+    }
+
+    /// The score in centipawns, with mates counted as a large advantage so that scores can be compared and drawn.
+    pub fn centipawns(self) -> i32 {
+        match self {
+            Score::Centipawns(centipawns) => centipawns,
+            Score::Mate(moves) if moves > 0 => 10_000 - moves,
+            Score::Mate(moves) => -10_000 - moves,
+        }
+    }
+
+    /// White's expected share of the points, from 0 to 1, on the usual logistic curve over centipawns.
+    pub fn white_expectation(self) -> f32 {
+        match self {
+            Score::Mate(moves) => if moves > 0 { 1.0 } else { 0.0 },
+            Score::Centipawns(centipawns) => 1.0 / (1.0 + 10f32.powf(-centipawns as f32 / 400.0)),
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Score::Centipawns(centipawns) => write!(formatter, "{:+.2}", *centipawns as f32 / 100.0),
+            Score::Mate(moves) => write!(formatter, "#{}", moves),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct SearchLimits {
+    pub max_depth: u8,
+    pub max_time: Option<Duration>,
+}
+
+impl SearchLimits {
+    pub fn depth(max_depth: u8) -> Self {
+        SearchLimits { max_depth, max_time: None }
+    }
+
+    pub fn time(max_time: Duration) -> Self {
+        SearchLimits { max_depth: MAX_DEPTH, max_time: Some(max_time) }
+    }
+
+    /// Searches until the stop flag is set.
+    pub fn infinite() -> Self {
+        SearchLimits { max_depth: MAX_DEPTH, max_time: None }
+    }
+}
+
+/// The result of one completed iteration of the search.
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+    pub depth: u8,
+    pub score: Score,
+    /// The principal variation, the line both sides are expected to play, starting with the best move.
+    pub pv: Vec<ChessMove>,
+    pub nodes: u64,
+}
+
+impl SearchInfo {
+    pub fn best_move(&self) -> Option<ChessMove> {
+        self.pv.first().copied()
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Copy, Clone)]
+struct TableEntry {
+    key: u64,
+    best_move: Option<Move>,
+    score: i32,
+    depth: i32,
+    bound: Bound,
+}
+
+/// Iterative deepening alpha-beta search with quiescence search and a transposition table.
+pub struct Searcher {
+    table: Vec<Option<TableEntry>>,
+    killers: [[Option<Move>; 2]; MAX_PLY],
+    pv_lines: Vec<Vec<Move>>,
+    /// Repetition keys of the game so far followed by the positions on the current search path.
+    repetition_keys: Vec<u64>,
+    nodes: u64,
+    stop: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+    aborted: bool,
+    /// Moves skipped at the root, so that the search finds the best line that does not start with one of them.
+    excluded_root_moves: Vec<Move>,
+}
+
+impl Searcher {
+    /// Creates a searcher that gives up as soon as `stop` is set.
+    pub fn new(stop: Arc<AtomicBool>) -> Self {
+        Searcher { table: vec![None; TABLE_SIZE], killers: [[None; 2]; MAX_PLY], pv_lines: vec![Vec::new(); MAX_PLY + 1], repetition_keys: Vec::new(), nodes: 0, stop, deadline: None, aborted: false, excluded_root_moves: Vec::new() }
+    }
+
+    /// Searches the board with increasing depth and calls `on_depth` after every completed depth. `history` holds the
+    /// positions of the game before the board's, oldest first, so that repeating one counts as a draw. Returns the last
+    /// completed result, or `None` when the side to move has no legal moves.
+    pub fn search(&mut self, board: &ChessBoard, history: &[ChessPosition], limits: SearchLimits, mut on_depth: impl FnMut(&SearchInfo)) -> Option<SearchInfo> {
+        self.search_lines(board, history, limits, 1, |lines| on_depth(&lines[0])).into_iter().next()
+    }
+
+    /// Like `search`, but finds the best `line_count` lines that start with different moves (MultiPV), best first.
+    pub fn search_lines(&mut self, board: &ChessBoard, history: &[ChessPosition], limits: SearchLimits, line_count: usize, on_depth: impl FnMut(&[SearchInfo])) -> Vec<SearchInfo> {
+        self.search_position(Position::from_board(board), position::history_keys(history), limits, line_count, on_depth)
+    }
+
+    /// Searches for the opponent's main threat: their best move if the side to move could pass. Returns `None` when the
+    /// side to move is in check, since passing would leave the king to be captured.
+    pub fn search_threat(&mut self, board: &ChessBoard, limits: SearchLimits) -> Option<SearchInfo> {
+        let position = Position::from_board(board);
+        if position.is_in_check() {
+            return None;
+        }
+        self.search_position(position.pass(), Vec::new(), limits, 1, |_| ()).into_iter().next()
+    }
+
+    fn search_position(&mut self, root: Position, history_keys: Vec<u64>, limits: SearchLimits, line_count: usize, mut on_depth: impl FnMut(&[SearchInfo])) -> Vec<SearchInfo> {
+        let line_count = line_count.clamp(1, root.legal_moves().len().max(1));
+        if root.legal_moves().is_empty() {
+            return Vec::new();
+        }
+        self.repetition_keys = history_keys;
+        self.repetition_keys.push(root.repetition_key());
+        self.nodes = 0;
+        self.aborted = false;
+        self.deadline = limits.max_time.map(|max_time| Instant::now() + max_time);
+
+        let mut result: Vec<SearchInfo> = Vec::new();
+        for depth in 1..=limits.max_depth.min(MAX_DEPTH) {
+            // Every further line is searched with the first moves of the better lines left out
+            let mut lines = Vec::new();
+            self.excluded_root_moves.clear();
+            for _ in 0..line_count {
+                let score = self.negamax(&root, depth as i32, -INFINITY, INFINITY, 0);
+                if self.aborted && (!result.is_empty() || !lines.is_empty()) {
+                    break;
+                }
+                let pv = self.principal_variation(&root, depth as usize);
+                let Some(first_move) = pv.first().copied() else {
+                    break;
+                };
+                self.excluded_root_moves.push(first_move);
+                lines.push((score, SearchInfo { depth, score: Score::from_search(score, root.side_to_move()), pv: pv.iter().map(|chess_move| chess_move.to_chess_move()).collect(), nodes: self.nodes }));
+                if self.aborted {
+                    break;
+                }
+            }
+            self.excluded_root_moves.clear();
+            if self.aborted && !result.is_empty() {
+                break;
+            }
+
+            lines.sort_by_key(|(score, _)| -score);
+            result = lines.into_iter().map(|(_, info)| info).collect();
+            if result.is_empty() {
+                break;
+            }
+            on_depth(&result);
+            if self.aborted || matches!(result[0].score, Score::Mate(moves) if (moves.unsigned_abs() as u8) * 2 <= depth) && line_count == 1 {
+                break;
+            }
+        }
+        result
+    }
+
+    /// The line collected during the search, extended with transposition table moves where it was cut short.
+    fn principal_variation(&self, root: &Position, length: usize) -> Vec<Move> {
+        let mut line = self.pv_lines[0].clone();
+        let mut position = *root;
+        for chess_move in line.iter() {
+            position = position.make_move(*chess_move);
+        }
+        while line.len() < length {
+            let Some(entry) = self.probe(position.key()) else {
+                break;
+            };
+            let Some(chess_move) = entry.best_move.filter(|chess_move| position.legal_moves().contains(chess_move)) else {
+                break;
+            };
+            line.push(chess_move);
+            position = position.make_move(chess_move);
+        }
+        line
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if self.aborted {
+            return true;
+        }
+        if self.nodes.is_multiple_of(STOP_CHECK_INTERVAL) && (self.stop.load(Ordering::Relaxed) || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)) {
+            self.aborted = true;
+        }
+        self.aborted
+    }
+
+    fn is_repetition(&self, position: &Position) -> bool {
+        let key = position.repetition_key();
+        let earlier = &self.repetition_keys[..self.repetition_keys.len() - 1];
+        earlier.iter().rev().take(position.halfmove_clock() as usize).any(|earlier_key| *earlier_key == key)
+    }
+
+    fn probe(&self, key: u64) -> Option<TableEntry> {
+        self.table[key as usize % TABLE_SIZE].filter(|entry| entry.key == key)
+    }
+
+    fn store(&mut self, key: u64, best_move: Option<Move>, score: i32, depth: i32, bound: Bound, ply: usize) {
+        // Mate scores are stored relative to the position instead of the root
+        let score = if score >= MATE - MAX_PLY as i32 {
+            score + ply as i32
+        } else if score <= -MATE + MAX_PLY as i32 {
+            score - ply as i32
+        } else {
+            score
+        };
+        self.table[key as usize % TABLE_SIZE] = Some(TableEntry { key, best_move, score, depth, bound });
+    }
+
+    fn negamax(&mut self, position: &Position, depth: i32, mut alpha: i32, mut beta: i32, ply: usize) -> i32 {
+        self.pv_lines[ply].clear();
+        if self.should_stop() {
+            return 0;
+        }
+        self.nodes += 1;
+
+        if ply > 0 {
+            if position.halfmove_clock() >= 100 || self.is_repetition(position) {
+                return 0;
+            }
+            // A mate found closer to the root can not be improved on
+            alpha = alpha.max(-MATE + ply as i32);
+            beta = beta.min(MATE - ply as i32 - 1);
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
+        let in_check = position.is_in_check();
+        let depth = if in_check { depth + 1 } else { depth };
+        if depth <= 0 {
+            return self.quiescence(position, alpha, beta, ply);
+        }
+        if ply >= MAX_PLY - 1 {
+            return evaluate(position);
+        }
+
+        let key = position.key();
+        let entry = self.probe(key);
+        if let Some(entry) = entry.filter(|entry| ply > 0 && entry.depth >= depth) {
+            let score = if entry.score >= MATE - MAX_PLY as i32 {
+                entry.score - ply as i32
+            } else if entry.score <= -MATE + MAX_PLY as i32 {
+                entry.score + ply as i32
+            } else {
+                entry.score
+            };
+            match entry.bound {
+                Bound::Exact => return score,
+                Bound::Lower if score >= beta => return score,
+                Bound::Upper if score <= alpha => return score,
+                _ => (),
+            }
+        }
+
+        let mut moves = Vec::with_capacity(48);
+        position.pseudo_legal_moves(&mut moves, false);
+        self.order_moves(position, &mut moves, entry.and_then(|entry| entry.best_move), ply);
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        let mut legal_moves = 0;
+        for chess_move in moves {
+            if ply == 0 && self.excluded_root_moves.contains(&chess_move) {
+                continue;
+            }
+            let child = position.make_move(chess_move);
+            if child.left_king_in_check() {
+                continue;
+            }
+            legal_moves += 1;
+
+            self.repetition_keys.push(child.repetition_key());
+            let mut score;
+            if legal_moves == 1 {
+                score = -self.negamax(&child, depth - 1, -beta, -alpha, ply + 1);
+            } else {
+                // Later moves are first only checked against the best line found so far
+                score = -self.negamax(&child, depth - 1, -alpha - 1, -alpha, ply + 1);
+                if score > alpha && score < beta {
+                    score = -self.negamax(&child, depth - 1, -beta, -alpha, ply + 1);
+                }
+            }
+            self.repetition_keys.pop();
+            if self.aborted {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(chess_move);
+                if score > alpha {
+                    alpha = score;
+                    let mut line = vec![chess_move];
+                    line.append(&mut self.pv_lines[ply + 1]);
+                    self.pv_lines[ply] = line;
+                    if alpha >= beta {
+                        if position.piece_at(chess_move.to).is_none() && self.killers[ply][0] != Some(chess_move) {
+                            self.killers[ply] = [Some(chess_move), self.killers[ply][0]];
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+
+        if legal_moves == 0 {
+            return if in_check { -MATE + ply as i32 } else { 0 };
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        if ply > 0 || self.excluded_root_moves.is_empty() {
+            self.store(key, best_move, best_score, depth, bound, ply);
+        }
+        best_score
+    }
+
+    /// Only looks at captures and promotions until the position is quiet, so that the evaluation is not taken in the
+    /// middle of an exchange.
+    fn quiescence(&mut self, position: &Position, mut alpha: i32, beta: i32, ply: usize) -> i32 {
+        self.pv_lines[ply].clear();
+        if self.should_stop() {
+            return 0;
+        }
+        self.nodes += 1;
+
+        let stand_pat = evaluate(position);
+        if stand_pat >= beta || ply >= MAX_PLY - 1 {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut moves = Vec::with_capacity(16);
+        position.pseudo_legal_moves(&mut moves, true);
+        self.order_moves(position, &mut moves, None, ply);
+
+        let mut best_score = stand_pat;
+        for chess_move in moves {
+            let child = position.make_move(chess_move);
+            if child.left_king_in_check() {
+                continue;
+            }
+            let score = -self.quiescence(&child, -beta, -alpha, ply + 1);
+            if self.aborted {
+                return 0;
+            }
+            if score > best_score {
+                best_score = score;
+                if score > alpha {
+                    alpha = score;
+                    if alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+        best_score
+    }
+
+    /// Puts the transposition table move first, then captures of valuable pieces by cheap ones, then killer moves.
+    fn order_moves(&self, position: &Position, moves: &mut [Move], table_move: Option<Move>, ply: usize) {
+        moves.sort_by_cached_key(|chess_move| {
+            if Some(*chess_move) == table_move {
+                return i32::MIN;
+            }
+            let attacker = position.piece_at(chess_move.from).map(|(_, kind)| piece_value(kind)).unwrap_or(0);
+            let mut order = 0;
+            if let Some((_, victim)) = position.piece_at(chess_move.to) {
+                order -= 100_000 + piece_value(victim) * 10 - attacker;
+            }
+            if chess_move.promotion == Some(PieceKind::Queen) {
+                order -= 90_000;
+            }
+            if order == 0 {
+                if self.killers[ply][0] == Some(*chess_move) {
+                    order = -80_000;
+                } else if self.killers[ply][1] == Some(*chess_move) {
+                    order = -70_000;
+                }
+            }
+            order
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::mpsc, thread};
+
+    fn search(fen: &str, max_depth: u8) -> SearchInfo {
+        let mut searcher = Searcher::new(Arc::new(AtomicBool::new(false)));
+        searcher.search(&ChessBoard::from_fen(fen).unwrap(), &[], SearchLimits::depth(max_depth), |_| ()).unwrap()
+    }
+
+    #[test]
+    fn mate_in_one_is_found() {
+        let info = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 2);
+        assert_eq!(info.best_move(), ChessMove::from_uci("a1a8"));
+        assert_eq!(info.score, Score::Mate(1));
+
+        let info = search("r5k1/8/8/8/8/8/5PPP/6K1 b - - 0 1", 2);
+        assert_eq!(info.best_move(), ChessMove::from_uci("a8a1"));
+        assert_eq!(info.score, Score::Mate(-1));
+    }
+
+    #[test]
+    fn no_result_without_legal_moves() {
+        let mut searcher = Searcher::new(Arc::new(AtomicBool::new(false)));
+        let board = ChessBoard::from_fen("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(searcher.search(&board, &[], SearchLimits::depth(3), |_| ()).is_none());
+    }
+
+    #[test]
+    fn lines_start_with_different_moves_best_first() {
+        let mut searcher = Searcher::new(Arc::new(AtomicBool::new(false)));
+        let lines = searcher.search_lines(&ChessBoard::starting_positions(), &[], SearchLimits::depth(3), 4, |_| ());
+        assert_eq!(lines.len(), 4);
+        let first_moves: Vec<ChessMove> = lines.iter().map(|line| line.best_move().unwrap()).collect();
+        for (i, first_move) in first_moves.iter().enumerate() {
+            assert!(!first_moves[..i].contains(first_move));
+        }
+        assert!(lines.windows(2).all(|pair| pair[0].score.centipawns() >= pair[1].score.centipawns()));
+    }
+
+    #[test]
+    fn stop_flag_ends_an_infinite_search() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        let thread_stop = Arc::clone(&stop);
+        thread::spawn(move || {
+            let mut searcher = Searcher::new(thread_stop);
+            let _ = sender.send(searcher.search(&ChessBoard::starting_positions(), &[], SearchLimits::infinite(), |_| ()));
+        });
+        thread::sleep(Duration::from_millis(50));
+        stop.store(true, Ordering::Relaxed);
+        let info = receiver.recv_timeout(Duration::from_secs(10)).expect("the search did not stop").unwrap();
+        assert!(info.best_move().is_some());
+        assert!(info.depth < MAX_DEPTH);
+    }
+}
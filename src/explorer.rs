@@ -0,0 +1,316 @@
+//! The opening explorer: which moves were played from a position in a local PGN collection, and how those games ended.
+
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::PathBuf,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use crate::{
+    chess::{
+        pgn::{PgnGame, PgnReader},
+        ChessBoard, ChessMove,
+    },
+    engine::position::{Move, PieceKind, Position},
+    store,
+};
+
+/// Only this many plies of each game are indexed, which covers the opening and keeps the index small.
+pub const MAX_INDEXED_PLIES: usize = 30;
+/// How often an import reports how many games it has read.
+const PROGRESS_INTERVAL: u32 = 5000;
+
+/// How the games that reached a position with a move ended.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MoveStats {
+    pub white_wins: u32,
+    pub draws: u32,
+    pub black_wins: u32,
+}
+
+impl MoveStats {
+    pub fn games(&self) -> u32 {
+        self.white_wins + self.draws + self.black_wins
+    }
+
+    /// White wins, draws and black wins in percent.
+    pub fn percentages(&self) -> (f32, f32, f32) {
+        let games = self.games().max(1) as f32;
+        (100.0 * self.white_wins as f32 / games, 100.0 * self.draws as f32 / games, 100.0 * self.black_wins as f32 / games)
+    }
+
+    fn add(&mut self, other: MoveStats) {
+        self.white_wins += other.white_wins;
+        self.draws += other.draws;
+        self.black_wins += other.black_wins;
+    }
+}
+
+/// A move played from the explored position.
+#[derive(Clone, Debug)]
+pub struct Continuation {
+    pub chess_move: ChessMove,
+    pub san: String,
+    pub stats: MoveStats,
+}
+
+/// One move from one position, with the results of the games that played it.
+#[derive(Copy, Clone, Debug)]
+struct IndexEntry {
+    /// The position hash, see `Position::repetition_key`.
+    key: u64,
+    chess_move: u16,
+    stats: MoveStats,
+}
+
+const ENTRY_BYTES: usize = 8 + 2 + 3 * 4;
+/// The first bytes of an index file, followed by `INDEX_VERSION`.
+const INDEX_MAGIC: &[u8; 4] = b"RCOI";
+/// Changes whenever the layout of the index file changes, so older files are not misread.
+const INDEX_VERSION: u16 = 1;
+
+/// Moves and results of indexed games, sorted by position hash so the moves of a position are found by binary search.
+#[derive(Clone, Debug, Default)]
+pub struct OpeningIndex {
+    entries: Vec<IndexEntry>,
+    pub games: u32,
+}
+
+impl OpeningIndex {
+    /// Every move played from the position of `board`, most played first.
+    pub fn continuations(&self, board: &ChessBoard) -> Vec<Continuation> {
+        let key = Position::from_board(board).repetition_key();
+        let start = self.entries.partition_point(|entry| entry.key < key);
+        let mut continuations: Vec<Continuation> = self.entries[start..].iter().take_while(|entry| entry.key == key).filter_map(|entry| {
+            let chess_move = decode_move(entry.chess_move).to_chess_move();
+            board.move_to_san(chess_move).map(|san| Continuation { chess_move, san, stats: entry.stats })
+        }).collect();
+        continuations.sort_by_key(|continuation| std::cmp::Reverse(continuation.stats.games()));
+        continuations
+    }
+
+    /// Indexes finished games. Games without a result are skipped, and a game stops counting at its first move that
+    /// cannot be read. Calls `on_progress` with the number of games read so far.
+    pub fn add_games(&mut self, games: impl Iterator<Item = PgnGame>, mut on_progress: impl FnMut(u32)) {
+        let start = Position::from_board(&ChessBoard::starting_positions());
+        let mut new_entries = Vec::new();
+        let mut read = 0;
+        for game in games {
+            read += 1;
+            if read % PROGRESS_INTERVAL == 0 {
+                on_progress(read);
+            }
+            let stats = match game.result.as_str() {
+                "1-0" => MoveStats { white_wins: 1, ..Default::default() },
+                "0-1" => MoveStats { black_wins: 1, ..Default::default() },
+                "1/2-1/2" => MoveStats { draws: 1, ..Default::default() },
+                _ => continue,
+            };
+            let mut position = match game.tag("FEN").map(ChessBoard::from_fen) {
+                Some(Ok(board)) => Position::from_board(&board),
+                Some(Err(_)) => continue,
+                None => start,
+            };
+            for pgn_move in game.moves.iter().take(MAX_INDEXED_PLIES) {
+                let Some(chess_move) = san_to_move(&position, &pgn_move.san) else {
+                    break;
+                };
+                new_entries.push(IndexEntry { key: position.repetition_key(), chess_move: encode_move(chess_move), stats });
+                position = position.make_move(chess_move);
+            }
+            self.games += 1;
+        }
+        on_progress(read);
+
+        new_entries.append(&mut self.entries);
+        new_entries.sort_unstable_by_key(|entry| (entry.key, entry.chess_move));
+        for entry in new_entries {
+            match self.entries.last_mut() {
+                Some(last) if last.key == entry.key && last.chess_move == entry.chess_move => last.stats.add(entry.stats),
+                _ => self.entries.push(entry),
+            }
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(INDEX_MAGIC.len() + 2 + 4 + self.entries.len() * ENTRY_BYTES);
+        bytes.extend_from_slice(INDEX_MAGIC);
+        bytes.extend_from_slice(&INDEX_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.games.to_le_bytes());
+        for entry in self.entries.iter() {
+            bytes.extend_from_slice(&entry.key.to_le_bytes());
+            bytes.extend_from_slice(&entry.chess_move.to_le_bytes());
+            for count in [entry.stats.white_wins, entry.stats.draws, entry.stats.black_wins] {
+                bytes.extend_from_slice(&count.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Reads an index written by `to_bytes`. Returns `None` when the bytes are cut off, are not an index or were written
+    /// by another version.
+    pub fn from_bytes(bytes: &[u8]) -> Option<OpeningIndex> {
+        let (version, bytes) = bytes.strip_prefix(INDEX_MAGIC)?.split_first_chunk::<2>()?;
+        if u16::from_le_bytes(*version) != INDEX_VERSION {
+            return None;
+        }
+        let (games, entries) = bytes.split_first_chunk::<4>()?;
+        if entries.len() % ENTRY_BYTES != 0 {
+            return None;
+        }
+        let u32_at = |chunk: &[u8], offset: usize| u32::from_le_bytes(chunk[offset..offset + 4].try_into().unwrap());
+        let entries = entries.chunks_exact(ENTRY_BYTES).map(|chunk| IndexEntry { key: u64::from_le_bytes(chunk[0..8].try_into().unwrap()), chess_move: u16::from_le_bytes(chunk[8..10].try_into().unwrap()), stats: MoveStats { white_wins: u32_at(chunk, 10), draws: u32_at(chunk, 14), black_wins: u32_at(chunk, 18) } }).collect();
+        Some(OpeningIndex { entries, games: u32::from_le_bytes(*games) })
+    }
+}
+
+pub enum ImportProgress {
+    /// How many games have been read so far.
+    Read(u32),
+    /// The index with the imported games added, already saved to the data directory.
+    Done(Result<OpeningIndex, String>),
+}
+
+/// Adds the games of a PGN file to `index`, or to the saved index when it is `None`, on a background thread and saves
+/// the result.
+pub fn import_in_background(path: PathBuf, index: Option<OpeningIndex>) -> Receiver<ImportProgress> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut index = index.unwrap_or_else(store::load_opening_index);
+        let result = File::open(&path).map_err(|error| format!("Could not read {}: {}", path.display(), error)).and_then(|file| {
+            index.add_games(PgnReader::new(BufReader::new(file)), |read| {
+                let _ = sender.send(ImportProgress::Read(read));
+            });
+            store::save_opening_index(&index).map_err(|error: io::Error| format!("Could not save the opening index: {}", error))?;
+            Ok(index)
+        });
+        let _ = sender.send(ImportProgress::Done(result));
+    });
+    receiver
+}
+
+/// Packs a move into 16 bits: the from square, the to square and the promotion piece.
+fn encode_move(chess_move: Move) -> u16 {
+    let promotion = match chess_move.promotion {
+        Some(PieceKind::Knight) => 1,
+        Some(PieceKind::Bishop) => 2,
+        Some(PieceKind::Rook) => 3,
+        Some(PieceKind::Queen) => 4,
+        _ => 0,
+    };
+    chess_move.from as u16 | (chess_move.to as u16) << 6 | promotion << 12
+}
+
+fn decode_move(encoded: u16) -> Move {
+    let promotion = match encoded >> 12 {
+        1 => Some(PieceKind::Knight),
+        2 => Some(PieceKind::Bishop),
+        3 => Some(PieceKind::Rook),
+        4 => Some(PieceKind::Queen),
+        _ => None,
+    };
+    Move { from: (encoded & 63) as u8, to: (encoded >> 6 & 63) as u8, promotion }
+}
+
+/// Reads a SAN move on the engine's position, which is much faster than `ChessBoard::san_to_move` for large imports.
+fn san_to_move(position: &Position, san: &str) -> Option<Move> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']).replace('0', "O");
+    // Only the moves that fit the SAN are checked for legality
+    let mut moves = Vec::with_capacity(48);
+    position.pseudo_legal_moves(&mut moves, false);
+    let is_king_move = |chess_move: &Move| position.piece_at(chess_move.from).is_some_and(|(_, kind)| kind == PieceKind::King);
+    match san.as_str() {
+        "O-O" => return moves.into_iter().find(|chess_move| is_king_move(chess_move) && chess_move.to == chess_move.from + 2 && position.is_legal(*chess_move)),
+        "O-O-O" => return moves.into_iter().find(|chess_move| is_king_move(chess_move) && chess_move.from == chess_move.to + 2 && position.is_legal(*chess_move)),
+        _ => (),
+    }
+
+    let (san, promotion) = match san.split_once('=') {
+        Some((san, promotion)) => (san.to_string(), piece_kind(promotion.chars().next()?)),
+        None => (san.clone(), None),
+    };
+    let mut characters: Vec<char> = san.chars().filter(|character| *character != 'x').collect();
+    let kind = match characters.first() {
+        Some(letter) if letter.is_ascii_uppercase() => piece_kind(characters.remove(0))?,
+        _ => PieceKind::Pawn,
+    };
+    if characters.len() < 2 {
+        return None;
+    }
+    let destination: String = characters.split_off(characters.len() - 2).into_iter().collect();
+    let to = square(&destination)?;
+    let (from_file, from_rank) = characters.iter().fold((None, None), |(file, rank), character| match character {
+        'a'..='h' => (Some(*character as u8 - b'a'), rank),
+        '1'..='8' => (file, Some(*character as u8 - b'1')),
+        _ => (file, rank),
+    });
+
+    moves.into_iter().find(|chess_move| {
+        chess_move.to == to && chess_move.promotion == promotion && position.piece_at(chess_move.from).is_some_and(|(_, piece)| piece == kind) && from_file.is_none_or(|file| chess_move.from % 8 == file) && from_rank.is_none_or(|rank| chess_move.from / 8 == rank) && position.is_legal(*chess_move)
+    })
+}
+
+fn piece_kind(letter: char) -> Option<PieceKind> {
+    match letter {
+        'N' => Some(PieceKind::Knight),
+        'B' => Some(PieceKind::Bishop),
+        'R' => Some(PieceKind::Rook),
+        'Q' => Some(PieceKind::Queen),
+        'K' => Some(PieceKind::King),
+        _ => None,
+    }
+}
+
+fn square(name: &str) -> Option<u8> {
+    let bytes = name.as_bytes();
+    match bytes {
+        [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Some((rank - b'1') * 8 + (file - b'a')),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::pgn;
+
+    fn index() -> OpeningIndex {
+        let games = "[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 1-0\n\n[Result \"1/2-1/2\"]\n\n1. e4 c5 1/2-1/2\n\n[Result \"0-1\"]\n\n1. d4 d5 0-1\n\n[Result \"*\"]\n\n1. c4 *\n";
+        let mut index = OpeningIndex::default();
+        index.add_games(pgn::parse_pgn(games).into_iter(), |_| ());
+        index
+    }
+
+    #[test]
+    fn continuations_count_finished_games() {
+        let index = index();
+        assert_eq!(index.games, 3);
+        let continuations = index.continuations(&ChessBoard::starting_positions());
+        let summary: Vec<(&str, MoveStats)> = continuations.iter().map(|continuation| (continuation.san.as_str(), continuation.stats)).collect();
+        assert_eq!(summary, vec![("e4", MoveStats { white_wins: 1, draws: 1, black_wins: 0 }), ("d4", MoveStats { white_wins: 0, draws: 0, black_wins: 1 })]);
+    }
+
+    #[test]
+    fn index_bytes_round_trip() {
+        let index = index();
+        let read = OpeningIndex::from_bytes(&index.to_bytes()).unwrap();
+        assert_eq!(read.games, index.games);
+        assert_eq!(read.to_bytes(), index.to_bytes());
+        let board = ChessBoard::starting_positions();
+        let (ours, theirs) = (index.continuations(&board), read.continuations(&board));
+        assert_eq!(ours.iter().map(|continuation| (continuation.chess_move, continuation.stats)).collect::<Vec<_>>(), theirs.iter().map(|continuation| (continuation.chess_move, continuation.stats)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn index_bytes_reject_unreadable_files() {
+        let bytes = index().to_bytes();
+        assert!(OpeningIndex::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+        assert!(OpeningIndex::from_bytes(&bytes[INDEX_MAGIC.len() + 2..]).is_none());
+        let mut other_version = bytes.clone();
+        other_version[INDEX_MAGIC.len()..INDEX_MAGIC.len() + 2].copy_from_slice(&(INDEX_VERSION + 1).to_le_bytes());
+        assert!(OpeningIndex::from_bytes(&other_version).is_none());
+        assert!(OpeningIndex::from_bytes(&[]).is_none());
+    }
+}
@@ -0,0 +1,5 @@
+pub mod chess;
+pub mod engine;
+pub mod explorer;
+pub mod network;
+pub mod store;
@@ -1,23 +1,55 @@
 //#![windows_subsystem = "windows"]
 
-pub mod chess;
 pub mod ui;
-use std::process::exit;
+use std::{
+    process::exit,
+    sync::mpsc::{Receiver, TryRecvError},
+    time::Duration,
+};
 
-use chess::ChessBoard;
+use chess::{
+    chess_board::GameStatus,
+    game_record::{self, GameRecord},
+    game_tree::GameTree,
+    ChessBoard, ChessMove, Coordinate,
+};
 use draw::WindowParameters;
 use macroquad::prelude::*;
-use ui::{draw, layouts, ui_chess_board::UIChessBoard};
+use engine::{SearchInfo, SearchLimits};
+use rusty_chess::{chess, engine, explorer, network, store};
+use store::{CurrentGame, GameMode};
+use ui::{
+    analysis_screen::AnalysisScreen,
+    draw,
+    games_screen::{GameRequest, GamesScreen},
+    layouts,
+    online_screen::OnlineScreen,
+    puzzle_screen::PuzzleScreen,
+    replay_screen::ReplayScreen,
+    review_screen::ReviewScreen,
+    ui_chess_board::UIChessBoard,
+    ui_manager::{Title, UIManager},
+};
+
+/// How long the engine thinks about a hint. Hints are only offered in the game against yourself: online games are
+/// played against another person and the bot screen has no game to play yet.
+const HINT_SEARCH_TIME: Duration = Duration::from_millis(1500);
 
 fn window_conf() -> Conf {
     Conf { window_title: "Rusty Chess".to_owned(), window_width: 1600, window_height: 900, icon: None, window_resizable: true, fullscreen: true, ..Default::default() }
 }
 
+#[derive(Copy, Clone, PartialEq)]
 pub enum GameState {
     Menu,
     AgainstYourself,
     AgainstBot,
     Online,
+    Analysis,
+    Review,
+    Puzzles,
+    Games,
+    Replay,
 }
 
 #[macroquad::main(window_conf)]
@@ -32,15 +64,49 @@ async fn main() {
 
     let textures = ui::ui_chess_board::load_piece_textures().await;
 
+    let mut settings = store::load_settings();
+
     let mut ui_chess_board = UIChessBoard::new(0.05, 0.05555555555, 0.5, &board.squares, &window_parameters.aspect_ratio_number, chess::Color::White, textures);
+    ui_chess_board.show_legal_moves = settings.show_legal_moves;
 
-    let mut main_menu = layouts::main_menu();
+    let mut main_menu = layouts::main_menu(settings.show_legal_moves);
+
+    // A game that was still going when the app was last closed or crashed
+    let mut unfinished_game = store::load_current_game();
+    if let Some(current_game) = unfinished_game.as_ref() {
+        let description = match current_game.mode {
+            GameMode::AgainstYourself => format!("Unfinished game against yourself, {} moves", current_game.record.moves.len().div_ceil(2)),
+            GameMode::Online => format!("Unfinished online game, {} vs {}", current_game.record.white, current_game.record.black),
+        };
+        layouts::add_resume_prompt(&mut main_menu, &description);
+    }
 
     let mut against_yourself = layouts::against_yourself();
 
+    let mut record = new_against_yourself_record();
+
+    // How many moves are played on the board while an earlier position is previewed from the move list
+    let mut preview: Option<usize> = None;
+
+    let mut hint_search: Option<Receiver<Option<SearchInfo>>> = None;
+
     let mut against_bot = layouts::against_bot();
 
-    let mut online = layouts::online();
+    let mut online = OnlineScreen::new(ui_chess_board.clone());
+
+    let mut analysis = AnalysisScreen::new(ui_chess_board.clone());
+
+    let mut review = ReviewScreen::new(ui_chess_board.clone());
+
+    // The screens the review and the analysis were opened from, which their Back buttons return to
+    let mut review_return_state = GameState::AgainstYourself;
+    let mut analysis_return_state = GameState::Menu;
+
+    let mut puzzles = PuzzleScreen::new(ui_chess_board.clone());
+
+    let mut games = GamesScreen::new(ui_chess_board.clone());
+
+    let mut replay = ReplayScreen::new(ui_chess_board.clone());
 
     let mut is_fullscreen = true;
 
@@ -59,6 +125,42 @@ async fn main() {
                 main_menu.update(&window_parameters);
                 main_menu.render(&window_parameters);
 
+                if main_menu.was_button_clicked("Resume") {
+                    if let Some(current_game) = unfinished_game.take() {
+                        match current_game.mode {
+                            GameMode::AgainstYourself => {
+                                board = current_game.record.starting_board();
+                                for chess_move in current_game.record.moves.iter() {
+                                    let _ = board.move_piece(chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion);
+                                }
+                                ui_chess_board.play_as = current_game.play_as;
+                                ui_chess_board.reset_board(&board.squares);
+                                ui_chess_board.set_last_move(&board, current_game.record.moves.last().copied());
+                                ui_chess_board.clear_drawings();
+                                ui_chess_board.show_drawings_of(current_game.record.moves.len());
+                                record = current_game.record;
+                                list_moves(&mut against_yourself, &record);
+                                preview = None;
+                                ui_chess_board.interactive = true;
+                                hint_search = None;
+                                game_state = GameState::AgainstYourself;
+                            }
+                            GameMode::Online => {
+                                online.resume(&current_game);
+                                game_state = GameState::Online;
+                            }
+                        }
+                    }
+                }
+                if main_menu.was_button_clicked("Discard") {
+                    unfinished_game = None;
+                    store::clear_current_game();
+                }
+                if unfinished_game.is_none() || game_state != GameState::Menu {
+                    unfinished_game = None;
+                    layouts::remove_resume_prompt(&mut main_menu);
+                }
+
                 if main_menu.was_button_clicked("Against yourself") {
                     game_state = GameState::AgainstYourself;
                 }
@@ -66,8 +168,34 @@ async fn main() {
                     game_state = GameState::AgainstBot;
                 }
                 if main_menu.was_button_clicked("Online") {
+                    online.connect();
                     game_state = GameState::Online;
                 }
+                if main_menu.was_button_clicked("Analysis") {
+                    analysis.start();
+                    analysis_return_state = GameState::Menu;
+                    game_state = GameState::Analysis;
+                }
+                if main_menu.was_button_clicked("Puzzles") {
+                    puzzles.start();
+                    game_state = GameState::Puzzles;
+                }
+                if main_menu.was_button_clicked("Games") {
+                    games.start();
+                    game_state = GameState::Games;
+                }
+                if main_menu.was_button_clicked("Legal moves") {
+                    settings.show_legal_moves = !settings.show_legal_moves;
+                    match store::save_settings(&settings) {
+                        Ok(()) => main_menu.remove_title("Settings status"),
+                        Err(error) => main_menu.add_title("Settings status", Title::new(&format!("Could not save the settings: {}", error), 24.0, 0.68, 0.28, BLACK)),
+                    }
+                    main_menu.add_button("Legal moves", layouts::legal_moves_button(settings.show_legal_moves));
+                    ui_chess_board.show_legal_moves = settings.show_legal_moves;
+                    online.set_show_legal_moves(settings.show_legal_moves);
+                    analysis.set_show_legal_moves(settings.show_legal_moves);
+                    puzzles.set_show_legal_moves(settings.show_legal_moves);
+                }
                 if main_menu.was_button_clicked("Quit") {
                     exit(0);
                 }
@@ -80,20 +208,89 @@ async fn main() {
                 }
                 if against_yourself.was_button_clicked("Reset") {
                     ui_chess_board.reset_board(&ChessBoard::starting_positions().squares);
+                    ui_chess_board.clear_drawings();
                     board = ChessBoard::starting_positions();
                     ui_chess_board.update(&board.squares);
+                    record = new_against_yourself_record();
+                    list_moves(&mut against_yourself, &record);
+                    preview = None;
+                    ui_chess_board.interactive = true;
+                    hint_search = None;
+                    store::clear_current_game();
+                }
+                if against_yourself.was_button_clicked("Hint") && hint_search.is_none() && preview.is_none() && board.game_status == GameStatus::Ongoing {
+                    hint_search = Some(engine::analysis::search_in_background(&board, record.history(), SearchLimits::time(HINT_SEARCH_TIME)));
+                    against_yourself.add_title("Status", Title::new("Thinking...", 30.0, 0.7, 0.6, BLACK));
+                }
+                match hint_search.as_ref().map(|receiver| receiver.try_recv()) {
+                    Some(Ok(result)) => {
+                        hint_search = None;
+                        against_yourself.remove_title("Status");
+                        // Only hints that are actually shown count
+                        if let Some(hint) = result.and_then(|info| info.best_move()) {
+                            ui_chess_board.show_hint(hint.from_tuple(), hint.to_tuple());
+                            record.add_hint(board.side_to_move());
+                            save_current_game(&mut against_yourself, &record, ui_chess_board.play_as);
+                        }
+                    }
+                    Some(Err(TryRecvError::Disconnected)) => {
+                        hint_search = None;
+                        against_yourself.add_title("Status", Title::new("No hint found", 30.0, 0.7, 0.6, BLACK));
+                    }
+                    Some(Err(TryRecvError::Empty)) | None => (),
+                }
+                if against_yourself.was_button_clicked("Save") {
+                    let status = match store::save_game(&record) {
+                        Ok(_) => "Game saved".to_string(),
+                        Err(error) => format!("Could not save the game: {}", error),
+                    };
+                    against_yourself.add_title("Status", Title::new(&status, 30.0, 0.7, 0.6, BLACK));
+                }
+                if let Some(index) = against_yourself.take_clicked_move("Moves") {
+                    // Clicking the last move goes back to the game
+                    preview = (index + 1 < record.moves.len()).then_some(index + 1);
+                    let shown = shown_board(&board, &record, preview);
+                    ui_chess_board.interactive = preview.is_none();
+                    ui_chess_board.update(&shown.squares);
+                    ui_chess_board.set_last_move(&shown, record.moves[..preview.unwrap_or(record.moves.len())].last().copied());
+                    ui_chess_board.show_drawings_of(preview.unwrap_or(record.moves.len()));
+                    against_yourself.set_current_move("Moves", Some(index));
                 }
                 if against_yourself.was_button_clicked("Flip") {
-                    ui_chess_board.flip(&board.squares);
+                    ui_chess_board.flip(&shown_board(&board, &record, preview).squares);
+                    save_current_game(&mut against_yourself, &record, ui_chess_board.play_as);
+                }
+                if against_yourself.was_button_clicked("Analyze") && !record.moves.is_empty() {
+                    // Finished games are already in the game store
+                    review.start(record.clone(), (record.status != GameStatus::Ongoing).then(|| GameTree::from_record(&record)));
+                    review_return_state = GameState::AgainstYourself;
+                    game_state = GameState::Review;
                 }
 
                 let movement_proposal = ui_chess_board.request_move(&window_parameters);
                 if let Some(coord) = movement_proposal.0 {
-                    let result = board.move_piece(coord.0, coord.1, movement_proposal.1);
+                    let result = board.move_piece_with_history(&record.history(), coord.0, coord.1, movement_proposal.1);
+                    if let Ok(game_status) = result {
+                        record.push_move(ChessMove::new(Coordinate::from_tuple_usize(coord.0), Coordinate::from_tuple_usize(coord.1), movement_proposal.1), game_status);
+                        list_moves(&mut against_yourself, &record);
+                        ui_chess_board.clear_hint();
+                        hint_search = None;
+                        against_yourself.remove_title("Status");
+                        save_current_game(&mut against_yourself, &record, ui_chess_board.play_as);
+                        // Finished games are kept in the game store
+                        if game_status != GameStatus::Ongoing {
+                            if let Err(error) = store::save_game(&record) {
+                                against_yourself.add_title("Status", Title::new(&format!("Could not save the game: {}", error), 30.0, 0.7, 0.6, BLACK));
+                            }
+                        }
+                    }
                     ui_chess_board.check_result(result);
                     ui_chess_board.update(&board.squares);
+                    ui_chess_board.set_last_move(&board, record.moves.last().copied());
+                    ui_chess_board.show_drawings_of(record.moves.len());
                 }
 
+                ui_chess_board.update_legal_moves(&board);
                 ui_chess_board.render(&window_parameters);
                 against_yourself.render(&window_parameters);
             }
@@ -105,14 +302,100 @@ async fn main() {
                 }
             }
             GameState::Online => {
-                online.update(&window_parameters);
+                if online.update(&window_parameters) {
+                    online.disconnect();
+                    game_state = GameState::Menu;
+                }
                 online.render(&window_parameters);
-                if online.was_button_clicked("Back") {
+            }
+            GameState::Analysis => {
+                if analysis.update(&window_parameters) {
+                    analysis.stop();
+                    game_state = analysis_return_state;
+                }
+                analysis.render(&window_parameters);
+            }
+            GameState::Review => {
+                if review.update(&window_parameters) {
+                    if review_return_state == GameState::AgainstYourself {
+                        record.accuracy = review.accuracy().or(record.accuracy);
+                    }
+                    review.stop();
+                    game_state = review_return_state;
+                }
+                review.render(&window_parameters);
+            }
+            GameState::Puzzles => {
+                if puzzles.update(&window_parameters) {
+                    game_state = GameState::Menu;
+                }
+                puzzles.render(&window_parameters);
+            }
+            GameState::Games => {
+                if games.update(&window_parameters) {
                     game_state = GameState::Menu;
                 }
+                match games.take_request() {
+                    Some(GameRequest::Review(tree, record)) => {
+                        review.start(record, Some(tree));
+                        review_return_state = GameState::Games;
+                        game_state = GameState::Review;
+                    }
+                    Some(GameRequest::Replay(record)) => {
+                        if let Some(record) = record {
+                            replay.start(record);
+                        }
+                        game_state = GameState::Replay;
+                    }
+                    Some(GameRequest::Analyse(tree, record)) => {
+                        analysis.load(tree, record);
+                        analysis_return_state = GameState::Games;
+                        game_state = GameState::Analysis;
+                    }
+                    None => (),
+                }
+                games.render(&window_parameters);
+            }
+            GameState::Replay => {
+                if replay.update(&window_parameters) {
+                    replay.stop();
+                    game_state = GameState::Games;
+                }
+                replay.render(&window_parameters);
             }
         }
         window_parameters.clear_outside(BLACK);
         next_frame().await
     }
 }
+
+/// Shows the moves of the game in the move list of a game layout, with the last one highlighted.
+fn list_moves(layout: &mut UIManager, record: &GameRecord) {
+    let board = record.starting_board();
+    layout.set_moves("Moves", record.san_moves(), board.fullmove_number(), board.side_to_move() == chess::Color::Black);
+}
+
+/// The board of the game against yourself as it is shown: after the first `preview` moves while an earlier position is
+/// previewed from the move list.
+fn shown_board(board: &ChessBoard, record: &GameRecord, preview: Option<usize>) -> ChessBoard {
+    match preview {
+        Some(plies) => record.board_after(plies),
+        None => board.clone(),
+    }
+}
+
+fn new_against_yourself_record() -> GameRecord {
+    let name = game_record::local_player_name();
+    GameRecord::new("Against yourself", &name, &name)
+}
+
+/// Saves the game against yourself so it can be resumed after the app was closed or crashed, or forgets it once it has
+/// ended. Saving happens after every move because the release build aborts on panics without unwinding. A failed save
+/// is shown in the status of `layout`.
+fn save_current_game(layout: &mut UIManager, record: &GameRecord, play_as: chess::Color) {
+    if record.status != GameStatus::Ongoing || record.moves.is_empty() {
+        store::clear_current_game();
+    } else if let Err(error) = store::save_current_game(&CurrentGame::new(GameMode::AgainstYourself, record.clone(), play_as)) {
+        layout.add_title("Status", Title::new(&format!("Could not save the game: {}", error), 30.0, 0.7, 0.6, BLACK));
+    }
+}
@@ -0,0 +1,79 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+    time::Duration,
+};
+
+use super::protocol::{self, ClientMessage, ServerMessage};
+
+/// A connection to a rusty_chess server. Incoming messages are read on a background thread so the UI can poll them every frame.
+pub struct ServerConnection {
+    stream: TcpStream,
+    receiver: Receiver<ServerMessage>,
+    connected: bool,
+}
+
+impl ServerConnection {
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let socket_address = address.to_socket_addrs()?.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Could not resolve server address"))?;
+        let stream = TcpStream::connect_timeout(&socket_address, Duration::from_secs(3))?;
+        let reader = BufReader::new(stream.try_clone()?);
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                if let Ok(message) = protocol::decode::<ServerMessage>(&line) {
+                    if sender.send(message).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(ServerConnection { stream, receiver, connected: true })
+    }
+
+    pub fn send(&mut self, message: &ClientMessage) {
+        if writeln!(self.stream, "{}", protocol::encode(message)).is_err() {
+            self.connected = false;
+        }
+    }
+
+    pub fn poll(&mut self) -> Vec<ServerMessage> {
+        let mut messages = Vec::new();
+        loop {
+            match self.receiver.try_recv() {
+                Ok(message) => messages.push(message),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.connected = false;
+                    break;
+                }
+            }
+        }
+        messages
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+}
+
+/// Connects on a background thread, so the caller does not wait for the connect timeout.
+pub fn connect_in_background(address: String) -> Receiver<io::Result<ServerConnection>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(ServerConnection::connect(&address));
+    });
+    receiver
+}
+
+impl Drop for ServerConnection {
+    fn drop(&mut self) {
+        let _ = self.stream.shutdown(std::net::Shutdown::Both);
+    }
+}
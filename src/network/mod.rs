@@ -0,0 +1,12 @@
+pub mod client;
+pub mod protocol;
+pub mod server;
+pub mod websocket;
+
+pub const DEFAULT_PORT: u16 = 7878;
+pub const DEFAULT_WEBSOCKET_PORT: u16 = 7879;
+
+/// The server the GUI connects to, overridable with the `RUSTY_CHESS_SERVER` environment variable.
+pub fn server_address() -> String {
+    std::env::var("RUSTY_CHESS_SERVER").unwrap_or_else(|_| format!("127.0.0.1:{}", DEFAULT_PORT))
+}
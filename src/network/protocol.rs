@@ -0,0 +1,130 @@
+//! The online protocol shared by the TCP and WebSocket transports. Messages are JSON objects tagged by `type`,
+//! the full schema is documented in `docs/protocol.md`.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::chess::{
+    chess_board::{DrawType, GameStatus, WinType},
+    clock::TimeControl,
+    Color,
+};
+
+/// The longest chat message, in characters, the server accepts.
+pub const MAX_CHAT_LENGTH: usize = 200;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Hello { name: String },
+    ListChallenges,
+    CreateChallenge {
+        color: Option<Color>,
+        #[serde(default)]
+        time_control: Option<TimeControl>,
+    },
+    CancelChallenge,
+    AcceptChallenge { challenge_id: u32 },
+    Move { game_id: u32, uci: String },
+    Resign { game_id: u32 },
+    Watch { game_id: u32 },
+    StopWatching { game_id: u32 },
+    /// Takes back a seat in a running game after the connection was lost.
+    Resume { session_token: String },
+    Chat { game_id: u32, text: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Welcome { player_id: u32 },
+    Lobby { challenges: Vec<ChallengeInfo>, games: Vec<GameInfo> },
+    ChallengeCreated { challenge_id: u32 },
+    GameStarted { game_id: u32, color: Color, opponent: String, time_control: Option<TimeControl>, session_token: String },
+    /// Sent after a successful `Resume` with every move played so far.
+    Resumed { game_id: u32, color: Color, opponent: String, time_control: Option<TimeControl>, moves: Vec<String> },
+    /// Answer to a `Resume` whose game is over or unknown.
+    ResumeFailed { reason: String },
+    OpponentDisconnected { game_id: u32, grace_ms: u64 },
+    OpponentReconnected { game_id: u32 },
+    Chat { game_id: u32, player_id: u32, from: String, text: String },
+    /// Sent to a spectator when they start watching a game.
+    Observing { game_id: u32, white: String, black: String, fen: String, moves: Vec<String>, time_control: Option<TimeControl> },
+    Move { game_id: u32, uci: String },
+    Clock { game_id: u32, white_ms: u64, black_ms: u64, running: Option<Color> },
+    MoveRejected { game_id: u32, uci: String, reason: String },
+    GameEnded { game_id: u32, result: String, reason: String },
+    Error { message: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChallengeInfo {
+    pub challenge_id: u32,
+    pub player: String,
+    /// The color the challenger will play, `None` if it is decided when the challenge is accepted.
+    pub color: Option<Color>,
+    /// `None` for games without a clock.
+    pub time_control: Option<TimeControl>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GameInfo {
+    pub game_id: u32,
+    pub white: String,
+    pub black: String,
+}
+
+/// Messages are sent as one JSON object per line.
+pub fn encode<T: Serialize>(message: &T) -> String {
+    serde_json::to_string(message).expect("protocol messages always serialize")
+}
+
+pub fn decode<T: DeserializeOwned>(line: &str) -> Result<T, serde_json::Error> {
+    serde_json::from_str(line.trim())
+}
+
+/// Splits a finished game status into its PGN style result (`1-0`, `0-1`, `1/2-1/2`) and a reason.
+pub fn result_of(game_status: GameStatus) -> Option<(String, String)> {
+    let (result, reason) = match game_status {
+        GameStatus::Ongoing => return None,
+        GameStatus::Win(Color::White, win_type) => ("1-0", win_reason(win_type)),
+        GameStatus::Win(Color::Black, win_type) => ("0-1", win_reason(win_type)),
+        GameStatus::Draw(DrawType::Stalemate) => ("1/2-1/2", "stalemate"),
+        GameStatus::Draw(DrawType::MoveRule) => ("1/2-1/2", "fifty_move_rule"),
+        GameStatus::Draw(DrawType::Repetion) => ("1/2-1/2", "repetition"),
+        GameStatus::Draw(DrawType::InsufficientMaterial) => ("1/2-1/2", "insufficient_material"),
+        GameStatus::Draw(DrawType::Agreement) => ("1/2-1/2", "agreement"),
+    };
+    Some((result.to_string(), reason.to_string()))
+}
+
+pub fn game_status_from_result(result: &str, reason: &str) -> Option<GameStatus> {
+    let win_type = match reason {
+        "checkmate" => Some(WinType::Checkmate),
+        "resignation" => Some(WinType::Resignation),
+        "timeout" => Some(WinType::Timeout),
+        "abandonment" => Some(WinType::Abandonment),
+        _ => None,
+    };
+    match (result, win_type) {
+        ("1-0", Some(win_type)) => Some(GameStatus::Win(Color::White, win_type)),
+        ("0-1", Some(win_type)) => Some(GameStatus::Win(Color::Black, win_type)),
+        ("1/2-1/2", _) => match reason {
+            "stalemate" => Some(GameStatus::Draw(DrawType::Stalemate)),
+            "fifty_move_rule" => Some(GameStatus::Draw(DrawType::MoveRule)),
+            "repetition" => Some(GameStatus::Draw(DrawType::Repetion)),
+            "insufficient_material" => Some(GameStatus::Draw(DrawType::InsufficientMaterial)),
+            "agreement" => Some(GameStatus::Draw(DrawType::Agreement)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn win_reason(win_type: WinType) -> &'static str {
+    match win_type {
+        WinType::Checkmate => "checkmate",
+        WinType::Resignation => "resignation",
+        WinType::Timeout => "timeout",
+        WinType::Abandonment => "abandonment",
+    }
+}
@@ -0,0 +1,690 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hasher},
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::chess::{
+    chess_board::{ChessPosition, GameStatus, WinType},
+    clock::{ChessClock, TimeControl},
+    ChessBoard, ChessMove, Color,
+};
+
+use super::{
+    protocol::{self, ChallengeInfo, ClientMessage, GameInfo, ServerMessage},
+    websocket,
+};
+
+pub type SharedServer = Arc<Mutex<Server>>;
+
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+/// How long a player may be disconnected from a running game before it is declared abandoned.
+pub const RECONNECT_GRACE_PERIOD: Duration = Duration::from_secs(60);
+
+struct Player {
+    name: String,
+    sender: Sender<ServerMessage>,
+}
+
+struct Challenge {
+    player_id: u32,
+    color: Option<Color>,
+    time_control: Option<TimeControl>,
+}
+
+/// One side of a game. The session token lets the player take the seat back after losing the connection.
+pub struct Seat {
+    pub player_id: u32,
+    pub name: String,
+    pub session_token: String,
+    pub disconnected_since: Option<Instant>,
+}
+
+impl Seat {
+    fn new(player_id: u32, name: String) -> Self {
+        Seat { player_id, name, session_token: new_session_token(), disconnected_since: None }
+    }
+}
+
+/// A game hosted by the server. The server's board is the authoritative state, clients only mirror it.
+pub struct ServerGame {
+    pub board: ChessBoard,
+    pub white: Seat,
+    pub black: Seat,
+    pub moves: Vec<ChessMove>,
+    /// The position before each move in `moves`, used to detect threefold repetition.
+    pub history: Vec<ChessPosition>,
+    pub clock: Option<ChessClock>,
+    pub spectators: Vec<u32>,
+}
+
+impl ServerGame {
+    fn color_of(&self, player_id: u32) -> Option<Color> {
+        if self.white.player_id == player_id {
+            Some(Color::White)
+        } else if self.black.player_id == player_id {
+            Some(Color::Black)
+        } else {
+            None
+        }
+    }
+
+    fn seat(&self, color: Color) -> &Seat {
+        match color {
+            Color::White => &self.white,
+            Color::Black => &self.black,
+        }
+    }
+
+    fn seat_mut(&mut self, color: Color) -> &mut Seat {
+        match color {
+            Color::White => &mut self.white,
+            Color::Black => &mut self.black,
+        }
+    }
+
+    fn players(&self) -> [u32; 2] {
+        [self.white.player_id, self.black.player_id]
+    }
+
+    /// Everyone who receives the game's moves: both players and all spectators.
+    fn audience(&self) -> Vec<u32> {
+        let mut audience = self.players().to_vec();
+        audience.extend(&self.spectators);
+        audience
+    }
+
+    fn clock_message(&self, game_id: u32) -> Option<ServerMessage> {
+        self.clock.as_ref().map(|clock| ServerMessage::Clock { game_id, white_ms: clock.remaining_ms(Color::White), black_ms: clock.remaining_ms(Color::Black), running: clock.running() })
+    }
+}
+
+#[derive(Default)]
+pub struct Server {
+    next_id: u32,
+    players: HashMap<u32, Player>,
+    challenges: HashMap<u32, Challenge>,
+    games: HashMap<u32, ServerGame>,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Server { next_id: 1, ..Default::default() }
+    }
+
+    fn new_id(&mut self) -> u32 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    pub fn connect(&mut self, sender: Sender<ServerMessage>) -> u32 {
+        let player_id = self.new_id();
+        let _ = sender.send(ServerMessage::Welcome { player_id });
+        let _ = sender.send(self.lobby_message());
+        self.players.insert(player_id, Player { name: format!("Guest {}", player_id), sender });
+        player_id
+    }
+
+    pub fn disconnect(&mut self, player_id: u32) {
+        self.players.remove(&player_id);
+        self.remove_challenges_of(player_id);
+        for game in self.games.values_mut() {
+            game.spectators.retain(|spectator| *spectator != player_id);
+        }
+
+        let mut opponents = Vec::new();
+        for (game_id, game) in self.games.iter_mut() {
+            if let Some(color) = game.color_of(player_id) {
+                game.seat_mut(color).disconnected_since = Some(Instant::now());
+                let mut opponent = color;
+                opponent.switch();
+                opponents.push((*game_id, game.seat(opponent).player_id));
+            }
+        }
+        for (game_id, opponent) in opponents {
+            self.send(opponent, ServerMessage::OpponentDisconnected { game_id, grace_ms: RECONNECT_GRACE_PERIOD.as_millis() as u64 });
+        }
+    }
+
+    pub fn handle(&mut self, player_id: u32, message: ClientMessage) {
+        match message {
+            ClientMessage::Hello { name } => {
+                let name = name.trim();
+                if let Some(player) = self.players.get_mut(&player_id) {
+                    if !name.is_empty() {
+                        player.name = name.to_string();
+                    }
+                }
+                self.broadcast_lobby();
+            }
+            ClientMessage::ListChallenges => self.send(player_id, self.lobby_message()),
+            ClientMessage::CreateChallenge { color, time_control } => {
+                self.remove_challenges_of(player_id);
+                let challenge_id = self.new_id();
+                self.challenges.insert(challenge_id, Challenge { player_id, color, time_control });
+                self.send(player_id, ServerMessage::ChallengeCreated { challenge_id });
+                self.broadcast_lobby();
+            }
+            ClientMessage::CancelChallenge => {
+                self.remove_challenges_of(player_id);
+                self.broadcast_lobby();
+            }
+            ClientMessage::AcceptChallenge { challenge_id } => self.accept_challenge(player_id, challenge_id),
+            ClientMessage::Move { game_id, uci } => self.make_move(player_id, game_id, uci),
+            ClientMessage::Resign { game_id } => self.resign(player_id, game_id),
+            ClientMessage::Watch { game_id } => self.watch(player_id, game_id),
+            ClientMessage::Resume { session_token } => self.resume(player_id, &session_token),
+            ClientMessage::Chat { game_id, text } => self.chat(player_id, game_id, text),
+            ClientMessage::StopWatching { game_id } => {
+                if let Some(game) = self.games.get_mut(&game_id) {
+                    game.spectators.retain(|spectator| *spectator != player_id);
+                }
+            }
+        }
+    }
+
+    fn accept_challenge(&mut self, player_id: u32, challenge_id: u32) {
+        let challenge = match self.challenges.get(&challenge_id) {
+            Some(challenge) if challenge.player_id != player_id => self.challenges.remove(&challenge_id).unwrap(),
+            Some(_) => return self.send(player_id, ServerMessage::Error { message: "You can not accept your own challenge".to_string() }),
+            None => return self.send(player_id, ServerMessage::Error { message: "That challenge is no longer available".to_string() }),
+        };
+        self.remove_challenges_of(player_id);
+
+        let challenger_color = challenge.color.unwrap_or_else(random_color);
+        let (white, black) = match challenger_color {
+            Color::White => (challenge.player_id, player_id),
+            Color::Black => (player_id, challenge.player_id),
+        };
+        let game_id = self.new_id();
+        let time_control = challenge.time_control;
+        let clock = time_control.map(|time_control| {
+            let mut clock = ChessClock::new(time_control);
+            clock.start(Color::White);
+            clock
+        });
+        let white_seat = Seat::new(white, self.player_name(white));
+        let black_seat = Seat::new(black, self.player_name(black));
+        self.send(white, ServerMessage::GameStarted { game_id, color: Color::White, opponent: black_seat.name.clone(), time_control, session_token: white_seat.session_token.clone() });
+        self.send(black, ServerMessage::GameStarted { game_id, color: Color::Black, opponent: white_seat.name.clone(), time_control, session_token: black_seat.session_token.clone() });
+
+        let game = ServerGame { board: ChessBoard::starting_positions(), white: white_seat, black: black_seat, moves: Vec::new(), history: Vec::new(), clock, spectators: Vec::new() };
+        let clock_message = game.clock_message(game_id);
+        self.games.insert(game_id, game);
+        if let Some(clock_message) = clock_message {
+            self.send(white, clock_message.clone());
+            self.send(black, clock_message);
+        }
+        self.broadcast_lobby();
+    }
+
+    fn make_move(&mut self, player_id: u32, game_id: u32, uci: String) {
+        let game = match self.games.get_mut(&game_id) {
+            Some(game) => game,
+            None => return self.send(player_id, ServerMessage::Error { message: format!("No game with id {}", game_id) }),
+        };
+        // A flag that fell between two ticks ends the game before the late move is looked at
+        if let Some(flagged) = game.clock.as_ref().and_then(|clock| clock.flagged()) {
+            game.board.forfeit(flagged, WinType::Timeout);
+            let game_status = game.board.game_status;
+            return self.end_game_if_finished(game_id, game_status);
+        }
+        let rejection = match (game.color_of(player_id), ChessMove::from_uci(&uci)) {
+            (None, _) => Some("You are not playing in this game".to_string()),
+            (Some(color), _) if color != game.board.side_to_move() => Some("It is not your turn".to_string()),
+            (_, None) => Some("The move is not valid UCI notation".to_string()),
+            (Some(color), Some(chess_move)) => {
+                let position_before_move = ChessPosition::new(&game.board);
+                match game.board.move_piece_with_history(&game.history, chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion) {
+                    Ok(_) => {
+                        game.moves.push(chess_move);
+                        game.history.push(position_before_move);
+                        if let Some(clock) = game.clock.as_mut() {
+                            clock.press(color);
+                        }
+                        None
+                    }
+                    Err(error) => Some(format!("{:?}", error)),
+                }
+            }
+        };
+        if let Some(reason) = rejection {
+            return self.send(player_id, ServerMessage::MoveRejected { game_id, uci, reason });
+        }
+
+        let game_status = game.board.game_status;
+        let clock_message = game.clock_message(game_id);
+        for id in game.audience() {
+            self.send(id, ServerMessage::Move { game_id, uci: uci.clone() });
+            if let Some(clock_message) = clock_message.clone() {
+                self.send(id, clock_message);
+            }
+        }
+        self.end_game_if_finished(game_id, game_status);
+    }
+
+    fn watch(&mut self, player_id: u32, game_id: u32) {
+        let game = match self.games.get_mut(&game_id) {
+            Some(game) => game,
+            None => return self.send(player_id, ServerMessage::Error { message: "That game is no longer running".to_string() }),
+        };
+        if game.color_of(player_id).is_some() {
+            return self.send(player_id, ServerMessage::Error { message: "You can not watch your own game".to_string() });
+        }
+        if !game.spectators.contains(&player_id) {
+            game.spectators.push(player_id);
+        }
+        let observing = ServerMessage::Observing { game_id, white: game.white.name.clone(), black: game.black.name.clone(), fen: game.board.to_fen(), moves: game.moves.iter().map(|chess_move| chess_move.to_uci()).collect(), time_control: game.clock.as_ref().map(|clock| clock.time_control) };
+        let clock_message = game.clock_message(game_id);
+
+        self.send(player_id, observing);
+        if let Some(clock_message) = clock_message {
+            self.send(player_id, clock_message);
+        }
+    }
+
+    /// Relays a chat message to everyone following the game. Only the players and spectators of a game may chat in it.
+    fn chat(&mut self, player_id: u32, game_id: u32, text: String) {
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        if text.chars().count() > protocol::MAX_CHAT_LENGTH {
+            return self.send(player_id, ServerMessage::Error { message: format!("Chat messages can be at most {} characters long", protocol::MAX_CHAT_LENGTH) });
+        }
+        let audience = match self.games.get(&game_id) {
+            Some(game) if game.audience().contains(&player_id) => game.audience(),
+            _ => return self.send(player_id, ServerMessage::Error { message: "You can only chat in games you play or watch".to_string() }),
+        };
+        let from = self.player_name(player_id);
+        for id in audience {
+            self.send(id, ServerMessage::Chat { game_id, player_id, from: from.clone(), text: text.to_string() });
+        }
+    }
+
+    /// Gives the seat belonging to `session_token` to `player_id` and sends them the full game so far.
+    fn resume(&mut self, player_id: u32, session_token: &str) {
+        let found = self.games.iter_mut().find_map(|(game_id, game)| [Color::White, Color::Black].into_iter().find(|color| game.seat(*color).session_token == session_token).map(|color| (*game_id, game, color)));
+        let (game_id, game, color) = match found {
+            Some(found) => found,
+            None => return self.send(player_id, ServerMessage::ResumeFailed { reason: "The game can not be resumed any more".to_string() }),
+        };
+        let seat = game.seat_mut(color);
+        seat.player_id = player_id;
+        seat.disconnected_since = None;
+        let mut opponent_color = color;
+        opponent_color.switch();
+        let opponent = game.seat(opponent_color);
+
+        let resumed = ServerMessage::Resumed { game_id, color, opponent: opponent.name.clone(), time_control: game.clock.as_ref().map(|clock| clock.time_control), moves: game.moves.iter().map(|chess_move| chess_move.to_uci()).collect() };
+        let opponent_id = opponent.player_id;
+        let clock_message = game.clock_message(game_id);
+        self.remove_challenges_of(player_id);
+        self.send(player_id, resumed);
+        if let Some(clock_message) = clock_message {
+            self.send(player_id, clock_message);
+        }
+        self.send(opponent_id, ServerMessage::OpponentReconnected { game_id });
+    }
+
+    /// Ends games where a player ran out of time or stayed disconnected for longer than the grace period.
+    /// Called periodically by the server thread.
+    pub fn tick(&mut self) {
+        let mut finished = Vec::new();
+        for (game_id, game) in self.games.iter_mut() {
+            if let Some(flagged) = game.clock.as_ref().and_then(|clock| clock.flagged()) {
+                game.board.forfeit(flagged, WinType::Timeout);
+            }
+            for color in [Color::White, Color::Black] {
+                if game.seat(color).disconnected_since.is_some_and(|since| since.elapsed() >= RECONNECT_GRACE_PERIOD) {
+                    game.board.forfeit(color, WinType::Abandonment);
+                }
+            }
+            if game.board.game_status != GameStatus::Ongoing {
+                finished.push((*game_id, game.board.game_status));
+            }
+        }
+        for (game_id, game_status) in finished {
+            self.end_game_if_finished(game_id, game_status);
+        }
+    }
+
+    fn resign(&mut self, player_id: u32, game_id: u32) {
+        let game = match self.games.get_mut(&game_id) {
+            Some(game) => game,
+            None => return,
+        };
+        if let Some(color) = game.color_of(player_id) {
+            game.board.resign(color);
+            let game_status = game.board.game_status;
+            self.end_game_if_finished(game_id, game_status);
+        }
+    }
+
+    fn end_game_if_finished(&mut self, game_id: u32, game_status: GameStatus) {
+        if let Some((result, reason)) = protocol::result_of(game_status) {
+            if let Some(mut game) = self.games.remove(&game_id) {
+                if let Some(clock) = game.clock.as_mut() {
+                    clock.stop();
+                }
+                let clock_message = game.clock_message(game_id);
+                for id in game.audience() {
+                    if let Some(clock_message) = clock_message.clone() {
+                        self.send(id, clock_message);
+                    }
+                    self.send(id, ServerMessage::GameEnded { game_id, result: result.clone(), reason: reason.clone() });
+                }
+                self.broadcast_lobby();
+            }
+        }
+    }
+
+    fn remove_challenges_of(&mut self, player_id: u32) {
+        self.challenges.retain(|_, challenge| challenge.player_id != player_id);
+    }
+
+    fn challenge_list(&self) -> Vec<ChallengeInfo> {
+        let mut challenges: Vec<ChallengeInfo> = self.challenges.iter().map(|(challenge_id, challenge)| ChallengeInfo { challenge_id: *challenge_id, player: self.player_name(challenge.player_id), color: challenge.color, time_control: challenge.time_control }).collect();
+        challenges.sort_by_key(|challenge| challenge.challenge_id);
+        challenges
+    }
+
+    fn game_list(&self) -> Vec<GameInfo> {
+        let mut games: Vec<GameInfo> = self.games.iter().map(|(game_id, game)| GameInfo { game_id: *game_id, white: game.white.name.clone(), black: game.black.name.clone() }).collect();
+        games.sort_by_key(|game| game.game_id);
+        games
+    }
+
+    fn lobby_message(&self) -> ServerMessage {
+        ServerMessage::Lobby { challenges: self.challenge_list(), games: self.game_list() }
+    }
+
+    fn broadcast_lobby(&self) {
+        let lobby_message = self.lobby_message();
+        for player in self.players.values() {
+            let _ = player.sender.send(lobby_message.clone());
+        }
+    }
+
+    fn player_name(&self, player_id: u32) -> String {
+        self.players.get(&player_id).map(|player| player.name.clone()).unwrap_or_else(|| "Unknown".to_string())
+    }
+
+    fn send(&self, player_id: u32, message: ServerMessage) {
+        if let Some(player) = self.players.get(&player_id) {
+            let _ = player.sender.send(message);
+        }
+    }
+}
+
+fn new_session_token() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_nanos()).unwrap_or(0));
+    let first = hasher.finish();
+    hasher.write_u64(first);
+    format!("{:016x}{:016x}", first, hasher.finish())
+}
+
+fn random_color() -> Color {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.subsec_nanos()).unwrap_or(0);
+    if nanos.is_multiple_of(2) {
+        Color::White
+    } else {
+        Color::Black
+    }
+}
+
+/// Serves the same lobby over raw TCP on `tcp_address` and over WebSocket on `websocket_address`.
+/// Every client gets its own thread and the call only returns if a listener can not be bound.
+pub fn run(tcp_address: &str, websocket_address: &str) -> io::Result<()> {
+    let tcp_listener = TcpListener::bind(tcp_address)?;
+    let websocket_listener = TcpListener::bind(websocket_address)?;
+    let server: SharedServer = Arc::new(Mutex::new(Server::new()));
+    println!("rusty_chess server listening on {} (tcp) and {} (websocket)", tcp_listener.local_addr()?, websocket_listener.local_addr()?);
+
+    let ticking_server = Arc::clone(&server);
+    thread::spawn(move || loop {
+        thread::sleep(TICK_INTERVAL);
+        ticking_server.lock().unwrap().tick();
+    });
+
+    let websocket_server = Arc::clone(&server);
+    thread::spawn(move || websocket::serve(websocket_listener, websocket_server));
+
+    for stream in tcp_listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let server = Arc::clone(&server);
+                thread::spawn(move || {
+                    if let Err(error) = handle_tcp_client(stream, server) {
+                        eprintln!("Connection closed with error: {}", error);
+                    }
+                });
+            }
+            Err(error) => eprintln!("Failed to accept connection: {}", error),
+        }
+    }
+    Ok(())
+}
+
+fn handle_tcp_client(stream: TcpStream, server: SharedServer) -> io::Result<()> {
+    let (sender, receiver) = mpsc::channel::<ServerMessage>();
+    let mut writer = stream.try_clone()?;
+    thread::spawn(move || {
+        for message in receiver {
+            if writeln!(writer, "{}", protocol::encode(&message)).is_err() {
+                break;
+            }
+        }
+    });
+
+    let player_id = server.lock().unwrap().connect(sender.clone());
+    let reader = BufReader::new(stream);
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match protocol::decode::<ClientMessage>(&line) {
+            Ok(message) => server.lock().unwrap().handle(player_id, message),
+            Err(error) => {
+                let _ = sender.send(ServerMessage::Error { message: format!("Invalid message: {}", error) });
+            }
+        }
+    }
+    server.lock().unwrap().disconnect(player_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::Receiver;
+
+    type Connection = (u32, Receiver<ServerMessage>);
+
+    fn connect(server: &mut Server) -> Connection {
+        let (sender, receiver) = mpsc::channel();
+        let player_id = server.connect(sender);
+        (player_id, receiver)
+    }
+
+    fn received(receiver: &Receiver<ServerMessage>) -> Vec<ServerMessage> {
+        receiver.try_iter().collect()
+    }
+
+    /// Starts a game between two new players and returns the game id with white's and black's id and receiver.
+    fn start_game(server: &mut Server, time_control: Option<TimeControl>) -> (u32, Connection, Connection) {
+        let (white, white_receiver) = connect(server);
+        let (black, black_receiver) = connect(server);
+        server.handle(white, ClientMessage::CreateChallenge { color: Some(Color::White), time_control });
+        let challenge_id = received(&white_receiver).into_iter().find_map(|message| match message {
+            ServerMessage::ChallengeCreated { challenge_id } => Some(challenge_id),
+            _ => None,
+        }).unwrap();
+        server.handle(black, ClientMessage::AcceptChallenge { challenge_id });
+        let game_id = received(&black_receiver).into_iter().find_map(|message| match message {
+            ServerMessage::GameStarted { game_id, .. } => Some(game_id),
+            _ => None,
+        }).unwrap();
+        received(&white_receiver);
+        (game_id, (white, white_receiver), (black, black_receiver))
+    }
+
+    fn play(server: &mut Server, game_id: u32, white: u32, black: u32, moves: &[&str]) {
+        for (i, uci) in moves.iter().enumerate() {
+            let player_id = if i % 2 == 0 { white } else { black };
+            server.handle(player_id, ClientMessage::Move { game_id, uci: uci.to_string() });
+        }
+    }
+
+    fn game_ended(messages: &[ServerMessage]) -> Option<(&str, &str)> {
+        messages.iter().find_map(|message| match message {
+            ServerMessage::GameEnded { result, reason, .. } => Some((result.as_str(), reason.as_str())),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn accepted_challenge_starts_a_game() {
+        let mut server = Server::new();
+        let (white, white_receiver) = connect(&mut server);
+        let (black, black_receiver) = connect(&mut server);
+        assert_eq!(received(&white_receiver)[0], ServerMessage::Welcome { player_id: white });
+
+        server.handle(white, ClientMessage::CreateChallenge { color: Some(Color::White), time_control: None });
+        received(&white_receiver);
+        let challenge_id = match received(&black_receiver).last() {
+            Some(ServerMessage::Lobby { challenges, .. }) if challenges.len() == 1 => challenges[0].challenge_id,
+            other => panic!("expected a lobby with the challenge, got {:?}", other),
+        };
+
+        server.handle(black, ClientMessage::AcceptChallenge { challenge_id });
+        let messages = received(&black_receiver);
+        assert!(matches!(&messages[0], ServerMessage::GameStarted { color: Color::Black, opponent, .. } if opponent == &format!("Guest {}", white)));
+        assert!(matches!(messages.last(), Some(ServerMessage::Lobby { challenges, games }) if challenges.is_empty() && games.len() == 1));
+        assert!(matches!(&received(&white_receiver)[0], ServerMessage::GameStarted { color: Color::White, .. }));
+    }
+
+    #[test]
+    fn illegal_move_is_rejected() {
+        let mut server = Server::new();
+        let (game_id, (white, white_receiver), (_, black_receiver)) = start_game(&mut server, None);
+        server.handle(white, ClientMessage::Move { game_id, uci: "e2e5".to_string() });
+        assert!(matches!(&received(&white_receiver)[..], [ServerMessage::MoveRejected { uci, .. }] if uci == "e2e5"));
+        assert!(received(&black_receiver).is_empty());
+        assert!(server.games[&game_id].moves.is_empty());
+    }
+
+    #[test]
+    fn move_out_of_turn_is_rejected() {
+        let mut server = Server::new();
+        let (game_id, (_, white_receiver), (black, black_receiver)) = start_game(&mut server, None);
+        server.handle(black, ClientMessage::Move { game_id, uci: "e7e5".to_string() });
+        assert!(matches!(&received(&black_receiver)[..], [ServerMessage::MoveRejected { reason, .. }] if reason == "It is not your turn"));
+        assert!(received(&white_receiver).is_empty());
+    }
+
+    #[test]
+    fn game_over_is_sent_to_players_and_spectators() {
+        let mut server = Server::new();
+        let (game_id, (white, white_receiver), (black, black_receiver)) = start_game(&mut server, None);
+        let (spectator, spectator_receiver) = connect(&mut server);
+        server.handle(spectator, ClientMessage::Watch { game_id });
+        play(&mut server, game_id, white, black, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        for receiver in [&white_receiver, &black_receiver, &spectator_receiver] {
+            assert_eq!(game_ended(&received(receiver)), Some(("0-1", "checkmate")));
+        }
+        assert!(server.games.is_empty());
+    }
+
+    #[test]
+    fn repeated_position_ends_the_game() {
+        let mut server = Server::new();
+        let (game_id, (white, white_receiver), (black, _)) = start_game(&mut server, None);
+        play(&mut server, game_id, white, black, &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1"]);
+        assert_eq!(game_ended(&received(&white_receiver)), None);
+        play(&mut server, game_id, black, white, &["f6g8"]);
+        assert_eq!(game_ended(&received(&white_receiver)), Some(("1/2-1/2", "repetition")));
+    }
+
+    #[test]
+    fn move_after_the_flag_fell_is_refused() {
+        let mut server = Server::new();
+        let (game_id, (white, white_receiver), (_, black_receiver)) = start_game(&mut server, Some(TimeControl { initial_ms: 0, increment_ms: 0 }));
+        server.handle(white, ClientMessage::Move { game_id, uci: "e2e4".to_string() });
+        let messages = received(&white_receiver);
+        assert!(!messages.iter().any(|message| matches!(message, ServerMessage::Move { .. })));
+        assert_eq!(game_ended(&messages), Some(("0-1", "timeout")));
+        assert_eq!(game_ended(&received(&black_receiver)), Some(("0-1", "timeout")));
+        assert!(server.games.is_empty());
+    }
+
+    fn session_token(server: &Server, game_id: u32, color: Color) -> String {
+        server.games[&game_id].seat(color).session_token.clone()
+    }
+
+    #[test]
+    fn resume_gives_the_seat_back() {
+        let mut server = Server::new();
+        let (game_id, (white, _), (black, black_receiver)) = start_game(&mut server, None);
+        play(&mut server, game_id, white, black, &["e2e4"]);
+        let session_token = session_token(&server, game_id, Color::White);
+        server.disconnect(white);
+        received(&black_receiver);
+
+        let (reconnected, receiver) = connect(&mut server);
+        received(&receiver);
+        server.handle(reconnected, ClientMessage::Resume { session_token });
+        assert!(matches!(&received(&receiver)[..], [ServerMessage::Resumed { color: Color::White, moves, .. }] if moves == &["e2e4"]));
+        assert_eq!(received(&black_receiver), vec![ServerMessage::OpponentReconnected { game_id }]);
+        assert_eq!(server.games[&game_id].white.disconnected_since, None);
+
+        play(&mut server, game_id, black, reconnected, &["e7e5", "g1f3"]);
+        assert_eq!(server.games[&game_id].moves.len(), 3);
+    }
+
+    #[test]
+    fn resume_fails_for_unknown_and_finished_games() {
+        let mut server = Server::new();
+        let (game_id, (white, _), _) = start_game(&mut server, None);
+        let session_token = session_token(&server, game_id, Color::White);
+        let (player_id, receiver) = connect(&mut server);
+        received(&receiver);
+
+        server.handle(player_id, ClientMessage::Resume { session_token: "unknown".to_string() });
+        assert!(matches!(&received(&receiver)[..], [ServerMessage::ResumeFailed { .. }]));
+
+        server.handle(white, ClientMessage::Resign { game_id });
+        received(&receiver);
+        server.handle(player_id, ClientMessage::Resume { session_token });
+        assert!(matches!(&received(&receiver)[..], [ServerMessage::ResumeFailed { .. }]));
+    }
+
+    #[test]
+    fn disconnected_player_loses_after_the_grace_period() {
+        let mut server = Server::new();
+        let (game_id, (white, _), (_, black_receiver)) = start_game(&mut server, None);
+        server.disconnect(white);
+        assert!(matches!(&received(&black_receiver)[..], [ServerMessage::OpponentDisconnected { .. }, ..]));
+
+        server.tick();
+        assert!(server.games.contains_key(&game_id));
+
+        server.games.get_mut(&game_id).unwrap().white.disconnected_since = Instant::now().checked_sub(RECONNECT_GRACE_PERIOD);
+        server.tick();
+        assert_eq!(game_ended(&received(&black_receiver)), Some(("0-1", "abandonment")));
+        assert!(server.games.is_empty());
+    }
+}
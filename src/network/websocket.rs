@@ -0,0 +1,76 @@
+//! WebSocket transport for the online protocol so that browser based tools and bots can play.
+//! Every text frame carries exactly one JSON message, see `docs/protocol.md` for the schema.
+
+use std::{
+    io::ErrorKind,
+    net::{TcpListener, TcpStream},
+    sync::{
+        mpsc::{self, TryRecvError},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use tungstenite::{Error, Message};
+
+use super::{
+    protocol::{self, ClientMessage, ServerMessage},
+    server::SharedServer,
+};
+
+/// How long a read may block before queued outgoing messages are flushed.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+pub fn serve(listener: TcpListener, server: SharedServer) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let server = Arc::clone(&server);
+                thread::spawn(move || handle_websocket_client(stream, server));
+            }
+            Err(error) => eprintln!("Failed to accept websocket connection: {}", error),
+        }
+    }
+}
+
+fn handle_websocket_client(stream: TcpStream, server: SharedServer) {
+    let mut websocket = match tungstenite::accept(stream) {
+        Ok(websocket) => websocket,
+        Err(error) => return eprintln!("Websocket handshake failed: {}", error),
+    };
+    if websocket.get_mut().set_read_timeout(Some(POLL_INTERVAL)).is_err() {
+        return;
+    }
+
+    let (sender, receiver) = mpsc::channel::<ServerMessage>();
+    let player_id = server.lock().unwrap().connect(sender.clone());
+
+    'connection: loop {
+        match websocket.read() {
+            Ok(Message::Text(text)) => match protocol::decode::<ClientMessage>(text.as_str()) {
+                Ok(message) => server.lock().unwrap().handle(player_id, message),
+                Err(error) => {
+                    let _ = sender.send(ServerMessage::Error { message: format!("Invalid message: {}", error) });
+                }
+            },
+            Ok(Message::Close(_)) => break,
+            Ok(_) => (),
+            Err(Error::Io(error)) if error.kind() == ErrorKind::WouldBlock || error.kind() == ErrorKind::TimedOut => (),
+            Err(_) => break,
+        }
+
+        loop {
+            match receiver.try_recv() {
+                Ok(message) => {
+                    if websocket.send(Message::text(protocol::encode(&message))).is_err() {
+                        break 'connection;
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => break 'connection,
+            }
+        }
+    }
+    server.lock().unwrap().disconnect(player_id);
+}
@@ -0,0 +1,290 @@
+//! Files kept in the user's data directory, such as saved games.
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use crate::{
+    chess::{
+        clock::TimeControl,
+        game_record::GameRecord,
+        game_tree::GameTree,
+        pgn::{self, GameQuery, PgnGame},
+        puzzle::{self, Puzzle, PuzzleStats},
+        Color,
+    },
+    explorer::OpeningIndex,
+};
+
+/// The directory Rusty Chess keeps its files in, e.g. `~/.local/share/rusty_chess` on Linux.
+pub fn data_directory() -> PathBuf {
+    let base = if cfg!(target_os = "windows") {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library").join("Application Support"))
+    } else {
+        env::var_os("XDG_DATA_HOME").map(PathBuf::from).or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share")))
+    };
+    base.unwrap_or_else(env::temp_dir).join("rusty_chess")
+}
+
+pub fn games_directory() -> PathBuf {
+    data_directory().join("games")
+}
+
+/// Writes the game as a PGN file into the games directory and returns its path. Saving the same game again replaces the file.
+pub fn save_game(record: &GameRecord) -> io::Result<PathBuf> {
+    write_game(record.id, &record.to_pgn())
+}
+
+/// Like `save_game`, with the variations of `tree` in the movetext. The main line of the tree becomes the record's moves.
+pub fn save_game_tree(record: &GameRecord, tree: &GameTree) -> io::Result<PathBuf> {
+    let record = tree.mainline_record(record);
+    write_game(record.id, &record.to_pgn_with_tree(tree))
+}
+
+fn write_game(id: u64, pgn: &str) -> io::Result<PathBuf> {
+    let directory = games_directory();
+    fs::create_dir_all(&directory)?;
+    let path = directory.join(format!("{}.pgn", id));
+    fs::write(&path, pgn)?;
+    Ok(path)
+}
+
+/// The kinds of games that are saved while they are played.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GameMode {
+    AgainstYourself,
+    Online,
+}
+
+/// A game in progress, saved after every move so that it can be resumed after the app was closed or crashed.
+#[derive(Clone, Debug)]
+pub struct CurrentGame {
+    pub mode: GameMode,
+    pub record: GameRecord,
+    /// The side shown at the bottom of the board.
+    pub play_as: Color,
+    /// The token the server resumes an online game with.
+    pub session_token: Option<String>,
+    pub time_control: Option<TimeControl>,
+    /// The last known remaining time of white and black in milliseconds, for games with a clock.
+    pub clock_ms: Option<[u64; 2]>,
+    pub chat_muted: bool,
+}
+
+impl CurrentGame {
+    pub fn new(mode: GameMode, record: GameRecord, play_as: Color) -> Self {
+        CurrentGame { mode, record, play_as, session_token: None, time_control: None, clock_ms: None, chat_muted: false }
+    }
+
+    /// The game as PGN, with the mode and settings in extra tags in front of the record's own.
+    fn to_pgn(&self) -> String {
+        let mut tags = vec![("Mode", format!("{:?}", self.mode)), ("Orientation", format!("{:?}", self.play_as)), ("GameId", self.record.id.to_string()), ("ChatMuted", self.chat_muted.to_string())];
+        if let Some(session_token) = self.session_token.as_ref() {
+            tags.push(("SessionToken", session_token.clone()));
+        }
+        if let Some(time_control) = self.time_control {
+            tags.push(("TimeControl", format!("{}+{}", time_control.initial_ms / 1000, time_control.increment_ms / 1000)));
+        }
+        if let Some([white_ms, black_ms]) = self.clock_ms {
+            tags.push(("WhiteClockMs", white_ms.to_string()));
+            tags.push(("BlackClockMs", black_ms.to_string()));
+        }
+        let mut pgn: String = tags.into_iter().map(|(name, value)| format!("[{} \"{}\"]\n", name, value)).collect();
+        pgn.push_str(&self.record.to_pgn());
+        pgn
+    }
+
+    fn from_pgn(text: &str) -> Option<CurrentGame> {
+        let game = pgn::parse_pgn(text).into_iter().next()?;
+        let mode = match game.tag("Mode")? {
+            "AgainstYourself" => GameMode::AgainstYourself,
+            "Online" => GameMode::Online,
+            _ => return None,
+        };
+        let record = GameRecord::from_pgn(&game, game.tag("GameId").and_then(|id| id.parse().ok()).unwrap_or(0))?;
+        let play_as = if game.tag("Orientation") == Some("Black") { Color::Black } else { Color::White };
+        let time_control = game.tag("TimeControl").and_then(|time_control| time_control.split_once('+')).and_then(|(initial, increment)| Some(TimeControl { initial_ms: initial.parse::<u64>().ok()? * 1000, increment_ms: increment.parse::<u64>().ok()? * 1000 }));
+        let clock_ms = game.tag("WhiteClockMs").zip(game.tag("BlackClockMs")).and_then(|(white, black)| Some([white.parse().ok()?, black.parse().ok()?]));
+        Some(CurrentGame { mode, record, play_as, session_token: game.tag("SessionToken").map(str::to_string), time_control, clock_ms, chat_muted: game.tag("ChatMuted") == Some("true") })
+    }
+}
+
+fn current_game_path() -> PathBuf {
+    data_directory().join("current_game.pgn")
+}
+
+/// Saves the game in progress. The file is written next to the old one and then renamed over it, so a crash while
+/// saving leaves the previous save intact.
+pub fn save_current_game(current_game: &CurrentGame) -> io::Result<()> {
+    fs::create_dir_all(data_directory())?;
+    let path = current_game_path();
+    let temporary_path = path.with_extension("pgn.tmp");
+    fs::write(&temporary_path, current_game.to_pgn())?;
+    fs::rename(temporary_path, path)
+}
+
+/// The game that was in progress when the app was last closed, if any.
+pub fn load_current_game() -> Option<CurrentGame> {
+    fs::read_to_string(current_game_path()).ok().and_then(|text| CurrentGame::from_pgn(&text))
+}
+
+/// Forgets the game in progress, once it has ended or been abandoned.
+pub fn clear_current_game() {
+    let _ = fs::remove_file(current_game_path());
+}
+
+/// A game from the games directory.
+#[derive(Clone, Debug)]
+pub struct SavedGame {
+    pub path: PathBuf,
+    pub game: PgnGame,
+}
+
+impl SavedGame {
+    /// The game as a record that can be reviewed or saved again under the same id.
+    pub fn record(&self) -> Option<GameRecord> {
+        let id = self.path.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse().ok()).unwrap_or(0);
+        GameRecord::from_pgn(&self.game, id)
+    }
+}
+
+/// Every game in the games directory that matches the query, newest first. Files that cannot be read are skipped.
+pub fn search_games(query: &GameQuery) -> Vec<SavedGame> {
+    let Ok(entries) = fs::read_dir(games_directory()) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).filter(|path| path.extension().is_some_and(|extension| extension == "pgn")).collect();
+    // Saved games are named after the time they were created
+    paths.sort_by_key(|path| std::cmp::Reverse(path.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse::<u64>().ok()).unwrap_or(0)));
+    paths.into_iter().filter_map(|path| {
+        let text = fs::read_to_string(&path).ok()?;
+        let game = pgn::parse_pgn(&text).into_iter().next()?;
+        query.matches(&game).then_some(SavedGame { path, game })
+    }).collect()
+}
+
+/// Reads a puzzle file, as EPD when its extension is `.epd` and as CSV otherwise.
+pub fn load_puzzles(path: &Path) -> io::Result<Vec<Puzzle>> {
+    let text = fs::read_to_string(path)?;
+    if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("epd")) {
+        Ok(puzzle::parse_epd(&text))
+    } else {
+        Ok(puzzle::parse_csv(&text))
+    }
+}
+
+/// Reads a puzzle file on a background thread, see `load_puzzles`. The receiver gets the result once.
+pub fn load_puzzles_in_background(path: PathBuf) -> Receiver<io::Result<Vec<Puzzle>>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(load_puzzles(&path));
+    });
+    receiver
+}
+
+fn puzzle_stats_path() -> PathBuf {
+    data_directory().join("puzzles.txt")
+}
+
+/// The saved puzzle rating and results, or fresh ones when nothing has been saved yet.
+pub fn load_puzzle_stats() -> PuzzleStats {
+    fs::read_to_string(puzzle_stats_path()).map(|text| PuzzleStats::from_text(&text)).unwrap_or_default()
+}
+
+pub fn save_puzzle_stats(stats: &PuzzleStats) -> io::Result<()> {
+    fs::create_dir_all(data_directory())?;
+    fs::write(puzzle_stats_path(), stats.to_text())
+}
+
+fn opening_index_path() -> PathBuf {
+    data_directory().join("openings.idx")
+}
+
+/// The opening explorer's index of imported games, or an empty one when nothing has been imported.
+pub fn load_opening_index() -> OpeningIndex {
+    fs::read(opening_index_path()).ok().and_then(|bytes| OpeningIndex::from_bytes(&bytes)).unwrap_or_default()
+}
+
+/// Reads the opening index on a background thread, see `load_opening_index`. The receiver gets the index once.
+pub fn load_opening_index_in_background() -> Receiver<OpeningIndex> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = sender.send(load_opening_index());
+    });
+    receiver
+}
+
+pub fn save_opening_index(index: &OpeningIndex) -> io::Result<()> {
+    fs::create_dir_all(data_directory())?;
+    fs::write(opening_index_path(), index.to_bytes())
+}
+
+/// Preferences that apply to every board, changed from the main menu.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Settings {
+    /// Whether the squares a picked up or selected piece can move to are marked on the board.
+    pub show_legal_moves: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings { show_legal_moves: true }
+    }
+}
+
+impl Settings {
+    fn to_text(&self) -> String {
+        format!("legal_moves {}\n", if self.show_legal_moves { "on" } else { "off" })
+    }
+
+    fn from_text(text: &str) -> Self {
+        let mut settings = Settings::default();
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once(' ') else {
+                continue;
+            };
+            if key == "legal_moves" {
+                settings.show_legal_moves = value != "off";
+            }
+        }
+        settings
+    }
+}
+
+fn settings_path() -> PathBuf {
+    data_directory().join("settings.txt")
+}
+
+/// The saved settings, or the defaults when nothing has been saved yet.
+pub fn load_settings() -> Settings {
+    fs::read_to_string(settings_path()).map(|text| Settings::from_text(&text)).unwrap_or_default()
+}
+
+pub fn save_settings(settings: &Settings) -> io::Result<()> {
+    fs::create_dir_all(data_directory())?;
+    fs::write(settings_path(), settings.to_text())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_text_round_trip() {
+        for show_legal_moves in [true, false] {
+            let settings = Settings { show_legal_moves };
+            assert_eq!(Settings::from_text(&settings.to_text()), settings);
+        }
+    }
+
+    #[test]
+    fn settings_skip_unknown_lines() {
+        assert_eq!(Settings::from_text("theme dark\nlegal_moves off\ngarbage"), Settings { show_legal_moves: false });
+        assert_eq!(Settings::from_text(""), Settings::default());
+    }
+}
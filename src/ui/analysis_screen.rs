@@ -0,0 +1,653 @@
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::mpsc::Receiver,
+    time::{Duration, Instant},
+};
+
+use macroquad::prelude::*;
+
+use crate::{
+    chess::{
+        chess_board::GameStatus,
+        game_record::{self, GameRecord, MoveAnnotation, ShapeColor, MOVE_SUFFIXES},
+        game_tree::GameTree,
+        ChessBoard, ChessMove, Color as ChessColor, Coordinate,
+    },
+    draw::WindowParameters,
+    engine::{analysis, Analysis, Analyzer, Score, SearchInfo, SearchLimits},
+    explorer::{self, Continuation, ImportProgress, OpeningIndex},
+    store,
+};
+
+use super::{
+    layouts,
+    ui_chess_board::{Arrow, UIChessBoard},
+    ui_manager::UIManager,
+};
+
+const EVAL_BAR_WIDTH: f32 = 0.015;
+const MAX_LINES: usize = 5;
+const LINES_X: f32 = 0.6;
+const LINES_Y: f32 = 0.45;
+const LINE_WIDTH: f32 = 0.38;
+const LINE_HEIGHT: f32 = 0.05;
+/// Time between the moves of a line that is played out on the board.
+const PLAYBACK_INTERVAL: Duration = Duration::from_millis(600);
+/// How long the engine looks for the opponent's threat in each position.
+const THREAT_SEARCH_TIME: Duration = Duration::from_millis(1000);
+const BEST_MOVE_ARROW_COLOR: Color = Color::new(0.1, 0.6, 0.2, 0.8);
+const THREAT_ARROW_COLOR: Color = Color::new(0.85, 0.1, 0.1, 0.8);
+const EXPLORER_ROW_HEIGHT: f32 = 0.045;
+const MAX_EXPLORER_ROWS: usize = 5;
+const MOVETEXT_Y: f32 = 0.71;
+const MOVETEXT_ROW_HEIGHT: f32 = 0.033;
+const MOVETEXT_ROWS: usize = 3;
+const MOVETEXT_FONT_SIZE: f32 = 24.0;
+/// The space between two words of the movetext.
+const MOVETEXT_GAP: f32 = 0.006;
+
+/// The "Analysis" screen. Both sides are moved by hand while the engine evaluates the current position in the background.
+pub struct AnalysisScreen {
+    layout: UIManager,
+    /// Every line tried on this screen. Playing a move from an earlier position adds a variation.
+    tree: GameTree,
+    /// The node of `tree` whose position is on the board.
+    current: usize,
+    /// The tags the game is saved with. Its moves are in `tree`, so the record keeps none.
+    record: GameRecord,
+    /// The first row of the movetext that is shown.
+    movetext_scroll: usize,
+    /// Whether the movetext should scroll to the current move, set whenever another move is shown.
+    follow_current: bool,
+    analyzer: Analyzer,
+    /// How many of the best lines are searched and listed.
+    line_count: usize,
+    /// Moves of a clicked line that are still to be played, one every `PLAYBACK_INTERVAL`.
+    playback: VecDeque<ChessMove>,
+    next_playback_move: Instant,
+    show_best_move_arrow: bool,
+    show_threat_arrow: bool,
+    /// The running search for the opponent's threat in the current position.
+    threat_search: Option<Receiver<Option<SearchInfo>>>,
+    /// What the opponent would play if the side to move could pass.
+    threat: Option<ChessMove>,
+    /// Whether the opening explorer is listed instead of the engine lines.
+    show_explorer: bool,
+    /// The index of imported games, read from the data directory the first time the explorer is shown.
+    opening_index: Option<OpeningIndex>,
+    /// The index while it is still being read.
+    index_load: Option<Receiver<OpeningIndex>>,
+    /// The moves played from the current position in the imported games.
+    continuations: Vec<Continuation>,
+    import: Option<Receiver<ImportProgress>>,
+    /// Whether the controls for annotating the current move are shown instead of the engine lines.
+    show_annotations: bool,
+    /// The colour circles and arrows are drawn in with the right mouse button while no modifier key is held.
+    shape_color: ShapeColor,
+    /// How the last import or save went.
+    status: String,
+    ui_chess_board: UIChessBoard,
+}
+
+impl AnalysisScreen {
+    pub fn new(mut ui_chess_board: UIChessBoard) -> Self {
+        // Shapes drawn here are annotations of the current move
+        ui_chess_board.keep_drawings = false;
+        AnalysisScreen { layout: layouts::analysis(), tree: GameTree::new(ChessBoard::starting_positions()), current: GameTree::ROOT, record: GameRecord::new("Analysis", "?", "?"), movetext_scroll: 0, follow_current: true, analyzer: Analyzer::new(), line_count: 3, playback: VecDeque::new(), next_playback_move: Instant::now(), show_best_move_arrow: true, show_threat_arrow: false, threat_search: None, threat: None, show_explorer: false, opening_index: None, index_load: None, continuations: Vec::new(), import: None, show_annotations: false, shape_color: ShapeColor::Green, status: String::new(), ui_chess_board }
+    }
+
+    /// Turns the marks on the squares a picked up piece can move to on or off.
+    pub fn set_show_legal_moves(&mut self, show: bool) {
+        self.ui_chess_board.show_legal_moves = show;
+    }
+
+    /// Starts analysing the position the screen was left in.
+    pub fn start(&mut self) {
+        self.show(self.current);
+    }
+
+    /// Analyses a game with its variations from its first position. Saving writes it back over the same game.
+    pub fn load(&mut self, tree: GameTree, record: GameRecord) {
+        self.playback.clear();
+        self.tree = tree;
+        self.record = GameRecord { moves: Vec::new(), annotations: Vec::new(), ..record };
+        self.status.clear();
+        self.ui_chess_board.reset_board(&self.tree.board(GameTree::ROOT).squares);
+        self.show(GameTree::ROOT);
+    }
+
+    pub fn stop(&mut self) {
+        self.playback.clear();
+        self.analyzer.stop();
+        self.threat_search = None;
+    }
+
+    fn board(&self) -> &ChessBoard {
+        self.tree.board(self.current)
+    }
+
+    fn show(&mut self, node: usize) {
+        self.current = node;
+        self.follow_current = true;
+        let board = self.tree.board(node);
+        self.ui_chess_board.game_status = board.game_status;
+        self.ui_chess_board.update(&board.squares);
+        self.ui_chess_board.set_last_move(board, self.tree.node(node).chess_move);
+        self.analyzer.analyze(board, self.tree.history(node), self.line_count);
+        self.search_threat();
+        self.update_continuations();
+        self.update_annotation_controls();
+        let comment = self.tree.node(node).annotation.comment.clone().unwrap_or_default();
+        self.layout.set_input_text("Comment", &comment);
+    }
+
+    /// Shows the shapes of the current move on the board and its suffix on the annotation buttons.
+    fn update_annotation_controls(&mut self) {
+        let annotation = &self.tree.node(self.current).annotation;
+        self.ui_chess_board.set_shapes(annotation.shapes.clone());
+        if self.show_annotations {
+            layouts::add_annotation_buttons(&mut self.layout, &annotation.nags, self.shape_color);
+        }
+    }
+
+    fn set_show_annotations(&mut self, show_annotations: bool) {
+        self.show_annotations = show_annotations;
+        self.layout.add_button("Annotate", layouts::annotate_button(show_annotations));
+        if show_annotations {
+            let annotation = &self.tree.node(self.current).annotation;
+            layouts::add_annotation_panel(&mut self.layout, &annotation.nags, self.shape_color);
+            self.layout.set_input_text("Comment", &annotation.comment.clone().unwrap_or_default());
+        } else {
+            layouts::remove_annotation_panel(&mut self.layout);
+        }
+    }
+
+    /// Changes the annotation of the current move. The position before the first move cannot be annotated.
+    fn annotate(&mut self, change: impl FnOnce(&mut MoveAnnotation)) {
+        if self.current == GameTree::ROOT {
+            return;
+        }
+        change(&mut self.tree.node_mut(self.current).annotation);
+        self.update_annotation_controls();
+    }
+
+    fn update_continuations(&mut self) {
+        self.continuations = match (self.show_explorer, self.opening_index.as_ref()) {
+            (true, Some(index)) => index.continuations(self.tree.board(self.current)),
+            _ => Vec::new(),
+        };
+    }
+
+    /// Adds the games of a PGN file to the explorer. The current index stays in use until the import is done.
+    fn start_import(&mut self, path: &str) {
+        let path = path.trim();
+        if path.is_empty() || self.import.is_some() {
+            return;
+        }
+        // Without a loaded index the import reads the saved one itself, and its result replaces any load still running
+        self.index_load = None;
+        self.import = Some(explorer::import_in_background(PathBuf::from(path), self.opening_index.clone()));
+        self.status = "Importing...".to_string();
+    }
+
+    fn update_index_load(&mut self) {
+        if let Some(Ok(index)) = self.index_load.as_ref().map(|receiver| receiver.try_recv()) {
+            self.opening_index = Some(index);
+            self.index_load = None;
+            self.update_continuations();
+        }
+    }
+
+    fn update_import(&mut self) {
+        let Some(receiver) = self.import.as_ref() else {
+            return;
+        };
+        while let Ok(progress) = receiver.try_recv() {
+            match progress {
+                ImportProgress::Read(games) => self.status = format!("Importing... {} games read", games),
+                ImportProgress::Done(Ok(index)) => {
+                    self.status = format!("{} games in the explorer", index.games);
+                    self.opening_index = Some(index);
+                    self.import = None;
+                    self.update_continuations();
+                    return;
+                }
+                ImportProgress::Done(Err(error)) => {
+                    self.status = error;
+                    self.import = None;
+                    return;
+                }
+            }
+        }
+    }
+
+    fn search_threat(&mut self) {
+        self.threat = None;
+        let board = self.tree.board(self.current);
+        self.threat_search = (self.show_threat_arrow && board.game_status == GameStatus::Ongoing).then(|| analysis::search_threat_in_background(board, SearchLimits::time(THREAT_SEARCH_TIME)));
+    }
+
+    /// Shows the engine's best move and the opponent's threat as arrows, as far as they are turned on.
+    fn update_arrows(&mut self) {
+        if let Some(Ok(threat)) = self.threat_search.as_ref().map(|receiver| receiver.try_recv()) {
+            self.threat = threat.and_then(|info| info.best_move());
+            self.threat_search = None;
+        }
+
+        let mut arrows = Vec::new();
+        if self.show_threat_arrow {
+            if let Some(threat) = self.threat {
+                arrows.push(Arrow { from: threat.from_tuple(), to: threat.to_tuple(), color: THREAT_ARROW_COLOR });
+            }
+        }
+        if self.show_best_move_arrow {
+            if let Some(best_move) = self.analyzer.poll().and_then(|analysis| analysis.best().info.best_move()) {
+                arrows.push(Arrow { from: best_move.from_tuple(), to: best_move.to_tuple(), color: BEST_MOVE_ARROW_COLOR });
+            }
+        }
+        self.ui_chess_board.set_arrows(arrows);
+    }
+
+    fn play(&mut self, chess_move: ChessMove) -> bool {
+        match self.tree.play(self.current, chess_move) {
+            Ok(node) => {
+                self.show(node);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Takes the variation the current move is in off the tree, or the rest of the main line from the current move.
+    fn delete_current(&mut self) {
+        let node = self.tree.variation_start(self.current).unwrap_or(self.current);
+        if let Some(parent) = self.tree.delete(node) {
+            self.show(parent);
+        }
+    }
+
+    fn save(&mut self) {
+        self.status = match store::save_game_tree(&self.record, &self.tree) {
+            Ok(_) => "Saved to the game store".to_string(),
+            Err(error) => format!("Could not save the game: {}", error),
+        };
+    }
+
+    /// Where each word of the movetext goes when it is wrapped to the width of the panel: its node, text, row and x.
+    fn movetext_layout(&self, window_parameters: &WindowParameters) -> Vec<(usize, String, usize, f32)> {
+        let mut layout = Vec::new();
+        let (mut row, mut x) = (0, LINES_X);
+        for (node, word) in self.tree.movetext_words() {
+            let word = if word.starts_with('{') {
+                match self.tree.node(node).annotation.comment_text() {
+                    text if text.is_empty() => continue,
+                    text => format!("{{{}}}", text),
+                }
+            } else {
+                display_move(&word)
+            };
+            let width = measure_text(&word, None, MOVETEXT_FONT_SIZE as u16, 1.0).width / window_parameters.width;
+            if x > LINES_X && x + width > LINES_X + LINE_WIDTH {
+                row += 1;
+                x = LINES_X;
+            }
+            layout.push((node, word, row, x));
+            x += width + MOVETEXT_GAP;
+        }
+        layout
+    }
+
+    /// The node of the move under the mouse in the movetext.
+    fn hovered_word(&self, window_parameters: &WindowParameters) -> Option<usize> {
+        let (mouse_x, mouse_y) = window_parameters.mouse_position();
+        if !(MOVETEXT_Y..MOVETEXT_Y + MOVETEXT_ROWS as f32 * MOVETEXT_ROW_HEIGHT).contains(&mouse_y) {
+            return None;
+        }
+        let row = self.movetext_scroll + ((mouse_y - MOVETEXT_Y) / MOVETEXT_ROW_HEIGHT) as usize;
+        self.movetext_layout(window_parameters).into_iter().find(|(_, word, word_row, x)| *word_row == row && (*x..*x + measure_text(word, None, MOVETEXT_FONT_SIZE as u16, 1.0).width / window_parameters.width).contains(&mouse_x)).map(|(node, ..)| node)
+    }
+
+    fn update_movetext_scroll(&mut self, window_parameters: &WindowParameters) {
+        let layout = self.movetext_layout(window_parameters);
+        let rows = layout.last().map_or(0, |(_, _, row, _)| row + 1);
+        let max_scroll = rows.saturating_sub(MOVETEXT_ROWS);
+        let (mouse_x, mouse_y) = window_parameters.mouse_position();
+        if (LINES_X..=LINES_X + LINE_WIDTH).contains(&mouse_x) && (MOVETEXT_Y..MOVETEXT_Y + MOVETEXT_ROWS as f32 * MOVETEXT_ROW_HEIGHT).contains(&mouse_y) {
+            let wheel = mouse_wheel().1;
+            if wheel > 0.0 {
+                self.movetext_scroll = self.movetext_scroll.saturating_sub(1);
+            } else if wheel < 0.0 {
+                self.movetext_scroll += 1;
+            }
+        }
+        if self.follow_current {
+            self.follow_current = false;
+            if let Some((_, _, row, _)) = layout.iter().find(|(node, ..)| *node == self.current) {
+                if *row < self.movetext_scroll {
+                    self.movetext_scroll = *row;
+                } else if *row >= self.movetext_scroll + MOVETEXT_ROWS {
+                    self.movetext_scroll = row + 1 - MOVETEXT_ROWS;
+                }
+            }
+        }
+        self.movetext_scroll = self.movetext_scroll.min(max_scroll);
+    }
+
+    /// The index of the listed line under the mouse.
+    fn hovered_line(&self, window_parameters: &WindowParameters, analysis: &Analysis) -> Option<usize> {
+        let (mouse_x, mouse_y) = window_parameters.mouse_position();
+        if self.show_explorer || self.show_annotations || !(LINES_X..=LINES_X + LINE_WIDTH).contains(&mouse_x) || mouse_y < LINES_Y {
+            return None;
+        }
+        let index = ((mouse_y - LINES_Y) / LINE_HEIGHT) as usize;
+        (index < analysis.lines.len()).then_some(index)
+    }
+
+    /// The index of the explorer row under the mouse.
+    fn hovered_continuation(&self, window_parameters: &WindowParameters) -> Option<usize> {
+        let (mouse_x, mouse_y) = window_parameters.mouse_position();
+        if !self.show_explorer || !(LINES_X..=LINES_X + LINE_WIDTH).contains(&mouse_x) || mouse_y < LINES_Y {
+            return None;
+        }
+        let index = ((mouse_y - LINES_Y) / EXPLORER_ROW_HEIGHT) as usize;
+        (index < self.continuations.len().min(MAX_EXPLORER_ROWS)).then_some(index)
+    }
+
+    /// Returns true when the player wants to go back to the menu.
+    pub fn update(&mut self, window_parameters: &WindowParameters) -> bool {
+        self.layout.update(window_parameters);
+        self.ui_chess_board.update_assume_logic(window_parameters);
+
+        if self.layout.was_button_clicked("Flip") {
+            self.ui_chess_board.flip(&self.tree.board(self.current).squares);
+        }
+        if self.layout.was_button_clicked("Reset") {
+            self.load(GameTree::new(ChessBoard::starting_positions()), GameRecord::new("Analysis", "?", "?"));
+        }
+        let typing = self.layout.is_typing();
+        if self.layout.was_button_clicked("Undo") || (!typing && is_key_pressed(KeyCode::Left)) {
+            self.playback.clear();
+            if let Some(parent) = self.tree.node(self.current).parent {
+                self.show(parent);
+            }
+        }
+        if !typing && is_key_pressed(KeyCode::Right) {
+            self.playback.clear();
+            if let Some(&child) = self.tree.node(self.current).children.first() {
+                self.show(child);
+            }
+        }
+        if self.layout.was_button_clicked("Promote") {
+            self.tree.promote(self.current);
+        }
+        if self.layout.was_button_clicked("Delete") {
+            self.playback.clear();
+            self.delete_current();
+        }
+        if self.layout.was_button_clicked("Mainline") {
+            self.tree.make_mainline(self.current);
+        }
+        if self.layout.was_button_clicked("Save") {
+            self.save();
+        }
+        let line_count = if self.layout.was_button_clicked("Fewer lines") {
+            self.line_count.saturating_sub(1).max(1)
+        } else if self.layout.was_button_clicked("More lines") {
+            (self.line_count + 1).min(MAX_LINES)
+        } else {
+            self.line_count
+        };
+        if line_count != self.line_count {
+            self.line_count = line_count;
+            self.analyzer.analyze(self.tree.board(self.current), self.tree.history(self.current), self.line_count);
+        }
+        if self.layout.was_button_clicked("Best move arrow") {
+            self.show_best_move_arrow = !self.show_best_move_arrow;
+            self.layout.add_button("Best move arrow", layouts::best_move_arrow_button(self.show_best_move_arrow));
+        }
+        if self.layout.was_button_clicked("Threat arrow") {
+            self.show_threat_arrow = !self.show_threat_arrow;
+            self.layout.add_button("Threat arrow", layouts::threat_arrow_button(self.show_threat_arrow));
+            self.search_threat();
+        }
+        if self.layout.was_button_clicked("Explorer") {
+            self.show_explorer = !self.show_explorer;
+            if self.show_explorer && self.show_annotations {
+                self.set_show_annotations(false);
+            }
+            self.layout.add_button("Explorer", layouts::explorer_button(self.show_explorer));
+            if self.show_explorer && self.opening_index.is_none() && self.index_load.is_none() && self.import.is_none() {
+                self.index_load = Some(store::load_opening_index_in_background());
+            }
+            self.update_continuations();
+        }
+        if self.layout.was_button_clicked("Annotate") {
+            self.set_show_annotations(!self.show_annotations);
+            if self.show_annotations && self.show_explorer {
+                self.show_explorer = false;
+                self.layout.add_button("Explorer", layouts::explorer_button(false));
+                self.update_continuations();
+            }
+        }
+        if let Some((_, nag)) = MOVE_SUFFIXES.into_iter().find(|(symbol, _)| self.layout.was_button_clicked(&format!("Suffix {}", symbol))) {
+            self.annotate(|annotation| annotation.toggle_suffix(nag));
+        }
+        if let Some(comment) = self.layout.take_submitted_text("Comment") {
+            let comment = comment.trim().to_string();
+            self.annotate(|annotation| annotation.comment = (!comment.is_empty()).then_some(comment));
+        }
+        if let Some(color) = ShapeColor::ALL.into_iter().find(|color| self.layout.was_button_clicked(&format!("Shape {:?}", color))) {
+            self.shape_color = color;
+            self.update_annotation_controls();
+        }
+        if self.layout.was_button_clicked("Clear shapes") || self.ui_chess_board.was_left_clicked(window_parameters) && !self.tree.node(self.current).annotation.shapes.is_empty() {
+            self.annotate(|annotation| annotation.shapes.clear());
+        }
+        if let Some(shape) = self.ui_chess_board.take_drawn_shape(window_parameters, self.shape_color) {
+            self.annotate(|annotation| annotation.toggle_shape(shape));
+        }
+        if let Some(path) = self.layout.take_submitted_text("Import PGN") {
+            self.start_import(&path);
+        }
+        self.update_index_load();
+        self.update_import();
+
+        let movement_proposal = self.ui_chess_board.request_move(window_parameters);
+        if let Some((from, to)) = movement_proposal.0 {
+            let result = self.tree.play(self.current, ChessMove::new(Coordinate::from_tuple_usize(from), Coordinate::from_tuple_usize(to), movement_proposal.1));
+            if let Ok(node) = result {
+                self.playback.clear();
+                self.show(node);
+            }
+            self.ui_chess_board.check_result(result.map(|node| self.tree.board(node).game_status));
+            self.ui_chess_board.update(&self.tree.board(self.current).squares);
+        }
+        self.ui_chess_board.update_legal_moves(self.tree.board(self.current));
+
+        if is_mouse_button_pressed(MouseButton::Left) {
+            if let Some(analysis) = self.analyzer.poll().cloned() {
+                if let Some(index) = self.hovered_line(window_parameters, &analysis) {
+                    self.playback = analysis.lines[index].info.pv.iter().copied().collect();
+                    self.next_playback_move = Instant::now();
+                }
+            }
+            if let Some(index) = self.hovered_continuation(window_parameters) {
+                self.playback.clear();
+                self.play(self.continuations[index].chess_move);
+            }
+            if let Some(node) = self.hovered_word(window_parameters) {
+                self.playback.clear();
+                self.show(node);
+            }
+        }
+        if !self.playback.is_empty() && Instant::now() >= self.next_playback_move {
+            let chess_move = self.playback.pop_front().unwrap();
+            if !self.play(chess_move) {
+                self.playback.clear();
+            }
+            self.next_playback_move = Instant::now() + PLAYBACK_INTERVAL;
+        }
+        self.update_arrows();
+        self.update_movetext_scroll(window_parameters);
+
+        self.layout.was_button_clicked("Back")
+    }
+
+    fn render_eval_bar(&self, window_parameters: &WindowParameters, score: Option<Score>) {
+        let board = &self.ui_chess_board;
+        let x = board.x + board.width + 0.005;
+        window_parameters.render_rectangle(x, board.y, EVAL_BAR_WIDTH, board.height, DARKGRAY);
+
+        let white_share = match (score, self.board().game_status) {
+            (_, GameStatus::Win(ChessColor::White, _)) => 1.0,
+            (_, GameStatus::Win(ChessColor::Black, _)) => 0.0,
+            (_, GameStatus::Draw(_)) | (None, _) => 0.5,
+            (Some(score), _) => score.white_expectation(),
+        };
+        let white_height = board.height * white_share;
+        let white_y = match board.play_as {
+            ChessColor::White => board.y + board.height - white_height,
+            ChessColor::Black => board.y,
+        };
+        window_parameters.render_rectangle(x, white_y, EVAL_BAR_WIDTH, white_height, WHITE);
+        window_parameters.render_rectangle_line(x, board.y, EVAL_BAR_WIDTH, board.height, 0.001, GRAY);
+    }
+
+    fn render_lines(&self, window_parameters: &WindowParameters, analysis: &Analysis) {
+        let hovered = self.hovered_line(window_parameters, analysis);
+        let font_size = 28.0;
+        for (i, line) in analysis.lines.iter().enumerate() {
+            let y = LINES_Y + i as f32 * LINE_HEIGHT;
+            let background = if hovered == Some(i) { WHITE } else { Color::new(1.0, 1.0, 1.0, 0.6) };
+            window_parameters.render_rectangle(LINES_X, y, LINE_WIDTH, LINE_HEIGHT - 0.005, background);
+
+            // Lines that do not fit are cut off at the last whole move
+            let mut text = format!("{}. {}  {}", i + 1, line.info.score, line.pv_san);
+            while measure_text(&text, None, font_size as u16, 1.0).width / window_parameters.width > LINE_WIDTH - 0.01 {
+                match text.rfind(' ') {
+                    Some(end) => text.truncate(end),
+                    None => break,
+                }
+            }
+            window_parameters.render_text(&text, LINES_X + 0.005, y + LINE_HEIGHT * 0.65, font_size, BLACK);
+        }
+    }
+
+    /// Lists the moves played from the current position with their game counts and a bar of white wins, draws and black wins.
+    fn render_explorer(&self, window_parameters: &WindowParameters) {
+        let games = self.opening_index.as_ref().map_or(0, |index| index.games);
+        if self.continuations.is_empty() {
+            let text = if self.index_load.is_some() {
+                "Loading the explorer...".to_string()
+            } else if games == 0 {
+                "No games imported yet".to_string()
+            } else {
+                format!("Not played in the {} imported games", games)
+            };
+            window_parameters.render_text(&text, LINES_X, LINES_Y + EXPLORER_ROW_HEIGHT * 0.65, 28.0, DARKGRAY);
+        }
+
+        let hovered = self.hovered_continuation(window_parameters);
+        let bar_x = LINES_X + LINE_WIDTH * 0.4;
+        let bar_width = LINE_WIDTH * 0.6 - 0.005;
+        for (i, continuation) in self.continuations.iter().take(MAX_EXPLORER_ROWS).enumerate() {
+            let y = LINES_Y + i as f32 * EXPLORER_ROW_HEIGHT;
+            let background = if hovered == Some(i) { WHITE } else { Color::new(1.0, 1.0, 1.0, 0.6) };
+            window_parameters.render_rectangle(LINES_X, y, LINE_WIDTH, EXPLORER_ROW_HEIGHT - 0.005, background);
+            window_parameters.render_text(&continuation.san, LINES_X + 0.005, y + EXPLORER_ROW_HEIGHT * 0.65, 28.0, BLACK);
+            window_parameters.render_text(&continuation.stats.games().to_string(), LINES_X + LINE_WIDTH * 0.2, y + EXPLORER_ROW_HEIGHT * 0.65, 24.0, DARKGRAY);
+
+            let (white, draws, black) = continuation.stats.percentages();
+            let bar_y = y + 0.008;
+            let bar_height = EXPLORER_ROW_HEIGHT - 0.021;
+            let mut x = bar_x;
+            for (percentage, color, text_color) in [(white, WHITE, BLACK), (draws, LIGHTGRAY, BLACK), (black, DARKGRAY, WHITE)] {
+                let width = bar_width * percentage / 100.0;
+                window_parameters.render_rectangle(x, bar_y, width, bar_height, color);
+                if percentage >= 12.0 {
+                    window_parameters.render_text(&format!("{:.0}%", percentage), x + 0.003, bar_y + bar_height * 0.75, 20.0, text_color);
+                }
+                x += width;
+            }
+            window_parameters.render_rectangle_line(bar_x, bar_y, bar_width, bar_height, 0.001, GRAY);
+        }
+    }
+
+    /// The moves tried so far with their variations. The current move is highlighted and every move can be clicked.
+    fn render_movetext(&self, window_parameters: &WindowParameters) {
+        window_parameters.render_rectangle(LINES_X, MOVETEXT_Y, LINE_WIDTH, MOVETEXT_ROWS as f32 * MOVETEXT_ROW_HEIGHT, Color::new(1.0, 1.0, 1.0, 0.6));
+        let hovered = self.hovered_word(window_parameters);
+        for (node, word, row, x) in self.movetext_layout(window_parameters) {
+            if !(self.movetext_scroll..self.movetext_scroll + MOVETEXT_ROWS).contains(&row) {
+                continue;
+            }
+            let y = MOVETEXT_Y + (row - self.movetext_scroll) as f32 * MOVETEXT_ROW_HEIGHT;
+            let is_comment = word.starts_with('{');
+            if node == self.current && !is_comment {
+                let width = measure_text(&word, None, MOVETEXT_FONT_SIZE as u16, 1.0).width / window_parameters.width;
+                window_parameters.render_rectangle(x - 0.002, y + 0.002, width + 0.004, MOVETEXT_ROW_HEIGHT - 0.004, SKYBLUE);
+            }
+            let color = if is_comment {
+                DARKGREEN
+            } else if hovered == Some(node) {
+                BLUE
+            } else if self.tree.is_mainline(node) {
+                BLACK
+            } else {
+                DARKGRAY
+            };
+            window_parameters.render_text(&word, x, y + MOVETEXT_ROW_HEIGHT * 0.75, MOVETEXT_FONT_SIZE, color);
+        }
+    }
+
+    pub fn render(&mut self, window_parameters: &WindowParameters) {
+        self.ui_chess_board.render(window_parameters);
+        let analysis = self.analyzer.poll().cloned();
+        self.render_eval_bar(window_parameters, analysis.as_ref().map(|analysis| analysis.best().info.score));
+
+        let (evaluation, depth) = match (&analysis, self.board().game_status) {
+            (_, GameStatus::Win(color, _)) => (format!("{:?} won", color), String::new()),
+            (_, GameStatus::Draw(_)) => ("Draw".to_string(), String::new()),
+            (None, _) => ("Thinking...".to_string(), String::new()),
+            (Some(analysis), _) => {
+                let evaluation = match analysis.best().info.score {
+                    Score::Mate(moves) if moves > 0 => format!("White mates in {}", moves),
+                    Score::Mate(moves) => format!("Black mates in {}", -moves),
+                    score => format!("Evaluation {}", score),
+                };
+                (evaluation, format!("Depth {}", analysis.best().info.depth))
+            }
+        };
+        window_parameters.render_text(&evaluation, 0.6, 0.35, 40.0, BLACK);
+        window_parameters.render_text(&depth, 0.6, 0.4, 30.0, DARKGRAY);
+        window_parameters.render_text(&format!("{} lines", self.line_count), 0.745, 0.285, 30.0, BLACK);
+        if self.show_annotations {
+            let hint = if self.current == GameTree::ROOT { "Play or pick a move to annotate it" } else { "Right-click a square to highlight it, drag to draw an arrow" };
+            window_parameters.render_text(hint, LINES_X, 0.665, 22.0, DARKGRAY);
+        } else if self.show_explorer {
+            self.render_explorer(window_parameters);
+        } else if let Some(analysis) = analysis.as_ref() {
+            self.render_lines(window_parameters, analysis);
+        }
+        self.render_movetext(window_parameters);
+        window_parameters.render_text(&self.status, 0.6, 0.965, 22.0, DARKGRAY);
+        self.layout.render(window_parameters);
+    }
+}
+
+/// A move of the movetext with its NAGs shown as suffixes where they have one, e.g. `e4 $1` as `e4!`.
+fn display_move(word: &str) -> String {
+    word.split(' ').fold(String::new(), |mut shown, part| {
+        match part.strip_prefix('$').and_then(|nag| nag.parse().ok()).and_then(game_record::nag_symbol) {
+            Some(symbol) => shown.push_str(symbol),
+            None => {
+                if !shown.is_empty() {
+                    shown.push(' ');
+                }
+                shown.push_str(part);
+            }
+        }
+        shown
+    })
+}
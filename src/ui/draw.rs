@@ -114,6 +114,10 @@ impl WindowParameters {
         draw_circle(self.x_offset + x * self.width, self.y_offset + y * self.height, radius * self.width, color);
     }
 
+    pub fn render_circle_line(&self, x: f32, y: f32, radius: f32, thickness: f32, color: Color) {
+        draw_circle_lines(self.x_offset + x * self.width, self.y_offset + y * self.height, radius * self.width, thickness * self.width, color);
+    }
+
     pub fn render_rectangle(&self, x: f32, y: f32, width: f32, height: f32, color: Color) {
         draw_rectangle(self.x_offset + x * self.width, self.y_offset + y * self.height, width * self.width, height * self.height, color);
     }
@@ -122,6 +126,27 @@ impl WindowParameters {
         draw_rectangle_lines(self.x_offset + x * self.width, self.y_offset + y * self.height, width * self.width, height * self.height, thickness * self.width, color);
     }
 
+    pub fn render_line(&self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color) {
+        draw_line(self.x_offset + x1 * self.width, self.y_offset + y1 * self.height, self.x_offset + x2 * self.width, self.y_offset + y2 * self.height, thickness * self.width, color);
+    }
+
+    /// Draws an arrow from (x1, y1) to (x2, y2) whose tip ends at the second point. `thickness` is the width of the shaft.
+    pub fn render_arrow(&self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color) {
+        let start = vec2(self.x_offset + x1 * self.width, self.y_offset + y1 * self.height);
+        let end = vec2(self.x_offset + x2 * self.width, self.y_offset + y2 * self.height);
+        let length = start.distance(end);
+        if length == 0.0 {
+            return;
+        }
+        let direction = (end - start) / length;
+        let normal = vec2(-direction.y, direction.x);
+        let thickness = thickness * self.width;
+        let head_length = (thickness * 2.5).min(length);
+        let head_base = end - direction * head_length;
+        draw_line(start.x, start.y, head_base.x, head_base.y, thickness, color);
+        draw_triangle(end, head_base + normal * thickness * 1.5, head_base - normal * thickness * 1.5, color);
+    }
+
     pub fn render_text(&self, text: &str, x: f32, y: f32, font_size: f32, color: Color) {
         draw_text(text, self.x_offset + x * self.width, self.y_offset + y * self.height, font_size, color);
     }
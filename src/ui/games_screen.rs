@@ -0,0 +1,174 @@
+use macroquad::prelude::*;
+
+use crate::{
+    chess::{game_record::GameRecord, game_tree::GameTree, pgn::GameQuery, ChessBoard},
+    draw::WindowParameters,
+    store::{self, SavedGame},
+};
+
+use super::{layouts, ui_chess_board::UIChessBoard, ui_manager::UIManager};
+
+const LIST_X: f32 = 0.6;
+const LIST_Y: f32 = 0.36;
+const LIST_WIDTH: f32 = 0.38;
+const ROW_HEIGHT: f32 = 0.045;
+const VISIBLE_ROWS: usize = 10;
+const SEARCH_FIELDS: [&str; 6] = ["Player", "Date", "Result", "ECO", "Termination", "FEN"];
+
+/// What the player wants to do with a game from the list.
+pub enum GameRequest {
+    /// Review the game and save the accuracy with the variations saved in it.
+    Review(GameTree, GameRecord),
+    /// Replay the game, or open the replay screen without one to load a PGN file there.
+    Replay(Option<GameRecord>),
+    /// Analyse the game with the variations saved in it.
+    Analyse(GameTree, GameRecord),
+}
+
+/// The "Games" screen. Lists the games in the game store that match a search, and shows the final position of the
+/// selected one.
+pub struct GamesScreen {
+    layout: UIManager,
+    games: Vec<SavedGame>,
+    /// How many games are scrolled past at the top of the list.
+    scroll: usize,
+    selected: Option<(usize, GameRecord)>,
+    /// What the player asked for, taken by `take_request`.
+    request: Option<GameRequest>,
+    ui_chess_board: UIChessBoard,
+}
+
+impl GamesScreen {
+    pub fn new(mut ui_chess_board: UIChessBoard) -> Self {
+        ui_chess_board.interactive = false;
+        GamesScreen { layout: layouts::games(), games: Vec::new(), scroll: 0, selected: None, request: None, ui_chess_board }
+    }
+
+    /// Reads the games again, since games may have been saved since the screen was last shown.
+    pub fn start(&mut self) {
+        self.search();
+    }
+
+    fn query(&self) -> GameQuery {
+        let [player, date, result, eco, termination, fen] = SEARCH_FIELDS.map(|id| self.layout.input_text(id).to_string());
+        GameQuery { player, date, result, eco, termination, fen }
+    }
+
+    fn search(&mut self) {
+        self.games = store::search_games(&self.query());
+        self.scroll = 0;
+        self.select(None);
+    }
+
+    fn select(&mut self, index: Option<usize>) {
+        self.selected = index.and_then(|index| self.games[index].record().map(|record| (index, record)));
+        let board = match self.selected.as_ref() {
+            Some((_, record)) => record.board_after(record.moves.len()),
+            None => ChessBoard::starting_positions(),
+        };
+        self.ui_chess_board.game_status = board.game_status;
+        self.ui_chess_board.reset_board(&board.squares);
+        self.ui_chess_board.clear_drawings();
+        self.ui_chess_board.set_last_move(&board, self.selected.as_ref().and_then(|(_, record)| record.moves.last().copied()));
+    }
+
+    /// What the player asked to do with a game since the last call, if anything.
+    pub fn take_request(&mut self) -> Option<GameRequest> {
+        self.request.take()
+    }
+
+    /// The index into `games` of the row under the mouse.
+    fn hovered_row(&self, window_parameters: &WindowParameters) -> Option<usize> {
+        let (mouse_x, mouse_y) = window_parameters.mouse_position();
+        if !(LIST_X..=LIST_X + LIST_WIDTH).contains(&mouse_x) || !(LIST_Y..LIST_Y + VISIBLE_ROWS as f32 * ROW_HEIGHT).contains(&mouse_y) {
+            return None;
+        }
+        let index = self.scroll + ((mouse_y - LIST_Y) / ROW_HEIGHT) as usize;
+        (index < self.games.len()).then_some(index)
+    }
+
+    /// Returns true when the player wants to go back to the menu.
+    pub fn update(&mut self, window_parameters: &WindowParameters) -> bool {
+        self.layout.update(window_parameters);
+        self.ui_chess_board.update_assume_logic(window_parameters);
+
+        let submitted = SEARCH_FIELDS.iter().any(|id| self.layout.take_submitted_text(id).is_some());
+        if submitted || self.layout.was_button_clicked("Search") {
+            self.search();
+        }
+        if self.layout.was_button_clicked("Clear") {
+            for id in SEARCH_FIELDS {
+                self.layout.set_input_text(id, "");
+            }
+            self.search();
+        }
+
+        let (mouse_x, mouse_y) = window_parameters.mouse_position();
+        if (LIST_X..=LIST_X + LIST_WIDTH).contains(&mouse_x) && mouse_y >= LIST_Y {
+            let wheel = mouse_wheel().1;
+            let max_scroll = self.games.len().saturating_sub(VISIBLE_ROWS);
+            if wheel > 0.0 {
+                self.scroll = self.scroll.saturating_sub(1);
+            } else if wheel < 0.0 {
+                self.scroll = (self.scroll + 1).min(max_scroll);
+            }
+        }
+        if is_mouse_button_pressed(MouseButton::Left) {
+            if let Some(index) = self.hovered_row(window_parameters) {
+                self.select(Some(index));
+            }
+        }
+        let selected_record = self.selected.as_ref().map(|(_, record)| record.clone());
+        if self.layout.was_button_clicked("Review") {
+            self.request = self.selected.as_ref().filter(|(_, record)| !record.moves.is_empty()).and_then(|(index, record)| GameTree::from_pgn(&self.games[*index].game).map(|tree| GameRequest::Review(tree, record.clone())));
+        } else if self.layout.was_button_clicked("Replay") {
+            self.request = Some(GameRequest::Replay(selected_record));
+        } else if self.layout.was_button_clicked("Analyse") {
+            self.request = self.selected.as_ref().and_then(|(index, record)| GameTree::from_pgn(&self.games[*index].game).map(|tree| GameRequest::Analyse(tree, record.clone())));
+        }
+
+        self.layout.was_button_clicked("Back")
+    }
+
+    pub fn render(&mut self, window_parameters: &WindowParameters) {
+        self.ui_chess_board.render(window_parameters);
+
+        let summary = match self.games.len() {
+            0 if self.query().is_empty() => "No saved games yet".to_string(),
+            0 => "No games match the search".to_string(),
+            1 => "1 game".to_string(),
+            count => format!("{} games", count),
+        };
+        window_parameters.render_text(&summary, 0.86, 0.32, 26.0, DARKGRAY);
+
+        let hovered = self.hovered_row(window_parameters);
+        let selected = self.selected.as_ref().map(|(index, _)| *index);
+        for (row, (i, saved)) in self.games.iter().enumerate().skip(self.scroll).take(VISIBLE_ROWS).enumerate() {
+            let y = LIST_Y + row as f32 * ROW_HEIGHT;
+            let background = if selected == Some(i) {
+                SKYBLUE
+            } else if hovered == Some(i) {
+                WHITE
+            } else {
+                Color::new(1.0, 1.0, 1.0, 0.6)
+            };
+            window_parameters.render_rectangle(LIST_X, y, LIST_WIDTH, ROW_HEIGHT - 0.004, background);
+            let game = &saved.game;
+            let tag = |name: &str| game.tag(name).unwrap_or("?").to_string();
+            let text = format!("{}  {} - {}  {}  {}", tag("Date"), tag("White"), tag("Black"), game.result, game.tag("ECO").unwrap_or_default());
+            window_parameters.render_text(&text, LIST_X + 0.005, y + ROW_HEIGHT * 0.65, 24.0, BLACK);
+        }
+        if self.games.len() > VISIBLE_ROWS {
+            let shown = format!("{}-{} of {}", self.scroll + 1, (self.scroll + VISIBLE_ROWS).min(self.games.len()), self.games.len());
+            window_parameters.render_text(&shown, LIST_X, LIST_Y + VISIBLE_ROWS as f32 * ROW_HEIGHT + 0.025, 22.0, DARKGRAY);
+        }
+
+        if let Some((index, record)) = self.selected.as_ref() {
+            let game = &self.games[*index].game;
+            let opening = game.tag("Opening").map(|opening| format!(", {}", opening)).unwrap_or_default();
+            let details = format!("{}{}, {} moves, {}", record.event, opening, record.moves.len().div_ceil(2), game.tag("TerminationReason").or(game.tag("Termination")).unwrap_or("unknown ending"));
+            window_parameters.render_text(&details, 0.6, 0.95, 24.0, BLACK);
+        }
+        self.layout.render(window_parameters);
+    }
+}
@@ -1,8 +1,12 @@
 use macroquad::prelude::*;
 
-use super::ui_manager::*;
+use super::{ui_chess_board::shape_color, ui_manager::*};
+use crate::{
+    chess::game_record::{ShapeColor, MOVE_SUFFIXES},
+    network::protocol::MAX_CHAT_LENGTH,
+};
 
-pub fn main_menu() -> UIManager {
+pub fn main_menu(show_legal_moves: bool) -> UIManager {
     let mut main_menu = UIManager::new();
     main_menu.add_title(
         "Main Title",
@@ -10,24 +14,65 @@ pub fn main_menu() -> UIManager {
     );
     main_menu.add_button(
         "Against yourself",
-        Button::new_center_width(0.2, 0.3, 0.1, "Against yourself", BLUE, LIGHTGRAY),
+        Button::new_center_width(0.18, 0.3, 0.1, "Against yourself", BLUE, LIGHTGRAY),
     );
     main_menu.add_button(
         "Against bot",
-        Button::new_center_width(0.35, 0.3, 0.1, "Against bot", BLUE, LIGHTGRAY),
+        Button::new_center_width(0.31, 0.3, 0.1, "Against bot", BLUE, LIGHTGRAY),
     );
     main_menu.add_button(
         "Online",
-        Button::new_center_width(0.5, 0.3, 0.1, "Online", BLUE, LIGHTGRAY),
+        Button::new_center_width(0.44, 0.3, 0.1, "Online", BLUE, LIGHTGRAY),
+    );
+    main_menu.add_button(
+        "Analysis",
+        Button::new_center_width(0.57, 0.3, 0.1, "Analysis", BLUE, LIGHTGRAY),
+    );
+    main_menu.add_button(
+        "Puzzles",
+        Button::new_center_width(0.7, 0.3, 0.1, "Puzzles", BLUE, LIGHTGRAY),
+    );
+    main_menu.add_button(
+        "Games",
+        Button::new_center_width(0.83, 0.3, 0.1, "Games", BLUE, LIGHTGRAY),
     );
     main_menu.add_button(
         "Quit",
         Button::new(0.83, 0.0, 0.17, 0.08, "Quit Chess", BLUE, RED),
     );
+    main_menu.add_button("Legal moves", legal_moves_button(show_legal_moves));
 
     main_menu
 }
 
+/// Toggles the marks on the squares a picked up piece can move to, which some prefer off for serious games.
+pub fn legal_moves_button(shown: bool) -> Button {
+    let label = if shown { "legal moves: on" } else { "legal moves: off" };
+    Button::new(0.68, 0.18, 0.2, 0.06, label, BLUE, LIGHTGRAY)
+}
+
+/// Offers to resume the game that was in progress when the app was last closed.
+pub fn add_resume_prompt(main_menu: &mut UIManager, description: &str) {
+    main_menu.add_title(
+        "Unfinished game",
+        Title::new(description, 30.0, 0.68, 0.46, BLACK),
+    );
+    main_menu.add_button(
+        "Resume",
+        Button::new(0.68, 0.48, 0.12, 0.06, "resume", DARKGREEN, LIGHTGRAY),
+    );
+    main_menu.add_button(
+        "Discard",
+        Button::new(0.81, 0.48, 0.12, 0.06, "discard", BLUE, RED),
+    );
+}
+
+pub fn remove_resume_prompt(main_menu: &mut UIManager) {
+    main_menu.remove_title("Unfinished game");
+    main_menu.remove_button("Resume");
+    main_menu.remove_button("Discard");
+}
+
 pub fn against_yourself () -> UIManager{
     let back_button = Button::new(0.001, 0.001, 0.07, 0.04, "Back", GRAY, LIGHTGRAY);
 
@@ -41,6 +86,19 @@ pub fn against_yourself () -> UIManager{
         "Flip",
         Button::new(0.7, 0.1, 0.15, 0.05, "flip board", BLUE, GRAY),
     );
+    against_yourself.add_button(
+        "Hint",
+        Button::new(0.7, 0.3, 0.1, 0.05, "hint", BLUE, GREEN),
+    );
+    against_yourself.add_button(
+        "Save",
+        Button::new(0.7, 0.4, 0.1, 0.05, "save", BLUE, GRAY),
+    );
+    against_yourself.add_button(
+        "Analyze",
+        Button::new(0.7, 0.5, 0.15, 0.05, "analyze game", BLUE, GRAY),
+    );
+    add_move_list(&mut against_yourself);
 
     against_yourself
 }
@@ -53,18 +111,345 @@ pub fn against_bot () -> UIManager{
         "Not Implmented",
         Title::new_center_width("Not Implmented", 90.0, 0.4, RED),
     );
+    add_move_list(&mut against_bot);
 
     against_bot
 }
 
+pub fn analysis () -> UIManager{
+    let back_button = Button::new(0.001, 0.001, 0.07, 0.04, "Back", GRAY, LIGHTGRAY);
+    let mut analysis = UIManager::new();
+    analysis.add_button("Back", back_button.clone());
+    analysis.add_button(
+        "Flip",
+        Button::new(0.7, 0.1, 0.15, 0.05, "flip board", BLUE, GRAY),
+    );
+    analysis.add_button(
+        "Reset",
+        Button::new(0.7, 0.2, 0.055, 0.05, "reset", BLUE, RED),
+    );
+    analysis.add_button(
+        "Undo",
+        Button::new(0.765, 0.2, 0.085, 0.05, "undo", BLUE, GRAY),
+    );
+    analysis.add_button(
+        "Fewer lines",
+        Button::new(0.7, 0.25, 0.04, 0.05, "-", BLUE, GRAY),
+    );
+    analysis.add_button(
+        "More lines",
+        Button::new(0.81, 0.25, 0.04, 0.05, "+", BLUE, GRAY),
+    );
+    analysis.add_button("Best move arrow", best_move_arrow_button(true));
+    analysis.add_button("Threat arrow", threat_arrow_button(false));
+    analysis.add_button("Explorer", explorer_button(false));
+    analysis.add_button("Annotate", annotate_button(false));
+    analysis.add_button(
+        "Promote",
+        Button::new(0.6, 0.82, 0.089, 0.05, "promote", BLUE, GRAY),
+    );
+    analysis.add_button(
+        "Delete",
+        Button::new(0.697, 0.82, 0.089, 0.05, "delete", BLUE, RED),
+    );
+    analysis.add_button(
+        "Mainline",
+        Button::new(0.794, 0.82, 0.089, 0.05, "mainline", BLUE, GRAY),
+    );
+    analysis.add_button(
+        "Save",
+        Button::new(0.891, 0.82, 0.089, 0.05, "save", BLUE, GRAY),
+    );
+    analysis.add_text_input("Import PGN", TextInput::new(0.6, 0.88, 0.38, 0.05, "PGN file to add to the explorer, then Enter", 512));
+
+    analysis
+}
+
+pub fn puzzles () -> UIManager{
+    let back_button = Button::new(0.001, 0.001, 0.07, 0.04, "Back", GRAY, LIGHTGRAY);
+    let mut puzzles = UIManager::new();
+    puzzles.add_button("Back", back_button.clone());
+    puzzles.add_title(
+        "Puzzles",
+        Title::new("Puzzles", 50.0, 0.6, 0.1, BLACK),
+    );
+    puzzles.add_text_input("Puzzle file", TextInput::new(0.6, 0.15, 0.38, 0.05, "Puzzle file (.csv or .epd), then Enter", 512));
+    add_puzzle_buttons(&mut puzzles);
+    puzzles.add_button(
+        "Rush 3",
+        Button::new(0.6, 0.75, 0.12, 0.05, "rush 3 min", BLUE, GRAY),
+    );
+    puzzles.add_button(
+        "Rush 5",
+        Button::new(0.74, 0.75, 0.12, 0.05, "rush 5 min", BLUE, GRAY),
+    );
+
+    puzzles
+}
+
+/// The buttons for single puzzles, which are hidden during a puzzle rush.
+pub fn add_puzzle_buttons(puzzles: &mut UIManager) {
+    puzzles.add_button(
+        "Next",
+        Button::new(0.6, 0.6, 0.15, 0.05, "next puzzle", BLUE, GRAY),
+    );
+    puzzles.add_button(
+        "Retry",
+        Button::new(0.77, 0.6, 0.08, 0.05, "retry", BLUE, GRAY),
+    );
+    puzzles.add_button(
+        "Solution",
+        Button::new(0.6, 0.67, 0.2, 0.05, "show solution", BLUE, GRAY),
+    );
+}
+
+pub fn remove_puzzle_buttons(puzzles: &mut UIManager) {
+    for id in ["Next", "Retry", "Solution"] {
+        puzzles.remove_button(id);
+    }
+}
+
+pub fn games () -> UIManager{
+    let back_button = Button::new(0.001, 0.001, 0.07, 0.04, "Back", GRAY, LIGHTGRAY);
+    let mut games = UIManager::new();
+    games.add_button("Back", back_button.clone());
+    games.add_title(
+        "Games",
+        Title::new("Games", 50.0, 0.6, 0.08, BLACK),
+    );
+    games.add_text_input("Player", search_input(0.6, 0.12, 0.185, "Player"));
+    games.add_text_input("Date", search_input(0.795, 0.12, 0.185, "Date, e.g. 2026.10"));
+    games.add_text_input("Result", search_input(0.6, 0.175, 0.185, "Result: white, black, draw"));
+    games.add_text_input("ECO", search_input(0.795, 0.175, 0.185, "Opening or ECO code"));
+    games.add_text_input("Termination", search_input(0.6, 0.23, 0.185, "Termination, e.g. time"));
+    games.add_text_input("FEN", search_input(0.795, 0.23, 0.185, "Contains position (FEN)"));
+    games.add_button(
+        "Search",
+        Button::new(0.6, 0.29, 0.12, 0.045, "search", BLUE, GRAY),
+    );
+    games.add_button(
+        "Clear",
+        Button::new(0.73, 0.29, 0.12, 0.045, "clear", BLUE, GRAY),
+    );
+    games.add_button(
+        "Review",
+        Button::new(0.6, 0.87, 0.12, 0.05, "review", BLUE, GRAY),
+    );
+    games.add_button(
+        "Replay",
+        Button::new(0.73, 0.87, 0.12, 0.05, "replay", BLUE, GRAY),
+    );
+    games.add_button(
+        "Analyse",
+        Button::new(0.86, 0.87, 0.12, 0.05, "analyse", BLUE, GRAY),
+    );
+
+    games
+}
+
+/// A search field of the games screen, which keeps its text when Enter starts the search.
+fn search_input(x: f32, y: f32, width: f32, placeholder: &str) -> TextInput {
+    let mut text_input = TextInput::new(x, y, width, 0.045, placeholder, 100);
+    text_input.clear_on_submit = false;
+    text_input
+}
+
+pub fn replay () -> UIManager{
+    let back_button = Button::new(0.001, 0.001, 0.07, 0.04, "Back", GRAY, LIGHTGRAY);
+    let mut replay = UIManager::new();
+    replay.add_button("Back", back_button.clone());
+    replay.add_title(
+        "Replay",
+        Title::new("Replay", 50.0, 0.6, 0.08, BLACK),
+    );
+    replay.add_text_input("PGN file", TextInput::new(0.6, 0.17, 0.38, 0.045, "Open a PGN file, then Enter", 512));
+    replay.add_button(
+        "First",
+        Button::new(0.6, 0.24, 0.07, 0.05, "|<", BLUE, GRAY),
+    );
+    replay.add_button(
+        "Previous",
+        Button::new(0.68, 0.24, 0.07, 0.05, "<", BLUE, GRAY),
+    );
+    replay.add_button(
+        "Next",
+        Button::new(0.76, 0.24, 0.07, 0.05, ">", BLUE, GRAY),
+    );
+    replay.add_button(
+        "Last",
+        Button::new(0.84, 0.24, 0.07, 0.05, ">|", BLUE, GRAY),
+    );
+    replay.add_button("Autoplay", autoplay_button(false));
+    replay.add_button(
+        "Slower",
+        Button::new(0.77, 0.3, 0.04, 0.05, "-", BLUE, GRAY),
+    );
+    replay.add_button(
+        "Faster",
+        Button::new(0.9, 0.3, 0.04, 0.05, "+", BLUE, GRAY),
+    );
+
+    replay
+}
+
+pub fn autoplay_button(playing: bool) -> Button {
+    let (label, color) = if playing { ("pause", GREEN) } else { ("autoplay", BLUE) };
+    Button::new(0.6, 0.3, 0.15, 0.05, label, color, GRAY)
+}
+
+pub fn review () -> UIManager{
+    let back_button = Button::new(0.001, 0.001, 0.07, 0.04, "Back", GRAY, LIGHTGRAY);
+    let mut review = UIManager::new();
+    review.add_button("Back", back_button.clone());
+    review.add_title(
+        "Review",
+        Title::new("Game review", 50.0, 0.6, 0.1, BLACK),
+    );
+    review.add_button(
+        "Export",
+        Button::new(0.6, 0.85, 0.15, 0.05, "export pgn", BLUE, GRAY),
+    );
+
+    review
+}
+
+pub fn best_move_arrow_button(shown: bool) -> Button {
+    let color = if shown { GREEN } else { GRAY };
+    Button::new(0.86, 0.1, 0.13, 0.05, "best move", color, LIGHTGRAY)
+}
+
+pub fn threat_arrow_button(shown: bool) -> Button {
+    let color = if shown { RED } else { GRAY };
+    Button::new(0.86, 0.2, 0.13, 0.05, "threat", color, LIGHTGRAY)
+}
+
+pub fn annotate_button(shown: bool) -> Button {
+    let color = if shown { GREEN } else { GRAY };
+    Button::new(0.86, 0.15, 0.13, 0.05, "annotate", color, LIGHTGRAY)
+}
+
+/// Shows the controls for annotating the current move in place of the engine lines.
+pub fn add_annotation_panel(analysis: &mut UIManager, suffixes: &[u8], color: ShapeColor) {
+    let mut comment = TextInput::new(0.6, 0.52, 0.38, 0.05, "Comment on this move, then Enter", 300);
+    comment.clear_on_submit = false;
+    analysis.add_text_input("Comment", comment);
+    add_annotation_buttons(analysis, suffixes, color);
+    analysis.add_button(
+        "Clear shapes",
+        Button::new(0.904, 0.58, 0.076, 0.05, "clear", BLUE, RED),
+    );
+}
+
+/// The suffix and shape colour buttons of the annotation panel. `suffixes` are the NAGs of the current move, which are
+/// shown green, and `color` the colour new shapes are drawn in.
+pub fn add_annotation_buttons(analysis: &mut UIManager, suffixes: &[u8], color: ShapeColor) {
+    for (i, (symbol, nag)) in MOVE_SUFFIXES.into_iter().enumerate() {
+        let button_color = if suffixes.contains(&nag) { GREEN } else { BLUE };
+        analysis.add_button(
+            &format!("Suffix {}", symbol),
+            Button::new(0.6 + i as f32 * 0.064, 0.46, 0.058, 0.05, symbol, button_color, GRAY),
+        );
+    }
+    for (i, shape) in ShapeColor::ALL.into_iter().enumerate() {
+        let button_color = if shape == color { shape_color(shape) } else { GRAY };
+        analysis.add_button(
+            &format!("Shape {:?}", shape),
+            Button::new(0.6 + i as f32 * 0.076, 0.58, 0.07, 0.05, &format!("{:?}", shape).to_lowercase(), button_color, LIGHTGRAY),
+        );
+    }
+}
+
+pub fn remove_annotation_panel(analysis: &mut UIManager) {
+    for (symbol, _) in MOVE_SUFFIXES {
+        analysis.remove_button(&format!("Suffix {}", symbol));
+    }
+    analysis.remove_text_input("Comment");
+    for shape in ShapeColor::ALL {
+        analysis.remove_button(&format!("Shape {:?}", shape));
+    }
+    analysis.remove_button("Clear shapes");
+}
+
+pub fn explorer_button(shown: bool) -> Button {
+    let color = if shown { GREEN } else { GRAY };
+    Button::new(0.86, 0.25, 0.13, 0.05, "explorer", color, LIGHTGRAY)
+}
+
 pub fn online () -> UIManager{
     let back_button = Button::new(0.001, 0.001, 0.07, 0.04, "Back", GRAY, LIGHTGRAY);
     let mut online = UIManager::new();
     online.add_button("Back", back_button.clone());
     online.add_title(
-        "Not Implmented",
-        Title::new_center_width("Not Implmented", 90.0, 0.4, RED),
+        "Lobby",
+        Title::new_center_width("Lobby", 70.0, 0.1, BLACK),
     );
-    
+    online.add_button(
+        "Blitz",
+        Button::new(0.12, 0.2, 0.22, 0.08, "Blitz 5+3", BLUE, LIGHTGRAY),
+    );
+    online.add_button(
+        "Rapid",
+        Button::new(0.39, 0.2, 0.22, 0.08, "Rapid 10+5", BLUE, LIGHTGRAY),
+    );
+    online.add_button(
+        "Cancel challenge",
+        Button::new(0.66, 0.2, 0.22, 0.08, "Cancel challenge", BLUE, RED),
+    );
+
     online
-}
\ No newline at end of file
+}
+
+pub fn online_game () -> UIManager{
+    let back_button = Button::new(0.001, 0.001, 0.07, 0.04, "Back", GRAY, LIGHTGRAY);
+    let mut online_game = UIManager::new();
+    online_game.add_button("Back", back_button.clone());
+    online_game.add_button(
+        "Resign",
+        Button::new(0.7, 0.2, 0.1, 0.05, "resign", BLUE, RED),
+    );
+    online_game.add_button(
+        "Flip",
+        Button::new(0.7, 0.1, 0.15, 0.05, "flip board", BLUE, GRAY),
+    );
+    online_game.add_button(
+        "Lobby",
+        Button::new(0.7, 0.3, 0.15, 0.05, "to lobby", BLUE, GRAY),
+    );
+    add_move_list(&mut online_game);
+    add_chat(&mut online_game);
+
+    online_game
+}
+
+pub fn online_spectate () -> UIManager{
+    let back_button = Button::new(0.001, 0.001, 0.07, 0.04, "Back", GRAY, LIGHTGRAY);
+    let mut online_spectate = UIManager::new();
+    online_spectate.add_button("Back", back_button.clone());
+    online_spectate.add_button(
+        "Flip",
+        Button::new(0.7, 0.1, 0.15, 0.05, "flip board", BLUE, GRAY),
+    );
+    online_spectate.add_button(
+        "Lobby",
+        Button::new(0.7, 0.3, 0.15, 0.05, "to lobby", BLUE, GRAY),
+    );
+    add_move_list(&mut online_spectate);
+    add_chat(&mut online_spectate);
+
+    online_spectate
+}
+
+pub fn chat_mute_button(muted: bool) -> Button {
+    let label = if muted { "unmute" } else { "mute" };
+    Button::new(0.9, 0.89, 0.08, 0.05, label, GRAY, LIGHTGRAY)
+}
+
+fn add_move_list(layout: &mut UIManager) {
+    layout.add_move_list("Moves", MoveList::new(0.86, 0.1, 0.13, 0.4, 10));
+}
+
+fn add_chat(layout: &mut UIManager) {
+    layout.add_message_list("Chat", MessageList::new(0.7, 0.64, 0.28, 0.24, 24.0));
+    layout.add_text_input("Chat input", TextInput::new(0.7, 0.89, 0.19, 0.05, "Say something", MAX_CHAT_LENGTH));
+    layout.add_button("Mute", chat_mute_button(false));
+}
@@ -1,4 +1,10 @@
 pub mod ui_manager;
 pub mod draw;
 pub mod ui_chess_board;
-pub mod layouts;
\ No newline at end of file
+pub mod layouts;
+pub mod online_screen;
+pub mod analysis_screen;
+pub mod review_screen;
+pub mod puzzle_screen;
+pub mod games_screen;
+pub mod replay_screen;
@@ -0,0 +1,538 @@
+use std::{
+    io,
+    sync::mpsc::{Receiver, TryRecvError},
+    time::{Duration, Instant},
+};
+
+use macroquad::prelude::*;
+
+use crate::{
+    chess::{
+        chess_board::{GameStatus, MoveError},
+        clock::{self, ChessClock, TimeControl},
+        game_record::{self, GameRecord},
+        ChessBoard, ChessMove, Color as ChessColor, Coordinate,
+    },
+    draw::WindowParameters,
+    network::{
+        self,
+        client::{self, ServerConnection},
+        protocol::{self, ChallengeInfo, ClientMessage, GameInfo, ServerMessage},
+    },
+    store::{self, CurrentGame, GameMode},
+};
+
+use super::{
+    layouts,
+    ui_chess_board::UIChessBoard,
+    ui_manager::{Button, Title, UIManager},
+};
+
+const MAX_LISTED_ENTRIES: usize = 5;
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+enum LobbyEntry {
+    Challenge(u32),
+    Game(u32),
+}
+
+struct OnlineGame {
+    game_id: u32,
+    /// `None` while watching someone else's game.
+    color: Option<ChessColor>,
+    /// The other player's name, empty while watching.
+    opponent: String,
+    board: ChessBoard,
+    /// The moves in UCI notation.
+    moves: Vec<String>,
+    /// How many moves are played on the shown board while an earlier position is previewed from the move list.
+    preview: Option<usize>,
+    /// The result of the last move that arrived while an earlier position was previewed, applied once the game is shown again.
+    pending_result: Option<Result<GameStatus, MoveError>>,
+    has_ended: bool,
+    time_control: Option<TimeControl>,
+    clock: Option<ChessClock>,
+}
+
+/// The "Online" screen. It is a thin client: every move is sent to the server and only applied once the server confirms it.
+pub struct OnlineScreen {
+    lobby_layout: UIManager,
+    game_layout: UIManager,
+    spectate_layout: UIManager,
+    connection: Option<ServerConnection>,
+    /// The address and result of a connect that is still running on a background thread.
+    connecting: Option<(String, Receiver<io::Result<ServerConnection>>)>,
+    lobby_buttons: Vec<(String, LobbyEntry)>,
+    game: Option<OnlineGame>,
+    /// Token of the game we are seated in, used to resume it after the connection drops.
+    session_token: Option<String>,
+    reconnect_at: Option<Instant>,
+    player_id: Option<u32>,
+    chat_muted: bool,
+    ui_chess_board: UIChessBoard,
+}
+
+impl OnlineScreen {
+    pub fn new(ui_chess_board: UIChessBoard) -> Self {
+        OnlineScreen { lobby_layout: layouts::online(), game_layout: layouts::online_game(), spectate_layout: layouts::online_spectate(), connection: None, connecting: None, lobby_buttons: Vec::new(), game: None, session_token: None, reconnect_at: None, player_id: None, chat_muted: false, ui_chess_board }
+    }
+
+    /// Turns the marks on the squares a picked up piece can move to on or off.
+    pub fn set_show_legal_moves(&mut self, show: bool) {
+        self.ui_chess_board.show_legal_moves = show;
+    }
+
+    /// Starts connecting to the server, the result is picked up by `update`.
+    pub fn connect(&mut self) {
+        if self.connecting.is_some() {
+            return;
+        }
+        let address = network::server_address();
+        self.reconnect_at = None;
+        self.set_status(&format!("Connecting to {}", address));
+        self.connecting = Some((address.clone(), client::connect_in_background(address)));
+    }
+
+    fn update_connecting(&mut self) {
+        let Some((address, receiver)) = self.connecting.as_ref() else {
+            return;
+        };
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err(io::Error::other("The connection attempt stopped")),
+        };
+        let address = address.clone();
+        self.connecting = None;
+        match result {
+            Ok(mut connection) => {
+                let name = game_record::local_player_name();
+                connection.send(&ClientMessage::Hello { name });
+                if let Some(session_token) = self.session_token.clone() {
+                    connection.send(&ClientMessage::Resume { session_token });
+                }
+                self.connection = Some(connection);
+                self.set_status(&format!("Connected to {}", address));
+            }
+            Err(error) => {
+                self.connection = None;
+                if self.session_token.is_some() {
+                    self.reconnect_at = Some(Instant::now() + RECONNECT_INTERVAL);
+                }
+                self.set_status(&format!("Could not connect to {}: {}", address, error));
+            }
+        }
+    }
+
+    /// Connects and resumes the online game that was in progress when the app was last closed, if the server still has it.
+    pub fn resume(&mut self, current_game: &CurrentGame) {
+        self.session_token = current_game.session_token.clone();
+        self.chat_muted = current_game.chat_muted;
+        for layout in [&mut self.game_layout, &mut self.spectate_layout] {
+            layout.add_button("Mute", layouts::chat_mute_button(self.chat_muted));
+        }
+        self.connect();
+    }
+
+    /// Saves the game we are seated in after every change, so it can be resumed after the app was closed or crashed.
+    fn save_current_game(&mut self) {
+        let Some((game, color)) = self.game.as_ref().and_then(|game| game.color.filter(|_| !game.has_ended).map(|color| (game, color))) else {
+            return;
+        };
+        let mut current_game = CurrentGame::new(GameMode::Online, online_game_record(game, color, GameStatus::Ongoing), self.ui_chess_board.play_as);
+        current_game.session_token = self.session_token.clone();
+        current_game.time_control = game.time_control;
+        current_game.clock_ms = game.clock.as_ref().map(|clock| [ChessColor::White, ChessColor::Black].map(|color| clock.remaining_ms(color)));
+        current_game.chat_muted = self.chat_muted;
+        if let Err(error) = store::save_current_game(&current_game) {
+            self.set_status(&format!("Could not save the game: {}", error));
+        }
+    }
+
+    /// Leaves the server. A running game is kept on the server for the grace period and resumed on the next `connect`.
+    pub fn disconnect(&mut self) {
+        self.connection = None;
+        self.connecting = None;
+        self.game = None;
+        self.reconnect_at = None;
+        self.set_lobby(&[], &[]);
+    }
+
+    fn set_status(&mut self, status: &str) {
+        self.lobby_layout.add_title("Status", Title::new_center_width(status, 30.0, 0.9, BLACK));
+        self.game_layout.add_title("Status", Title::new(status, 30.0, 0.7, 0.6, BLACK));
+    }
+
+    fn start_game(&mut self, game_id: u32, color: ChessColor, opponent: &str, time_control: Option<TimeControl>, moves: Vec<String>) {
+        let mut board = ChessBoard::starting_positions();
+        for uci in moves.iter() {
+            if let Some(chess_move) = ChessMove::from_uci(uci) {
+                let _ = board.move_piece(chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion);
+            }
+        }
+        self.ui_chess_board.play_as = color;
+        self.ui_chess_board.player_color = Some(color);
+        self.ui_chess_board.interactive = true;
+        self.ui_chess_board.reset_board(&board.squares);
+        self.ui_chess_board.clear_drawings();
+        self.game_layout.add_title("Opponent", Title::new(&format!("Playing against {}", opponent), 40.0, 0.7, 0.5, BLACK));
+        self.game_layout.remove_title("Status");
+        self.game_layout.clear_messages("Chat");
+        self.game = Some(OnlineGame { game_id, color: Some(color), opponent: opponent.to_string(), board, moves, preview: None, pending_result: None, has_ended: false, time_control, clock: None });
+        self.list_moves();
+        self.show_last_move();
+    }
+
+    /// Shows the moves of the game in the move list, with the previewed or the last move highlighted.
+    fn list_moves(&mut self) {
+        let Some(game) = self.game.as_ref() else {
+            return;
+        };
+        let chess_moves: Vec<ChessMove> = game.moves.iter().filter_map(|uci| ChessMove::from_uci(uci)).collect();
+        let san_moves = ChessBoard::starting_positions().line_to_san(&chess_moves);
+        let preview = game.preview;
+        let layout = self.active_game_layout();
+        layout.set_moves("Moves", san_moves, 1, false);
+        if let Some(plies) = preview {
+            layout.set_current_move("Moves", Some(plies - 1));
+        }
+    }
+
+    /// The board of the game as it is shown, which is an earlier position while one is previewed.
+    fn shown_board(game: &OnlineGame) -> ChessBoard {
+        match game.preview {
+            Some(plies) => {
+                let mut board = ChessBoard::starting_positions();
+                for chess_move in game.moves.iter().take(plies).filter_map(|uci| ChessMove::from_uci(uci)) {
+                    let _ = board.move_piece(chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion);
+                }
+                board
+            }
+            None => game.board.clone(),
+        }
+    }
+
+    /// Highlights the move that led to the shown position and a king in check.
+    fn show_last_move(&mut self) {
+        let Some(game) = self.game.as_ref() else {
+            return;
+        };
+        let plies = game.preview.unwrap_or(game.moves.len());
+        let last_move = plies.checked_sub(1).and_then(|index| ChessMove::from_uci(&game.moves[index]));
+        self.ui_chess_board.set_last_move(&OnlineScreen::shown_board(game), last_move);
+        self.ui_chess_board.show_drawings_of(plies);
+    }
+
+    /// Shows the position after the move at `index` of the list, or the current position when it is the last move.
+    fn preview_move(&mut self, index: usize) {
+        let Some(game) = self.game.as_mut() else {
+            return;
+        };
+        game.preview = (index + 1 < game.moves.len()).then_some(index + 1);
+        if game.preview.is_none() {
+            if let Some(result) = game.pending_result.take() {
+                self.ui_chess_board.check_result(result);
+            }
+        }
+        let shown = OnlineScreen::shown_board(game);
+        self.ui_chess_board.interactive = game.preview.is_none() && game.color.is_some();
+        self.ui_chess_board.update(&shown.squares);
+        self.active_game_layout().set_current_move("Moves", Some(index));
+        self.show_last_move();
+    }
+
+    fn set_lobby(&mut self, challenges: &[ChallengeInfo], games: &[GameInfo]) {
+        for (id, _) in self.lobby_buttons.drain(..) {
+            self.lobby_layout.remove_button(&id);
+        }
+        for (i, challenge) in challenges.iter().take(MAX_LISTED_ENTRIES).enumerate() {
+            let color = match challenge.color {
+                Some(ChessColor::White) => "plays white",
+                Some(ChessColor::Black) => "plays black",
+                None => "random color",
+            };
+            let time_control = match challenge.time_control {
+                Some(time_control) => format!("{}+{}", time_control.initial_ms / 60_000, time_control.increment_ms / 1000),
+                None => "no clock".to_string(),
+            };
+            let id = format!("Challenge {}", challenge.challenge_id);
+            let label = format!("Join {} ({}, {})", challenge.player, time_control, color);
+            self.lobby_layout.add_button(&id, Button::new(0.05, 0.35 + i as f32 * 0.1, 0.42, 0.08, &label, BLUE, LIGHTGRAY));
+            self.lobby_buttons.push((id, LobbyEntry::Challenge(challenge.challenge_id)));
+        }
+        for (i, game) in games.iter().take(MAX_LISTED_ENTRIES).enumerate() {
+            let id = format!("Game {}", game.game_id);
+            let label = format!("Watch {} vs {}", game.white, game.black);
+            self.lobby_layout.add_button(&id, Button::new(0.53, 0.35 + i as f32 * 0.1, 0.42, 0.08, &label, DARKGREEN, LIGHTGRAY));
+            self.lobby_buttons.push((id, LobbyEntry::Game(game.game_id)));
+        }
+    }
+
+    fn send(&mut self, message: ClientMessage) {
+        if let Some(connection) = self.connection.as_mut() {
+            connection.send(&message);
+        }
+    }
+
+    fn handle_server_messages(&mut self) {
+        let messages = match self.connection.as_mut() {
+            Some(connection) => connection.poll(),
+            None => return,
+        };
+        for message in messages {
+            match message {
+                ServerMessage::Welcome { player_id } => self.player_id = Some(player_id),
+                ServerMessage::Lobby { challenges, games } => self.set_lobby(&challenges, &games),
+                ServerMessage::ChallengeCreated { .. } => self.set_status("Waiting for an opponent to accept your challenge"),
+                ServerMessage::GameStarted { game_id, color, opponent, time_control, session_token } => {
+                    self.session_token = Some(session_token);
+                    self.start_game(game_id, color, &opponent, time_control, Vec::new());
+                    self.save_current_game();
+                }
+                ServerMessage::Resumed { game_id, color, opponent, time_control, moves } => {
+                    self.start_game(game_id, color, &opponent, time_control, moves);
+                    self.save_current_game();
+                }
+                ServerMessage::OpponentDisconnected { grace_ms, .. } => self.set_status(&format!("Opponent disconnected, they have {} seconds to return", grace_ms / 1000)),
+                ServerMessage::OpponentReconnected { .. } => self.set_status("Opponent reconnected"),
+                ServerMessage::Chat { game_id, player_id, from, text } => {
+                    let is_current_game = self.game.as_ref().is_some_and(|game| game.game_id == game_id);
+                    if is_current_game && (!self.chat_muted || Some(player_id) == self.player_id) {
+                        self.active_game_layout().push_message("Chat", &format!("{}: {}", from, text));
+                    }
+                }
+                ServerMessage::Observing { game_id, white, black, fen, moves, time_control } => match ChessBoard::from_fen(&fen) {
+                    Ok(board) => {
+                        self.ui_chess_board.play_as = ChessColor::White;
+                        self.ui_chess_board.player_color = None;
+                        self.ui_chess_board.interactive = false;
+                        self.ui_chess_board.reset_board(&board.squares);
+                        self.ui_chess_board.clear_drawings();
+                        self.spectate_layout.add_title("Players", Title::new(&format!("Watching {} vs {}", white, black), 40.0, 0.7, 0.5, BLACK));
+                        self.spectate_layout.clear_messages("Chat");
+                        self.game = Some(OnlineGame { game_id, color: None, opponent: String::new(), board, moves, preview: None, pending_result: None, has_ended: false, time_control, clock: None });
+                        self.list_moves();
+                        self.show_last_move();
+                    }
+                    Err(error) => self.set_status(&format!("Could not read the position of the game: {:?}", error)),
+                },
+                ServerMessage::Clock { game_id, white_ms, black_ms, running } => {
+                    if let Some(game) = self.game.as_mut() {
+                        if let (true, Some(time_control)) = (game.game_id == game_id, game.time_control) {
+                            let mut clock = ChessClock::with_remaining(time_control, white_ms, black_ms);
+                            if let Some(running) = running {
+                                clock.start(running);
+                            }
+                            game.clock = Some(clock);
+                        }
+                    }
+                    self.save_current_game();
+                }
+                ServerMessage::Move { game_id, uci } => {
+                    if let (Some(game), Some(chess_move)) = (self.game.as_mut(), ChessMove::from_uci(&uci)) {
+                        if game.game_id == game_id {
+                            let result = game.board.move_piece(chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion);
+                            game.moves.push(uci);
+                            // A previewed position stays on the board until the player goes back to the game
+                            if game.preview.is_none() {
+                                self.ui_chess_board.check_result(result);
+                                self.ui_chess_board.update(&game.board.squares);
+                                self.ui_chess_board.set_last_move(&game.board, Some(chess_move));
+                                self.ui_chess_board.show_drawings_of(game.moves.len());
+                            } else {
+                                game.pending_result = Some(result);
+                            }
+                        }
+                    }
+                    self.list_moves();
+                    self.save_current_game();
+                }
+                ServerMessage::MoveRejected { reason, .. } => self.set_status(&format!("Move rejected: {}", reason)),
+                ServerMessage::GameEnded { game_id, result, reason } => {
+                    let mut save_error = None;
+                    if let Some(game) = self.game.as_mut() {
+                        if game.game_id == game_id {
+                            game.has_ended = true;
+                            let game_status = protocol::game_status_from_result(&result, &reason);
+                            if let Some(game_status) = game_status {
+                                // The server's result replaces that of a move still waiting for the preview to end
+                                game.pending_result = None;
+                                self.ui_chess_board.game_status = game_status;
+                            }
+                            if let Some(color) = game.color {
+                                self.session_token = None;
+                                store::clear_current_game();
+                                // Games we played are kept in the game store, games we watched are not
+                                save_error = store::save_game(&online_game_record(game, color, game_status.unwrap_or(game.board.game_status))).err();
+                            }
+                        }
+                    }
+                    if let Some(error) = save_error {
+                        self.set_status(&format!("Could not save the game: {}", error));
+                    }
+                }
+                ServerMessage::ResumeFailed { reason } => {
+                    // The game ended or was abandoned while we were away, so there is nothing left to reconnect to
+                    self.session_token = None;
+                    self.reconnect_at = None;
+                    self.game = None;
+                    store::clear_current_game();
+                    self.set_status(&reason);
+                }
+                ServerMessage::Error { message } => self.set_status(&message),
+            }
+        }
+        if self.connection.as_ref().is_some_and(|connection| !connection.is_connected()) {
+            self.connection = None;
+            if self.session_token.is_some() {
+                self.reconnect_at = Some(Instant::now() + RECONNECT_INTERVAL);
+                self.set_status("Lost connection to the server, reconnecting");
+            } else {
+                self.disconnect();
+                self.set_status("Lost connection to the server");
+            }
+        }
+    }
+
+    /// Returns true when the player wants to go back to the main menu.
+    pub fn update(&mut self, window_parameters: &WindowParameters) -> bool {
+        if self.reconnect_at.is_some_and(|reconnect_at| Instant::now() >= reconnect_at) {
+            self.connect();
+        }
+        self.update_connecting();
+        self.handle_server_messages();
+
+        if self.game.is_none() {
+            self.lobby_layout.update(window_parameters);
+            if self.lobby_layout.was_button_clicked("Blitz") {
+                self.send(ClientMessage::CreateChallenge { color: None, time_control: Some(TimeControl::new(5, 3)) });
+            }
+            if self.lobby_layout.was_button_clicked("Rapid") {
+                self.send(ClientMessage::CreateChallenge { color: None, time_control: Some(TimeControl::new(10, 5)) });
+            }
+            if self.lobby_layout.was_button_clicked("Cancel challenge") {
+                self.send(ClientMessage::CancelChallenge);
+                self.set_status("Challenge cancelled");
+            }
+            let clicked = self.lobby_buttons.iter().find(|(id, _)| self.lobby_layout.was_button_clicked(id)).map(|(_, entry)| match entry {
+                LobbyEntry::Challenge(challenge_id) => ClientMessage::AcceptChallenge { challenge_id: *challenge_id },
+                LobbyEntry::Game(game_id) => ClientMessage::Watch { game_id: *game_id },
+            });
+            if let Some(message) = clicked {
+                self.send(message);
+            }
+            return self.lobby_layout.was_button_clicked("Back");
+        }
+
+        let layout = self.active_game_layout();
+        layout.update(window_parameters);
+        let (flip_clicked, resign_clicked, lobby_clicked, back_clicked) = (layout.was_button_clicked("Flip"), layout.was_button_clicked("Resign"), layout.was_button_clicked("Lobby"), layout.was_button_clicked("Back"));
+        let (chat_text, mute_clicked) = (layout.take_submitted_text("Chat input"), layout.was_button_clicked("Mute"));
+        if let Some(index) = layout.take_clicked_move("Moves") {
+            self.preview_move(index);
+        }
+
+        if mute_clicked {
+            self.chat_muted = !self.chat_muted;
+            for layout in [&mut self.game_layout, &mut self.spectate_layout] {
+                layout.add_button("Mute", layouts::chat_mute_button(self.chat_muted));
+                layout.push_message("Chat", if self.chat_muted { "Chat muted" } else { "Chat unmuted" });
+            }
+            self.save_current_game();
+        }
+        if let (Some(text), Some(game_id)) = (chat_text, self.game.as_ref().map(|game| game.game_id)) {
+            self.send(ClientMessage::Chat { game_id, text });
+        }
+
+        self.ui_chess_board.update_assume_logic(window_parameters);
+        let movement_proposal = self.ui_chess_board.request_move(window_parameters);
+
+        let mut message = None;
+        if let Some(game) = self.game.as_mut() {
+            if let Some((from, to)) = movement_proposal.0 {
+                if from != to && !game.has_ended && Some(game.board.side_to_move()) == game.color {
+                    let chess_move = ChessMove::new(Coordinate::from_tuple_usize(from), Coordinate::from_tuple_usize(to), movement_proposal.1);
+                    message = Some(ClientMessage::Move { game_id: game.game_id, uci: chess_move.to_uci() });
+                }
+                self.ui_chess_board.update(&game.board.squares);
+            }
+            if flip_clicked {
+                self.ui_chess_board.flip(&OnlineScreen::shown_board(game).squares);
+            }
+            self.ui_chess_board.update_legal_moves(&game.board);
+            if resign_clicked && !game.has_ended {
+                message = Some(ClientMessage::Resign { game_id: game.game_id });
+            }
+        }
+        if let Some(message) = message {
+            self.send(message);
+        }
+
+        if flip_clicked {
+            self.save_current_game();
+        }
+        if lobby_clicked {
+            self.session_token = None;
+            store::clear_current_game();
+            if let Some(game) = self.game.take() {
+                match game.color {
+                    None => self.send(ClientMessage::StopWatching { game_id: game.game_id }),
+                    Some(_) if !game.has_ended => self.send(ClientMessage::Resign { game_id: game.game_id }),
+                    Some(_) => (),
+                }
+            }
+            self.send(ClientMessage::ListChallenges);
+            self.set_status("Back in the lobby");
+        }
+        back_clicked
+    }
+
+    fn active_game_layout(&mut self) -> &mut UIManager {
+        match self.game.as_ref().and_then(|game| game.color) {
+            Some(_) => &mut self.game_layout,
+            None => &mut self.spectate_layout,
+        }
+    }
+
+    fn render_clocks(&self, window_parameters: &WindowParameters, clock: &ChessClock) {
+        let mut top_color = self.ui_chess_board.play_as;
+        top_color.switch();
+        let bottom_y = self.ui_chess_board.y + self.ui_chess_board.height - 0.07;
+        for (color, y) in [(top_color, self.ui_chess_board.y), (self.ui_chess_board.play_as, bottom_y)] {
+            let background = if clock.running() == Some(color) { WHITE } else { LIGHTGRAY };
+            window_parameters.render_rectangle(0.57, y, 0.1, 0.07, background);
+            window_parameters.render_text(&clock::format_clock(clock.remaining(color)), 0.58, y + 0.05, 50.0, BLACK);
+        }
+    }
+
+    pub fn render(&mut self, window_parameters: &WindowParameters) {
+        if let Some(game) = self.game.as_ref() {
+            self.ui_chess_board.render(window_parameters);
+            if let Some(clock) = game.clock.as_ref() {
+                self.render_clocks(window_parameters, clock);
+            }
+            self.active_game_layout().render(window_parameters);
+        } else {
+            self.lobby_layout.render(window_parameters);
+        }
+    }
+}
+
+/// The record of a finished online game, for the game store.
+fn online_game_record(game: &OnlineGame, color: ChessColor, status: GameStatus) -> GameRecord {
+    let name = game_record::local_player_name();
+    let (white, black) = match color {
+        ChessColor::White => (name.as_str(), game.opponent.as_str()),
+        ChessColor::Black => (game.opponent.as_str(), name.as_str()),
+    };
+    let mut record = GameRecord::new("Online", white, black);
+    record.site = network::server_address();
+    let mut board = ChessBoard::starting_positions();
+    for chess_move in game.moves.iter().filter_map(|uci| ChessMove::from_uci(uci)) {
+        if let Ok(move_status) = board.move_piece(chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion) {
+            record.push_move(chess_move, move_status);
+        }
+    }
+    record.status = status;
+    record
+}
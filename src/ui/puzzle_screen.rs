@@ -0,0 +1,382 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    io,
+    path::PathBuf,
+    sync::mpsc::Receiver,
+    time::{Duration, Instant},
+};
+
+use macroquad::prelude::*;
+
+use crate::{
+    chess::{
+        chess_board::{GameStatus, WinType},
+        clock, game_record,
+        puzzle::{self, Puzzle, PuzzleStats, RushScore},
+        ChessBoard, ChessMove, Color as ChessColor, Coordinate,
+    },
+    draw::WindowParameters,
+    store,
+};
+
+use super::{layouts, ui_chess_board::UIChessBoard, ui_manager::UIManager};
+
+/// Time before the opponent's reply, so the player can see it being played.
+const REPLY_DELAY: Duration = Duration::from_millis(500);
+/// Mistakes that end a puzzle rush.
+const RUSH_STRIKES: u32 = 3;
+/// The puzzle rating a rush starts at and how much it rises with every solved puzzle.
+const RUSH_START_RATING: f32 = 800.0;
+const RUSH_RATING_STEP: f32 = 75.0;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum PuzzleState {
+    Playing,
+    Solved,
+    Failed,
+}
+
+/// A timed run of puzzles that get harder with every solved one.
+struct Rush {
+    minutes: u32,
+    ends_at: Instant,
+    score: u32,
+    strikes: u32,
+    /// Puzzles already shown in this rush.
+    used: HashSet<usize>,
+    /// When the next puzzle is put on the board, a moment after the last one was finished.
+    next_puzzle_at: Option<Instant>,
+    over: bool,
+}
+
+/// A puzzle file that is read on a background thread.
+struct PuzzleFileLoad {
+    path: String,
+    receiver: Receiver<io::Result<Vec<Puzzle>>>,
+}
+
+/// The "Puzzles" screen. The player finds the solution of puzzles from a local file while the opponent's replies are
+/// played for them.
+pub struct PuzzleScreen {
+    layout: UIManager,
+    puzzles: Vec<Puzzle>,
+    stats: PuzzleStats,
+    current: Option<usize>,
+    board: ChessBoard,
+    /// How many moves of the solution have been played.
+    progress: usize,
+    /// How many moves have been played on the board since the puzzle started, the opening move included.
+    played: usize,
+    state: PuzzleState,
+    /// Moves that are played by themselves, one every `REPLY_DELAY`: the opponent's replies and a shown solution.
+    queued_moves: VecDeque<ChessMove>,
+    next_queued_move: Instant,
+    /// Whether the first queued move is the opening move, which is not part of the solution.
+    opening_move_pending: bool,
+    message: String,
+    /// The running or last finished puzzle rush. Puzzles of a rush do not change the puzzle rating.
+    rush: Option<Rush>,
+    loading: Option<PuzzleFileLoad>,
+    ui_chess_board: UIChessBoard,
+}
+
+impl PuzzleScreen {
+    pub fn new(ui_chess_board: UIChessBoard) -> Self {
+        PuzzleScreen { layout: layouts::puzzles(), puzzles: Vec::new(), stats: PuzzleStats::default(), current: None, board: ChessBoard::starting_positions(), progress: 0, played: 0, state: PuzzleState::Playing, queued_moves: VecDeque::new(), next_queued_move: Instant::now(), opening_move_pending: false, message: String::new(), rush: None, loading: None, ui_chess_board }
+    }
+
+    /// Turns the marks on the squares a picked up piece can move to on or off.
+    pub fn set_show_legal_moves(&mut self, show: bool) {
+        self.ui_chess_board.show_legal_moves = show;
+    }
+
+    /// Loads the saved rating and, the first time, the puzzle file that was used last.
+    pub fn start(&mut self) {
+        self.stats = store::load_puzzle_stats();
+        if self.puzzles.is_empty() {
+            match self.stats.puzzle_file.clone() {
+                Some(puzzle_file) => self.load_file(&puzzle_file),
+                None => self.message = "Enter the path of a CSV or EPD puzzle file".to_string(),
+            }
+        }
+    }
+
+    /// Starts reading a puzzle file. The puzzles on the board stay in use until it has been read.
+    fn load_file(&mut self, path: &str) {
+        if self.loading.is_some() {
+            return;
+        }
+        self.loading = Some(PuzzleFileLoad { path: path.to_string(), receiver: store::load_puzzles_in_background(PathBuf::from(path)) });
+        self.message = format!("Loading {}...", path);
+    }
+
+    fn update_loading(&mut self) {
+        let Some(result) = self.loading.as_ref().and_then(|loading| loading.receiver.try_recv().ok()) else {
+            return;
+        };
+        let path = self.loading.take().unwrap().path;
+        match result {
+            Ok(puzzles) if puzzles.is_empty() => self.message = format!("No puzzles found in {}", path),
+            Ok(puzzles) => {
+                self.puzzles = puzzles;
+                self.current = None;
+                self.leave_rush();
+                self.stats.puzzle_file = Some(path);
+                self.save_stats();
+                self.next_puzzle();
+            }
+            Err(error) => self.message = format!("Could not read {}: {}", path, error),
+        }
+    }
+
+    fn save_stats(&mut self) {
+        if let Err(error) = store::save_puzzle_stats(&self.stats) {
+            self.message = format!("Could not save the puzzle rating: {}", error);
+        }
+    }
+
+    fn start_rush(&mut self, minutes: u32) {
+        if self.puzzles.is_empty() {
+            return;
+        }
+        layouts::remove_puzzle_buttons(&mut self.layout);
+        self.rush = Some(Rush { minutes, ends_at: Instant::now() + Duration::from_secs(minutes as u64 * 60), score: 0, strikes: 0, used: HashSet::new(), next_puzzle_at: None, over: false });
+        self.next_rush_puzzle();
+    }
+
+    fn next_rush_puzzle(&mut self) {
+        let Some(rush) = self.rush.as_mut() else {
+            return;
+        };
+        rush.next_puzzle_at = None;
+        let rating = RUSH_START_RATING + RUSH_RATING_STEP * rush.score as f32;
+        match puzzle::closest_puzzle(&self.puzzles, rating, &rush.used) {
+            Some(index) => {
+                rush.used.insert(index);
+                self.current = Some(index);
+                self.set_up();
+            }
+            None => self.end_rush(),
+        }
+    }
+
+    fn end_rush(&mut self) {
+        let Some(rush) = self.rush.as_mut().filter(|rush| !rush.over) else {
+            return;
+        };
+        rush.over = true;
+        let (minutes, score) = (rush.minutes, rush.score);
+        self.queued_moves.clear();
+        self.state = PuzzleState::Failed;
+        layouts::add_puzzle_buttons(&mut self.layout);
+        let new_best = self.stats.add_rush_score(RushScore { minutes, score, date: game_record::today() });
+        self.message = format!("Rush over, {} solved{}", score, if new_best && score > 0 { ". New best score!" } else { "" });
+        self.save_stats();
+    }
+
+    /// Stops a running rush without counting it and goes back to single puzzles.
+    fn leave_rush(&mut self) {
+        if self.rush.take().is_some_and(|rush| !rush.over) {
+            layouts::add_puzzle_buttons(&mut self.layout);
+        }
+    }
+
+    fn next_puzzle(&mut self) {
+        self.leave_rush();
+        self.current = self.stats.pick_puzzle(&self.puzzles, self.current);
+        self.set_up();
+    }
+
+    /// Puts the current puzzle on the board and queues the opponent's opening move.
+    fn set_up(&mut self) {
+        let Some(puzzle) = self.current.map(|index| self.puzzles[index].clone()) else {
+            return;
+        };
+        let Some(board) = puzzle.board() else {
+            return;
+        };
+        self.board = board;
+        self.progress = 0;
+        self.state = PuzzleState::Playing;
+        self.queued_moves = puzzle.opening_move.into_iter().collect();
+        self.opening_move_pending = puzzle.opening_move.is_some();
+        self.next_queued_move = Instant::now() + REPLY_DELAY;
+
+        let player = match (puzzle.opening_move.is_some(), self.board.side_to_move()) {
+            (true, ChessColor::White) | (false, ChessColor::Black) => ChessColor::Black,
+            (true, ChessColor::Black) | (false, ChessColor::White) => ChessColor::White,
+        };
+        self.played = 0;
+        self.ui_chess_board.reset_board(&self.board.squares);
+        self.ui_chess_board.clear_drawings();
+        if self.ui_chess_board.play_as != player {
+            self.ui_chess_board.flip(&self.board.squares);
+        }
+        self.ui_chess_board.set_last_move(&self.board, None);
+        self.message = format!("{:?} to move", player);
+    }
+
+    fn play(&mut self, chess_move: ChessMove) -> bool {
+        let played = self.board.move_piece(chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion).is_ok();
+        self.ui_chess_board.update(&self.board.squares);
+        if played {
+            self.played += 1;
+            self.ui_chess_board.set_last_move(&self.board, Some(chess_move));
+            self.ui_chess_board.show_drawings_of(self.played);
+        }
+        played
+    }
+
+    fn solution(&self) -> &[ChessMove] {
+        self.current.map(|index| self.puzzles[index].solution.as_slice()).unwrap_or(&[])
+    }
+
+    fn finish(&mut self, solved: bool) {
+        if let Some(rush) = self.rush.as_mut().filter(|rush| !rush.over) {
+            if solved {
+                rush.score += 1;
+            } else {
+                rush.strikes += 1;
+            }
+            rush.next_puzzle_at = Some(Instant::now() + REPLY_DELAY);
+            self.state = if solved { PuzzleState::Solved } else { PuzzleState::Failed };
+            if rush.strikes >= RUSH_STRIKES {
+                self.end_rush();
+            }
+            return;
+        }
+        if let Some(index) = self.current {
+            self.stats.record_result(&self.puzzles[index], solved);
+            self.save_stats();
+        }
+        self.state = if solved { PuzzleState::Solved } else { PuzzleState::Failed };
+    }
+
+    /// Checks a move of the player against the solution. Any move that mates is accepted as well.
+    fn try_player_move(&mut self, chess_move: ChessMove) {
+        let Some(expected) = self.solution().get(self.progress).copied() else {
+            return;
+        };
+        let mut board = self.board.clone();
+        let mates = matches!(board.move_piece(chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion), Ok(GameStatus::Win(_, WinType::Checkmate)));
+        if chess_move != expected && !mates {
+            self.finish(false);
+            self.message = "Wrong move. Retry or show the solution".to_string();
+            return;
+        }
+
+        self.play(chess_move);
+        self.progress += 1;
+        if mates || self.progress >= self.solution().len() {
+            self.finish(true);
+            self.message = "Solved!".to_string();
+        } else {
+            self.queued_moves.push_back(self.solution()[self.progress]);
+            self.next_queued_move = Instant::now() + REPLY_DELAY;
+            self.message = "Correct, keep going".to_string();
+        }
+    }
+
+    /// Returns true when the player wants to go back to the menu.
+    pub fn update(&mut self, window_parameters: &WindowParameters) -> bool {
+        self.layout.update(window_parameters);
+        self.ui_chess_board.interactive = self.state == PuzzleState::Playing && self.queued_moves.is_empty();
+        self.ui_chess_board.update_assume_logic(window_parameters);
+
+        if let Some(path) = self.layout.take_submitted_text("Puzzle file") {
+            self.load_file(&path);
+        }
+        self.update_loading();
+        if self.layout.was_button_clicked("Rush 3") {
+            self.start_rush(3);
+        }
+        if self.layout.was_button_clicked("Rush 5") {
+            self.start_rush(5);
+        }
+        if let Some(rush) = self.rush.as_ref().filter(|rush| !rush.over) {
+            if Instant::now() >= rush.ends_at {
+                self.end_rush();
+            } else if rush.next_puzzle_at.is_some_and(|next_puzzle_at| Instant::now() >= next_puzzle_at) {
+                self.next_rush_puzzle();
+            }
+        }
+        if self.layout.was_button_clicked("Next") && !self.puzzles.is_empty() {
+            self.next_puzzle();
+        }
+        if self.layout.was_button_clicked("Retry") {
+            self.set_up();
+        }
+        if self.layout.was_button_clicked("Solution") && self.current.is_some() && self.progress < self.solution().len() {
+            if self.state == PuzzleState::Playing {
+                self.finish(false);
+            }
+            self.state = PuzzleState::Failed;
+            // Queued moves are the next moves of the solution, apart from an opening move that has not been played yet
+            let queued = self.queued_moves.len() - usize::from(self.opening_move_pending);
+            let remaining: Vec<ChessMove> = self.solution().iter().skip(self.progress + queued).copied().collect();
+            self.queued_moves.extend(remaining);
+            self.message = "Solution".to_string();
+        }
+
+        let movement_proposal = self.ui_chess_board.request_move(window_parameters);
+        if let Some((from, to)) = movement_proposal.0 {
+            self.try_player_move(ChessMove::new(Coordinate::from_tuple_usize(from), Coordinate::from_tuple_usize(to), movement_proposal.1));
+            self.ui_chess_board.update(&self.board.squares);
+        }
+        self.ui_chess_board.update_legal_moves(&self.board);
+
+        if !self.queued_moves.is_empty() && Instant::now() >= self.next_queued_move {
+            let chess_move = self.queued_moves.pop_front().unwrap();
+            if !self.play(chess_move) {
+                self.queued_moves.clear();
+            } else if self.opening_move_pending {
+                self.opening_move_pending = false;
+            } else {
+                self.progress += 1;
+            }
+            self.next_queued_move = Instant::now() + REPLY_DELAY;
+        }
+
+        if self.layout.was_button_clicked("Back") {
+            self.leave_rush();
+            return true;
+        }
+        false
+    }
+
+    fn render_rush(&self, window_parameters: &WindowParameters) {
+        if let Some(rush) = self.rush.as_ref().filter(|rush| !rush.over) {
+            let remaining = rush.ends_at.saturating_duration_since(Instant::now());
+            let strikes = (0..RUSH_STRIKES).map(|strike| if strike < rush.strikes { "X" } else { "-" }).collect::<Vec<&str>>().join(" ");
+            window_parameters.render_text(&format!("Puzzle rush {} min", rush.minutes), 0.6, 0.61, 30.0, BLACK);
+            window_parameters.render_text(&format!("{}   Score {}   Strikes {}", clock::format_clock(remaining), rush.score, strikes), 0.6, 0.66, 36.0, BLACK);
+        }
+        for (i, minutes) in [3, 5].into_iter().enumerate() {
+            let best_scores = self.stats.best_rush_scores(minutes).iter().map(|best| best.score.to_string()).collect::<Vec<String>>();
+            let text = if best_scores.is_empty() { "none yet".to_string() } else { best_scores.join(", ") };
+            window_parameters.render_text(&format!("Best {} min: {}", minutes, text), 0.6, 0.85 + i as f32 * 0.04, 24.0, DARKGRAY);
+        }
+    }
+
+    pub fn render(&mut self, window_parameters: &WindowParameters) {
+        self.ui_chess_board.render(window_parameters);
+
+        window_parameters.render_text(&format!("Puzzle rating {:.0}", self.stats.rating), 0.6, 0.28, 40.0, BLACK);
+        window_parameters.render_text(&format!("{} solved, {} failed", self.stats.solved.len(), self.stats.failed.len()), 0.6, 0.32, 28.0, DARKGRAY);
+        if let Some(puzzle) = self.current.map(|index| &self.puzzles[index]) {
+            let rating = puzzle.rating.map(|rating| format!(", rating {}", rating)).unwrap_or_default();
+            window_parameters.render_text(&format!("Puzzle {}{}", puzzle.id, rating), 0.6, 0.39, 28.0, BLACK);
+            if !puzzle.themes.is_empty() && self.state != PuzzleState::Playing {
+                window_parameters.render_text(&format!("Themes: {}", puzzle.themes.join(", ")), 0.6, 0.43, 24.0, DARKGRAY);
+            }
+        }
+        let color = match self.state {
+            PuzzleState::Playing => BLACK,
+            PuzzleState::Solved => DARKGREEN,
+            PuzzleState::Failed => RED,
+        };
+        window_parameters.render_text(&self.message, 0.6, 0.52, 36.0, color);
+        self.render_rush(window_parameters);
+        self.layout.render(window_parameters);
+    }
+}
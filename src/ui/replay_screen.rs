@@ -0,0 +1,283 @@
+use std::{
+    fs,
+    time::{Duration, Instant},
+};
+
+use macroquad::prelude::*;
+
+use crate::{
+    chess::{
+        game_record::{self, GameRecord, MoveAnnotation},
+        notation, pgn, ChessBoard, Color as ChessColor,
+    },
+    draw::WindowParameters,
+};
+
+use super::{
+    layouts,
+    ui_chess_board::UIChessBoard,
+    ui_manager::{Title, UIManager},
+};
+
+/// The time between moves during autoplay, from fastest to slowest.
+const AUTOPLAY_INTERVALS: [Duration; 5] = [Duration::from_millis(500), Duration::from_secs(1), Duration::from_secs(2), Duration::from_secs(3), Duration::from_secs(5)];
+const MOVES_X: f32 = 0.6;
+const MOVES_Y: f32 = 0.4;
+const MOVE_ROW_HEIGHT: f32 = 0.04;
+const MOVE_ROWS: usize = 10;
+/// Where the move number, white's move and black's move of a row start.
+const MOVE_COLUMNS: [f32; 3] = [0.6, 0.66, 0.8];
+const MOVE_WIDTH: f32 = 0.13;
+const COMMENT_Y: f32 = 0.83;
+const COMMENT_WIDTH: f32 = 0.38;
+const COMMENT_LINE_HEIGHT: f32 = 0.03;
+const COMMENT_FONT_SIZE: f32 = 24.0;
+/// Comments that need more lines are cut off.
+const MAX_COMMENT_LINES: usize = 3;
+
+/// The "Replay" screen. Steps through a saved or imported game move by move, or plays it out at a chosen speed.
+pub struct ReplayScreen {
+    layout: UIManager,
+    record: GameRecord,
+    /// The board before the first move and after every move of the game.
+    positions: Vec<ChessBoard>,
+    san_moves: Vec<String>,
+    /// How many moves of the game are played on the shown board.
+    ply: usize,
+    autoplay: bool,
+    next_autoplay_move: Instant,
+    /// Index into `AUTOPLAY_INTERVALS`.
+    speed: usize,
+    /// The first row of the move list that is shown.
+    scroll: usize,
+    ui_chess_board: UIChessBoard,
+}
+
+impl ReplayScreen {
+    pub fn new(mut ui_chess_board: UIChessBoard) -> Self {
+        ui_chess_board.interactive = false;
+        ReplayScreen { layout: layouts::replay(), record: GameRecord::new("", "", ""), positions: vec![ChessBoard::starting_positions()], san_moves: Vec::new(), ply: 0, autoplay: false, next_autoplay_move: Instant::now(), speed: 1, scroll: 0, ui_chess_board }
+    }
+
+    /// Shows the game from its first position.
+    pub fn start(&mut self, record: GameRecord) {
+        let mut board = record.starting_board();
+        self.san_moves = board.line_to_san(&record.moves);
+        self.positions = vec![board.clone()];
+        for chess_move in record.moves.iter() {
+            let _ = board.move_piece(chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion);
+            self.positions.push(board.clone());
+        }
+        let result = game_record::pgn_result(record.status);
+        self.layout.add_title("Players", Title::new(&format!("{} - {}  {}", record.white, record.black, result), 32.0, 0.6, 0.14, BLACK));
+        self.layout.remove_title("Status");
+        self.record = record;
+        self.set_autoplay(false);
+        self.scroll = 0;
+        self.ui_chess_board.reset_board(&self.positions[0].squares);
+        self.ui_chess_board.clear_drawings();
+        self.show_ply(0);
+    }
+
+    pub fn stop(&mut self) {
+        self.set_autoplay(false);
+    }
+
+    /// Opens the first game of a PGN file.
+    fn open_file(&mut self, path: &str) {
+        let game = match fs::read_to_string(path.trim()) {
+            Ok(text) => pgn::parse_pgn(&text).into_iter().next(),
+            Err(error) => return self.set_status(&format!("Could not read {}: {}", path, error)),
+        };
+        match game.and_then(|game| GameRecord::from_pgn(&game, 0)) {
+            Some(record) => self.start(record),
+            None => self.set_status("No readable game in the file"),
+        }
+    }
+
+    fn set_status(&mut self, status: &str) {
+        self.layout.add_title("Status", Title::new(status, 24.0, 0.6, 0.95, BLACK));
+    }
+
+    fn set_autoplay(&mut self, autoplay: bool) {
+        self.autoplay = autoplay;
+        self.next_autoplay_move = Instant::now() + AUTOPLAY_INTERVALS[self.speed];
+        self.layout.add_button("Autoplay", layouts::autoplay_button(autoplay));
+    }
+
+    fn show_ply(&mut self, ply: usize) {
+        self.ply = ply.min(self.positions.len() - 1);
+        let board = &self.positions[self.ply];
+        self.ui_chess_board.game_status = board.game_status;
+        self.ui_chess_board.update(&board.squares);
+        self.ui_chess_board.set_last_move(board, self.ply.checked_sub(1).map(|index| self.record.moves[index]));
+        self.ui_chess_board.show_drawings_of(self.ply);
+        let shapes = self.annotation().map(|annotation| annotation.shapes.clone()).unwrap_or_default();
+        self.ui_chess_board.set_shapes(shapes);
+
+        // Keeps the row of the last played move in view
+        let row = self.move_index(self.ply.saturating_sub(1)) / 2;
+        if row < self.scroll {
+            self.scroll = row;
+        } else if row >= self.scroll + MOVE_ROWS {
+            self.scroll = row + 1 - MOVE_ROWS;
+        }
+    }
+
+    /// The annotation of the last played move.
+    fn annotation(&self) -> Option<&MoveAnnotation> {
+        self.ply.checked_sub(1).and_then(|index| self.record.annotations.get(index))
+    }
+
+    /// The move at `index` with the suffixes of its NAGs, e.g. `Nf3!?`.
+    fn annotated_move(&self, index: usize) -> String {
+        let nags = self.record.annotations.get(index).map(|annotation| annotation.nags.as_slice()).unwrap_or_default();
+        nags.iter().filter_map(|nag| game_record::nag_symbol(*nag)).fold(self.san_moves[index].clone(), |san, symbol| san + symbol)
+    }
+
+    /// Where the move at `ply` goes in a list that always starts with a white move, so that a game starting with black
+    /// to move leaves the first white cell empty.
+    fn move_index(&self, ply: usize) -> usize {
+        ply + self.black_moves_first() as usize
+    }
+
+    fn black_moves_first(&self) -> bool {
+        self.positions[0].side_to_move() == ChessColor::Black
+    }
+
+    fn row_count(&self) -> usize {
+        self.move_index(self.san_moves.len()).div_ceil(2)
+    }
+
+    /// The ply after the move under the mouse.
+    fn hovered_move(&self, window_parameters: &WindowParameters) -> Option<usize> {
+        let (mouse_x, mouse_y) = window_parameters.mouse_position();
+        if !(MOVES_Y..MOVES_Y + MOVE_ROWS as f32 * MOVE_ROW_HEIGHT).contains(&mouse_y) {
+            return None;
+        }
+        let column = (1..3).find(|column| (MOVE_COLUMNS[*column]..MOVE_COLUMNS[*column] + MOVE_WIDTH).contains(&mouse_x))?;
+        let row = self.scroll + ((mouse_y - MOVES_Y) / MOVE_ROW_HEIGHT) as usize;
+        let index = (row * 2 + column - 1).checked_sub(self.black_moves_first() as usize)?;
+        (index < self.san_moves.len()).then_some(index + 1)
+    }
+
+    /// Returns true when the player wants to go back to the game list.
+    pub fn update(&mut self, window_parameters: &WindowParameters) -> bool {
+        self.layout.update(window_parameters);
+        self.ui_chess_board.update_assume_logic(window_parameters);
+
+        if let Some(path) = self.layout.take_submitted_text("PGN file") {
+            self.open_file(&path);
+        }
+
+        let last = self.positions.len() - 1;
+        let mut ply = self.ply;
+        if self.layout.was_button_clicked("First") || is_key_pressed(KeyCode::Home) {
+            ply = 0;
+        }
+        if self.layout.was_button_clicked("Previous") || is_key_pressed(KeyCode::Left) {
+            ply = ply.saturating_sub(1);
+        }
+        if self.layout.was_button_clicked("Next") || is_key_pressed(KeyCode::Right) {
+            ply = (ply + 1).min(last);
+        }
+        if self.layout.was_button_clicked("Last") || is_key_pressed(KeyCode::End) {
+            ply = last;
+        }
+        if is_mouse_button_pressed(MouseButton::Left) {
+            if let Some(clicked) = self.hovered_move(window_parameters) {
+                ply = clicked;
+            }
+        }
+        if ply != self.ply {
+            self.set_autoplay(false);
+            self.show_ply(ply);
+        }
+
+        if self.layout.was_button_clicked("Autoplay") {
+            // Starting at the end replays the game from the beginning
+            if !self.autoplay && self.ply == last {
+                self.show_ply(0);
+            }
+            self.set_autoplay(!self.autoplay);
+        }
+        if self.layout.was_button_clicked("Slower") {
+            self.speed = (self.speed + 1).min(AUTOPLAY_INTERVALS.len() - 1);
+        }
+        if self.layout.was_button_clicked("Faster") {
+            self.speed = self.speed.saturating_sub(1);
+        }
+        if self.autoplay && Instant::now() >= self.next_autoplay_move {
+            self.show_ply(self.ply + 1);
+            self.next_autoplay_move = Instant::now() + AUTOPLAY_INTERVALS[self.speed];
+            if self.ply == last {
+                self.set_autoplay(false);
+            }
+        }
+
+        let (mouse_x, mouse_y) = window_parameters.mouse_position();
+        if mouse_x >= MOVES_X && (MOVES_Y..MOVES_Y + MOVE_ROWS as f32 * MOVE_ROW_HEIGHT).contains(&mouse_y) {
+            let wheel = mouse_wheel().1;
+            if wheel > 0.0 {
+                self.scroll = self.scroll.saturating_sub(1);
+            } else if wheel < 0.0 {
+                self.scroll = (self.scroll + 1).min(self.row_count().saturating_sub(MOVE_ROWS));
+            }
+        }
+
+        self.layout.was_button_clicked("Back")
+    }
+
+    fn render_moves(&self, window_parameters: &WindowParameters) {
+        let hovered = self.hovered_move(window_parameters);
+        let board = &self.positions[0];
+        let offset = self.black_moves_first() as usize;
+        for row in self.scroll..(self.scroll + MOVE_ROWS).min(self.row_count()) {
+            let y = MOVES_Y + (row - self.scroll) as f32 * MOVE_ROW_HEIGHT;
+            let text_y = y + MOVE_ROW_HEIGHT * 0.7;
+            window_parameters.render_text(&format!("{}.", board.fullmove_number() as usize + row), MOVE_COLUMNS[0], text_y, 26.0, DARKGRAY);
+            for (column, x) in MOVE_COLUMNS.into_iter().enumerate().skip(1) {
+                let Some(index) = (row * 2 + column - 1).checked_sub(offset).filter(|index| *index < self.san_moves.len()) else {
+                    continue;
+                };
+                if self.ply == index + 1 {
+                    window_parameters.render_rectangle(x, y, MOVE_WIDTH, MOVE_ROW_HEIGHT - 0.004, SKYBLUE);
+                } else if hovered == Some(index + 1) {
+                    window_parameters.render_rectangle(x, y, MOVE_WIDTH, MOVE_ROW_HEIGHT - 0.004, WHITE);
+                }
+                window_parameters.render_text(&self.annotated_move(index), x + 0.005, text_y, 28.0, BLACK);
+            }
+        }
+    }
+
+    /// The comment on the last played move, wrapped to the width of the move list.
+    fn render_comment(&self, window_parameters: &WindowParameters) {
+        let comment = self.annotation().map(MoveAnnotation::comment_text).unwrap_or_default();
+        let mut lines: Vec<String> = Vec::new();
+        for word in comment.split(' ').filter(|word| !word.is_empty()) {
+            match lines.last_mut() {
+                Some(line) if measure_text(format!("{} {}", line, word).as_str(), None, COMMENT_FONT_SIZE as u16, 1.0).width / window_parameters.width <= COMMENT_WIDTH => {
+                    line.push(' ');
+                    line.push_str(word);
+                }
+                _ => lines.push(word.to_string()),
+            }
+        }
+        for (i, line) in lines.iter().take(MAX_COMMENT_LINES).enumerate() {
+            window_parameters.render_text(line, MOVES_X, COMMENT_Y + i as f32 * COMMENT_LINE_HEIGHT, COMMENT_FONT_SIZE, DARKGREEN);
+        }
+    }
+
+    pub fn render(&mut self, window_parameters: &WindowParameters) {
+        self.ui_chess_board.render(window_parameters);
+        let position = match self.ply {
+            0 => "Starting position".to_string(),
+            ply => format!("After {} {}", notation::move_number_label(ply - 1, self.positions[0].fullmove_number(), !self.black_moves_first()), self.annotated_move(ply - 1)),
+        };
+        window_parameters.render_text(&position, 0.6, 0.38, 26.0, DARKGRAY);
+        window_parameters.render_text(&format!("{:.1} s", AUTOPLAY_INTERVALS[self.speed].as_secs_f32()), 0.825, 0.34, 28.0, BLACK);
+        self.render_moves(window_parameters);
+        self.render_comment(window_parameters);
+        self.layout.render(window_parameters);
+    }
+}
@@ -0,0 +1,226 @@
+use macroquad::prelude::*;
+
+use crate::{
+    chess::{
+        game_record::{Accuracy, GameRecord},
+        game_tree::GameTree,
+        notation, ChessBoard, Color as ChessColor,
+    },
+    draw::WindowParameters,
+    engine::{GameReview, GameReviewer, MoveClassification},
+    store,
+};
+
+use super::{
+    layouts,
+    ui_chess_board::UIChessBoard,
+    ui_manager::{Title, UIManager},
+};
+
+const SUMMARY_Y: f32 = 0.16;
+const SUMMARY_LINE_HEIGHT: f32 = 0.032;
+const GRAPH_X: f32 = 0.6;
+const GRAPH_Y: f32 = 0.3;
+const GRAPH_WIDTH: f32 = 0.38;
+const GRAPH_HEIGHT: f32 = 0.22;
+/// Moves that change white's expected share of the points by at least this much are marked on the graph as swings.
+const SWING: f32 = 0.2;
+const MOVES_Y: f32 = 0.58;
+const MOVE_HEIGHT: f32 = 0.038;
+const MAX_LISTED_MOVES: usize = 6;
+
+/// The "Game review" screen. The engine goes through a finished game and points out inaccuracies, mistakes and blunders.
+pub struct ReviewScreen {
+    layout: UIManager,
+    record: GameRecord,
+    /// The saved game with its variations, which gets the accuracy once the review is done.
+    saved_tree: Option<GameTree>,
+    reviewer: Option<GameReviewer>,
+    /// The board before the first move and after every move of the game.
+    positions: Vec<ChessBoard>,
+    /// The index of the position shown on the board.
+    current: usize,
+    /// Whether the accuracy of the finished review was written to the saved game.
+    accuracy_saved: bool,
+    ui_chess_board: UIChessBoard,
+}
+
+impl ReviewScreen {
+    pub fn new(mut ui_chess_board: UIChessBoard) -> Self {
+        ui_chess_board.interactive = false;
+        ReviewScreen { layout: layouts::review(), record: GameRecord::new("", "", ""), saved_tree: None, reviewer: None, positions: Vec::new(), current: 0, accuracy_saved: false, ui_chess_board }
+    }
+
+    /// Starts reviewing the game and shows its final position. `saved_tree` is the game as it is kept in the game store,
+    /// if it is, so that the accuracy can be saved with it.
+    pub fn start(&mut self, record: GameRecord, saved_tree: Option<GameTree>) {
+        let mut board = record.starting_board();
+        self.positions = vec![board.clone()];
+        for chess_move in record.moves.iter() {
+            let _ = board.move_piece(chess_move.from_tuple(), chess_move.to_tuple(), chess_move.promotion);
+            self.positions.push(board.clone());
+        }
+        self.current = self.positions.len() - 1;
+        self.ui_chess_board.reset_board(&board.squares);
+        self.ui_chess_board.set_last_move(&board, record.moves.last().copied());
+        self.ui_chess_board.clear_drawings();
+        self.ui_chess_board.show_drawings_of(self.current);
+        self.layout.remove_title("Status");
+        self.reviewer = Some(GameReviewer::start(&record));
+        self.accuracy_saved = false;
+        self.record = record;
+        self.saved_tree = saved_tree;
+    }
+
+    pub fn stop(&mut self) {
+        self.reviewer = None;
+    }
+
+    /// The accuracy of both sides, once the review is done.
+    pub fn accuracy(&self) -> Option<[Accuracy; 2]> {
+        self.record.accuracy.filter(|_| self.accuracy_saved)
+    }
+
+    /// The graph point under the mouse, as an index into `positions`.
+    fn hovered_point(&self, window_parameters: &WindowParameters) -> Option<usize> {
+        let (mouse_x, mouse_y) = window_parameters.mouse_position();
+        if !(GRAPH_X..=GRAPH_X + GRAPH_WIDTH).contains(&mouse_x) || !(GRAPH_Y..=GRAPH_Y + GRAPH_HEIGHT).contains(&mouse_y) || self.positions.len() < 2 {
+            return None;
+        }
+        Some(((mouse_x - GRAPH_X) / self.point_spacing()).round() as usize).map(|index| index.min(self.positions.len() - 1))
+    }
+
+    fn point_spacing(&self) -> f32 {
+        GRAPH_WIDTH / (self.positions.len().max(2) - 1) as f32
+    }
+
+    fn show_position(&mut self, index: usize) {
+        self.current = index;
+        self.ui_chess_board.update(&self.positions[index].squares);
+        self.ui_chess_board.set_last_move(&self.positions[index], index.checked_sub(1).map(|previous| self.record.moves[previous]));
+        self.ui_chess_board.show_drawings_of(index);
+    }
+
+    /// Returns true when the player wants to go back to the game.
+    pub fn update(&mut self, window_parameters: &WindowParameters) -> bool {
+        self.layout.update(window_parameters);
+        self.ui_chess_board.update_assume_logic(window_parameters);
+
+        let review_done = self.reviewer.as_mut().is_some_and(|reviewer| reviewer.review().is_some());
+        if review_done && !self.accuracy_saved {
+            self.accuracy_saved = true;
+            self.record.accuracy = self.reviewer.as_mut().and_then(|reviewer| reviewer.review()).map(|review| [ChessColor::White, ChessColor::Black].map(|color| review.accuracy(color)));
+            if let Err(error) = self.saved_tree.as_ref().map_or(Ok(()), |tree| store::save_game_tree(&self.record, tree).map(|_| ())) {
+                self.layout.add_title("Status", Title::new(&format!("Could not save the accuracy: {}", error), 24.0, 0.6, 0.93, BLACK));
+            }
+        }
+        if review_done && is_mouse_button_pressed(MouseButton::Left) {
+            if let Some(index) = self.hovered_point(window_parameters) {
+                self.show_position(index);
+            }
+        }
+
+        if self.layout.was_button_clicked("Export") {
+            if let Some(review) = self.reviewer.as_mut().and_then(|reviewer| reviewer.review()) {
+                let mut record = self.record.clone();
+                review.annotate(&mut record);
+                let status = match store::save_game(&record) {
+                    Ok(path) => format!("Saved to {}", path.display()),
+                    Err(error) => format!("Could not save the game: {}", error),
+                };
+                self.layout.add_title("Status", Title::new(&status, 24.0, 0.6, 0.93, BLACK));
+            }
+        }
+
+        self.layout.was_button_clicked("Back")
+    }
+
+    /// Draws white's expected share of the points after every move. Blunders and mistakes get a dot and large swings a red band.
+    fn render_graph(&self, window_parameters: &WindowParameters, review: &GameReview) {
+        let shares: Vec<f32> = review.evaluations().iter().map(|score| score.white_expectation()).collect();
+        let spacing = self.point_spacing();
+        let point = |index: usize| (GRAPH_X + index as f32 * spacing, GRAPH_Y + GRAPH_HEIGHT * (1.0 - shares[index]));
+
+        window_parameters.render_rectangle(GRAPH_X, GRAPH_Y, GRAPH_WIDTH, GRAPH_HEIGHT, DARKGRAY);
+        for i in 0..shares.len().saturating_sub(1) {
+            let white_height = GRAPH_HEIGHT * (shares[i] + shares[i + 1]) / 2.0;
+            window_parameters.render_rectangle(point(i).0, GRAPH_Y + GRAPH_HEIGHT - white_height, spacing, white_height, WHITE);
+            if (shares[i + 1] - shares[i]).abs() >= SWING {
+                window_parameters.render_rectangle(point(i).0, GRAPH_Y, spacing, GRAPH_HEIGHT, Color::new(1.0, 0.0, 0.0, 0.25));
+            }
+        }
+        window_parameters.render_line(GRAPH_X, GRAPH_Y + GRAPH_HEIGHT / 2.0, GRAPH_X + GRAPH_WIDTH, GRAPH_Y + GRAPH_HEIGHT / 2.0, 0.001, GRAY);
+        for i in 0..shares.len().saturating_sub(1) {
+            let ((x1, y1), (x2, y2)) = (point(i), point(i + 1));
+            window_parameters.render_line(x1, y1, x2, y2, 0.0015, BLACK);
+        }
+        for (i, move_review) in review.moves.iter().enumerate() {
+            let color = match move_review.classification {
+                Some(MoveClassification::Blunder) => RED,
+                Some(MoveClassification::Mistake) => ORANGE,
+                _ => continue,
+            };
+            let (x, y) = point(i + 1);
+            window_parameters.render_circle(x, y, 0.004, color);
+        }
+
+        let (current_x, _) = point(self.current);
+        window_parameters.render_line(current_x, GRAPH_Y, current_x, GRAPH_Y + GRAPH_HEIGHT, 0.0015, BLUE);
+        if let Some(hovered) = self.hovered_point(window_parameters) {
+            let (x, y) = point(hovered);
+            window_parameters.render_circle(x, y, 0.004, BLUE);
+        }
+        window_parameters.render_rectangle_line(GRAPH_X, GRAPH_Y, GRAPH_WIDTH, GRAPH_HEIGHT, 0.001, GRAY);
+    }
+
+    fn render_review(&self, window_parameters: &WindowParameters, review: &GameReview) {
+        self.render_graph(window_parameters, review);
+
+        if let Some(index) = review.unplayable_move {
+            let board = self.record.starting_board();
+            let label = notation::move_number_label(index, board.fullmove_number(), board.side_to_move() == ChessColor::White);
+            window_parameters.render_text(&format!("{} {} can not be played, the review stops before it", label, self.record.moves[index].to_uci()), 0.6, GRAPH_Y + GRAPH_HEIGHT + 0.03, 24.0, RED);
+        }
+        for (i, (color, name)) in [(ChessColor::White, &self.record.white), (ChessColor::Black, &self.record.black)].into_iter().enumerate() {
+            let accuracy = review.accuracy(color);
+            let counts = [MoveClassification::Inaccuracy, MoveClassification::Mistake, MoveClassification::Blunder].map(|classification| review.count(color, classification));
+            let y = SUMMARY_Y + i as f32 * 2.0 * SUMMARY_LINE_HEIGHT;
+            window_parameters.render_text(&format!("{:?} ({})  accuracy {:.1}%  ACPL {:.0}", color, name, accuracy.accuracy, accuracy.average_centipawn_loss), 0.6, y, 28.0, BLACK);
+            window_parameters.render_text(&format!("{} inaccuracies, {} mistakes, {} blunders", counts[0], counts[1], counts[2]), 0.62, y + SUMMARY_LINE_HEIGHT, 24.0, DARKGRAY);
+        }
+
+        let board = self.record.starting_board();
+        let flagged: Vec<(usize, &MoveClassification)> = review.moves.iter().enumerate().filter_map(|(i, review)| review.classification.as_ref().map(|classification| (i, classification))).collect();
+        if flagged.is_empty() {
+            window_parameters.render_text("No inaccuracies, mistakes or blunders", 0.6, MOVES_Y, 28.0, DARKGRAY);
+        }
+        for (row, (i, classification)) in flagged.iter().take(MAX_LISTED_MOVES).enumerate() {
+            let move_review = &review.moves[*i];
+            let mut text = format!("{} {}{}", notation::move_number_label(*i, board.fullmove_number(), board.side_to_move() == ChessColor::White), move_review.played_san, classification.symbol());
+            if let Some(best_move) = move_review.best_move_san.as_ref() {
+                text.push_str(&format!("  {} was best ({})", best_move, move_review.evaluation_before));
+            }
+            let color = match classification {
+                MoveClassification::Inaccuracy => DARKGREEN,
+                MoveClassification::Mistake => ORANGE,
+                MoveClassification::Blunder => RED,
+            };
+            window_parameters.render_text(&text, 0.6, MOVES_Y + row as f32 * MOVE_HEIGHT, 28.0, color);
+        }
+        if flagged.len() > MAX_LISTED_MOVES {
+            window_parameters.render_text(&format!("and {} more", flagged.len() - MAX_LISTED_MOVES), 0.6, MOVES_Y + MAX_LISTED_MOVES as f32 * MOVE_HEIGHT, 28.0, DARKGRAY);
+        }
+    }
+
+    pub fn render(&mut self, window_parameters: &WindowParameters) {
+        self.ui_chess_board.render(window_parameters);
+        if let Some(reviewer) = self.reviewer.as_mut() {
+            let (reviewed, total) = reviewer.progress();
+            match reviewer.review().cloned() {
+                Some(review) => self.render_review(window_parameters, &review),
+                None => window_parameters.render_text(&format!("Reviewing move {} of {}", (reviewed + 1).min(total), total), 0.6, SUMMARY_Y, 30.0, BLACK),
+            }
+        }
+        self.layout.render(window_parameters);
+    }
+}
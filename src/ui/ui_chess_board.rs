@@ -1,9 +1,10 @@
 use crate::{
     chess::{
         self,
-        chess_board::{GameStatus, MoveError},
+        chess_board::{ChessBoard, GameStatus, MoveError, WinType},
+        game_record::{self, Shape, ShapeColor},
         piece::PromotionPiece,
-        Color as ChessColor,
+        ChessMove, Color as ChessColor, Coordinate,
     },
     draw::WindowParameters,
 };
@@ -15,6 +16,28 @@ use super::draw::load_texture_from_bytes;
 
 const WHITE_SQUARE_COLOR: Color = Color::new(0.860, 0.767, 0.64, 1.0);
 const BLACK_SQUARE_COLOR: Color = BROWN;
+const HINT_COLOR: Color = Color::new(0.2, 0.8, 0.3, 0.55);
+const SELECTED_COLOR: Color = Color::new(0.95, 0.85, 0.2, 0.55);
+const LEGAL_MOVE_COLOR: Color = Color::new(0.1, 0.1, 0.1, 0.3);
+const LAST_MOVE_COLOR: Color = Color::new(0.75, 0.8, 0.25, 0.45);
+/// The glow around a king in check is drawn as this many stacked circles that get smaller and more opaque to the centre.
+const CHECK_GLOW_STEPS: usize = 6;
+const CHECK_GLOW_COLOR: Color = Color::new(0.95, 0.1, 0.1, 0.2);
+/// Width of an arrow's shaft as a fraction of the square width.
+const ARROW_THICKNESS: f32 = 0.18;
+const SHAPE_ALPHA: f32 = 0.7;
+/// The width of the circle drawn around a highlighted square, relative to the square.
+const CIRCLE_THICKNESS: f32 = 0.08;
+
+/// How highlights and arrows of a shape colour are drawn on the board.
+pub fn shape_color(color: ShapeColor) -> Color {
+    match color {
+        ShapeColor::Green => Color::new(0.1, 0.6, 0.2, SHAPE_ALPHA),
+        ShapeColor::Red => Color::new(0.85, 0.1, 0.1, SHAPE_ALPHA),
+        ShapeColor::Yellow => Color::new(0.95, 0.75, 0.1, SHAPE_ALPHA),
+        ShapeColor::Blue => Color::new(0.1, 0.35, 0.85, SHAPE_ALPHA),
+    }
+}
 
 #[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum PieceType {
@@ -26,6 +49,17 @@ pub enum PieceType {
     King(ChessColor),
 }
 
+/// The from and to squares of a move the player made on the board, with the piece a pawn promotes to.
+pub type MoveProposal = (Option<((usize, usize), (usize, usize))>, Option<PromotionPiece>);
+
+impl PieceType {
+    pub fn color(&self) -> ChessColor {
+        match self {
+            PieceType::Pawn(color) | PieceType::Knight(color) | PieceType::Bishop(color) | PieceType::Rook(color) | PieceType::Queen(color) | PieceType::King(color) => *color,
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Debug)]
 pub struct GraphicsPiece {
     piece_type: PieceType,
@@ -68,6 +102,14 @@ pub struct Square {
     graphics_piece: Option<GraphicsPiece>,
 }
 
+/// An arrow between the centres of two squares, given in board coordinates.
+#[derive(Clone, Copy, Debug)]
+pub struct Arrow {
+    pub from: (usize, usize),
+    pub to: (usize, usize),
+    pub color: Color,
+}
+
 #[derive(Clone)]
 pub struct UIChessBoard {
     pub x: f32,
@@ -81,6 +123,38 @@ pub struct UIChessBoard {
     pub game_status: GameStatus,
     pub textures: HashMap<PieceType, Texture2D>,
     window_aspect_ratio: f32,
+    /// When false the board only displays positions and ignores all move input.
+    pub interactive: bool,
+    /// The from and to squares of a suggested move, highlighted until cleared.
+    hint: Option<((usize, usize), (usize, usize))>,
+    arrows: Vec<Arrow>,
+    /// Highlighted squares and arrows annotated on the shown move.
+    shapes: Vec<Shape>,
+    /// The square the right mouse button was pressed on, while a shape is being drawn.
+    drawing_from: Option<(usize, usize)>,
+    /// When true the board keeps the circles and arrows drawn with the right mouse button itself, for each move of the
+    /// shown game. Screens that store drawn shapes elsewhere turn it off and use `take_drawn_shape`.
+    pub keep_drawings: bool,
+    /// The circles and arrows drawn on the positions of the shown game, by the number of moves played to reach them.
+    drawings: HashMap<usize, Vec<Shape>>,
+    /// The number of moves played to reach the shown position, set by `show_drawings_of`.
+    drawing_ply: usize,
+    position: [[Option<Piece>; 8]; 8],
+    /// The piece picked with a click, which the next click on another square moves.
+    selected: Option<(usize, usize)>,
+    /// The side to move in the shown position, set by `set_last_move`. Only its pieces can be selected.
+    side_to_move: ChessColor,
+    /// When set, only pieces of this colour can be selected, e.g. the player's own pieces online.
+    pub player_color: Option<ChessColor>,
+    /// When false the squares the picked up or selected piece can move to are not marked.
+    pub show_legal_moves: bool,
+    /// The square of the picked up or selected piece whose legal moves are marked.
+    legal_moves_from: Option<(usize, usize)>,
+    legal_moves: Vec<(usize, usize)>,
+    /// The from and to squares of the move that led to the shown position.
+    last_move: Option<((usize, usize), (usize, usize))>,
+    /// The square of the king of the side to move while it is in check.
+    king_in_check: Option<(usize, usize)>,
 }
 
 #[derive(Clone, Debug)]
@@ -91,6 +165,18 @@ struct PromotionPiecesPopup {
     is_hoverd: (bool, usize),
 }
 
+/// The colour the modifier keys held down select for a drawn shape, if any.
+fn modifier_shape_color() -> Option<ShapeColor> {
+    let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+    let alt = is_key_down(KeyCode::LeftAlt) || is_key_down(KeyCode::RightAlt);
+    match (shift, alt) {
+        (true, true) => Some(ShapeColor::Yellow),
+        (true, false) => Some(ShapeColor::Red),
+        (false, true) => Some(ShapeColor::Blue),
+        (false, false) => None,
+    }
+}
+
 pub async fn load_piece_textures() -> HashMap<PieceType, Texture2D> {
     let mut textures: HashMap<PieceType, Texture2D> = HashMap::new();
     let white_pawn = load_texture_from_bytes(include_bytes!("../../res/white_pawn.png")).await.unwrap();
@@ -163,7 +249,7 @@ impl UIChessBoard {
                 is_square_white = !is_square_white;
             }
         }
-        UIChessBoard { x, y, width, height, squares, held_piece: None, play_as, promotion: None, game_status: GameStatus::Ongoing, textures, window_aspect_ratio: window_aspect_ratio.to_owned() }
+        UIChessBoard { x, y, width, height, squares, held_piece: None, play_as, promotion: None, game_status: GameStatus::Ongoing, textures, window_aspect_ratio: window_aspect_ratio.to_owned(), interactive: true, hint: None, arrows: Vec::new(), shapes: Vec::new(), drawing_from: None, keep_drawings: true, drawings: HashMap::new(), drawing_ply: 0, position: *chess_position, selected: None, side_to_move: ChessColor::White, player_color: None, show_legal_moves: true, legal_moves_from: None, legal_moves: Vec::new(), last_move: None, king_in_check: None }
     }
 
     fn show_game_ended_popup(&mut self, window_parameters: &WindowParameters) {
@@ -183,6 +269,8 @@ impl UIChessBoard {
                     chess::chess_board::DrawType::Stalemate => "Draw by stalemate",
                     chess::chess_board::DrawType::MoveRule => "Draw by 50 move rule",
                     chess::chess_board::DrawType::Repetion => "Draw by 3 fold repetition",
+                    chess::chess_board::DrawType::InsufficientMaterial => "Draw by insufficient material",
+                    chess::chess_board::DrawType::Agreement => "Draw by agreement",
                 };
                 let text_center = window_parameters.get_text_center(draw_type_string, 30);
                 window_parameters.render_rectangle(popup_x, popup_y, popup_width, popup_height, GRAY);
@@ -190,10 +278,16 @@ impl UIChessBoard {
                 window_parameters.render_rectangle(button_x, button_y, button_size, button_size, RED);
                 window_parameters.render_text("X", button_x + button_size / 3.0, button_y + button_size / 1.5, 30.0, WHITE);
             }
-            chess::chess_board::GameStatus::Win(color) => {
-                let win_color = match color {
-                    ChessColor::White => "Win for White",
-                    ChessColor::Black => "Win for Black",
+            chess::chess_board::GameStatus::Win(color, win_type) => {
+                let win_color = match (color, win_type) {
+                    (ChessColor::White, WinType::Checkmate) => "Win for White",
+                    (ChessColor::Black, WinType::Checkmate) => "Win for Black",
+                    (ChessColor::White, WinType::Resignation) => "Black resigned",
+                    (ChessColor::Black, WinType::Resignation) => "White resigned",
+                    (ChessColor::White, WinType::Timeout) => "Black lost on time",
+                    (ChessColor::Black, WinType::Timeout) => "White lost on time",
+                    (ChessColor::White, WinType::Abandonment) => "Black abandoned the game",
+                    (ChessColor::Black, WinType::Abandonment) => "White abandoned the game",
                 };
                 let text_center = window_parameters.get_text_center(win_color, 30);
                 window_parameters.render_rectangle(popup_x, popup_y, popup_width, popup_height, GRAY);
@@ -205,6 +299,11 @@ impl UIChessBoard {
     }
 
     pub fn update(&mut self, chess_position: &[[Option<Piece>; 8]; 8]) {
+        self.legal_moves_from = None;
+        if self.selected.is_some_and(|(x, y)| chess_position[x][y] != self.position[x][y]) {
+            self.selected = None;
+        }
+        self.position = *chess_position;
         for i in 0..8 {
             for j in 0..8 {
                 let square = self.squares.get_mut(&(i, j)).unwrap();
@@ -220,10 +319,34 @@ impl UIChessBoard {
     }
 
     pub fn reset_board(&mut self, chess_position: &[[Option<Piece>; 8]; 8]) {
+        let interactive = self.interactive;
+        let show_legal_moves = self.show_legal_moves;
+        let keep_drawings = self.keep_drawings;
+        let drawings = std::mem::take(&mut self.drawings);
+        let drawing_ply = self.drawing_ply;
+        let player_color = self.player_color;
         *self = UIChessBoard::new(self.x, self.y, self.width, chess_position, &self.window_aspect_ratio, self.play_as, self.textures.clone());
+        self.interactive = interactive;
+        self.show_legal_moves = show_legal_moves;
+        self.keep_drawings = keep_drawings;
+        self.drawings = drawings;
+        self.drawing_ply = drawing_ply;
+        self.player_color = player_color;
     }
 
     pub fn flip(&mut self, chess_position: &[[Option<Piece>; 8]; 8]) {
+        let interactive = self.interactive;
+        let hint = self.hint;
+        let arrows = std::mem::take(&mut self.arrows);
+        let shapes = std::mem::take(&mut self.shapes);
+        let show_legal_moves = self.show_legal_moves;
+        let last_move = self.last_move;
+        let king_in_check = self.king_in_check;
+        let keep_drawings = self.keep_drawings;
+        let drawings = std::mem::take(&mut self.drawings);
+        let drawing_ply = self.drawing_ply;
+        let side_to_move = self.side_to_move;
+        let player_color = self.player_color;
         match self.play_as {
             ChessColor::White => {
                 *self = UIChessBoard::new(self.x, self.y, self.width, chess_position, &self.window_aspect_ratio, ChessColor::Black, self.textures.clone());
@@ -232,6 +355,154 @@ impl UIChessBoard {
                 *self = UIChessBoard::new(self.x, self.y, self.width, chess_position, &self.window_aspect_ratio, ChessColor::White, self.textures.clone());
             }
         }
+        self.interactive = interactive;
+        self.hint = hint;
+        self.arrows = arrows;
+        self.shapes = shapes;
+        self.show_legal_moves = show_legal_moves;
+        self.last_move = last_move;
+        self.king_in_check = king_in_check;
+        self.keep_drawings = keep_drawings;
+        self.drawings = drawings;
+        self.drawing_ply = drawing_ply;
+        self.side_to_move = side_to_move;
+        self.player_color = player_color;
+    }
+
+    /// Highlights the move that led to `board`, which has to be the position shown, and the king of the side to move
+    /// when it is in check. `reset_board` clears both, `flip` keeps them.
+    pub fn set_last_move(&mut self, board: &ChessBoard, last_move: Option<ChessMove>) {
+        self.last_move = last_move.map(|chess_move| (chess_move.from_tuple(), chess_move.to_tuple()));
+        let color = board.side_to_move();
+        self.side_to_move = color;
+        let king = if color == ChessColor::White { board.white_king_position } else { board.black_king_position };
+        self.king_in_check = board.is_in_check(color).then_some((king.x, king.y));
+    }
+
+    pub fn show_hint(&mut self, from: (usize, usize), to: (usize, usize)) {
+        self.hint = Some((from, to));
+    }
+
+    pub fn clear_hint(&mut self) {
+        self.hint = None;
+    }
+
+    /// Replaces the arrows drawn over the pieces.
+    pub fn set_arrows(&mut self, arrows: Vec<Arrow>) {
+        self.arrows = arrows;
+    }
+
+    /// Replaces the annotated square highlights and arrows.
+    pub fn set_shapes(&mut self, shapes: Vec<Shape>) {
+        self.shapes = shapes;
+    }
+
+    /// Looks up where the picked up or selected piece can move on `board`, which has to be the position shown. Only
+    /// pieces of the side to move get their moves marked, and when `player_color` is set only while it is that side.
+    pub fn update_legal_moves(&mut self, board: &ChessBoard) {
+        let active = self.held_piece.or(self.selected).filter(|_| self.show_legal_moves && self.interactive && self.promotion.is_none());
+        let movable = |color: ChessColor| color == board.side_to_move() && self.player_color.is_none_or(|player_color| player_color == color);
+        let Some(from) = active.filter(|from| board.squares[from.0][from.1].is_some_and(|piece| movable(piece.get_color()))) else {
+            self.legal_moves_from = None;
+            return;
+        };
+        if self.legal_moves_from == Some(from) {
+            return;
+        }
+        self.legal_moves_from = Some(from);
+        self.legal_moves = board.get_legal_moves_for_piece_at(Coordinate::from_tuple_usize(from)).into_iter().map(|to| (to.x, to.y)).collect();
+    }
+
+    /// Dots on the empty squares the active piece can move to and rings on the pieces it can capture.
+    fn render_legal_moves(&self, window_parameters: &WindowParameters) {
+        let Some(from) = self.legal_moves_from.filter(|_| self.show_legal_moves && self.interactive) else {
+            return;
+        };
+        let is_pawn = matches!(self.squares.get(&from).and_then(|square| square.graphics_piece.as_ref()).map(|piece| &piece.piece_type), Some(PieceType::Pawn(_)));
+        for (to, square) in self.legal_moves.iter().filter_map(|to| self.squares.get(to).map(|square| (to, square))) {
+            let (center_x, center_y) = (square.x + square.width / 2.0, square.y + square.height / 2.0);
+            // A pawn moving to another file captures, even onto the empty en passant square
+            if square.graphics_piece.is_some() || (is_pawn && to.0 != from.0) {
+                window_parameters.render_circle_line(center_x, center_y, square.width * 0.45, square.width * 0.07, LEGAL_MOVE_COLOR);
+            } else {
+                window_parameters.render_circle(center_x, center_y, square.width * 0.16, LEGAL_MOVE_COLOR);
+            }
+        }
+    }
+
+    fn hovered_square(&self, window_parameters: &WindowParameters) -> Option<(usize, usize)> {
+        let (mouse_x, mouse_y) = window_parameters.mouse_position();
+        self.squares.iter().find(|(_, square)| mouse_x >= square.x && mouse_x <= square.x + square.width && mouse_y >= square.y && mouse_y <= square.y + square.height).map(|(position, _)| *position)
+    }
+
+    /// A shape drawn with the right mouse button once the button is released: a circle when it was released on the square
+    /// it was pressed on, an arrow otherwise. Shift draws in red, Alt in blue and both together in yellow, without
+    /// modifier keys the shape gets `color`.
+    pub fn take_drawn_shape(&mut self, window_parameters: &WindowParameters, color: ShapeColor) -> Option<Shape> {
+        if is_mouse_button_pressed(MouseButton::Right) {
+            self.drawing_from = self.hovered_square(window_parameters);
+        }
+        if is_mouse_button_released(MouseButton::Right) {
+            let from = self.drawing_from.take()?;
+            let to = self.hovered_square(window_parameters)?;
+            return Some(Shape { from: Coordinate::from_tuple_usize(from), to: Coordinate::from_tuple_usize(to), color: modifier_shape_color().unwrap_or(color) });
+        }
+        None
+    }
+
+    /// Shows the circles and arrows drawn on the position after the first `ply` moves of the shown game.
+    pub fn show_drawings_of(&mut self, ply: usize) {
+        self.drawing_ply = ply;
+    }
+
+    /// Erases the circles and arrows of every position, for when another game is shown. `reset_board` and `flip` keep them.
+    pub fn clear_drawings(&mut self) {
+        self.drawings.clear();
+        self.drawing_ply = 0;
+    }
+
+    /// The circles and arrows drawn on the shown position.
+    fn drawings(&self) -> &[Shape] {
+        self.drawings.get(&self.drawing_ply).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Whether the left mouse button was pressed on the board, which erases the drawn circles and arrows.
+    pub fn was_left_clicked(&self, window_parameters: &WindowParameters) -> bool {
+        is_mouse_button_pressed(MouseButton::Left) && self.hovered_square(window_parameters).is_some()
+    }
+
+    /// Toggles the shapes drawn with the right mouse button on the shown position and erases them all on a left click.
+    fn update_drawings(&mut self, window_parameters: &WindowParameters) {
+        if self.was_left_clicked(window_parameters) {
+            self.drawings.remove(&self.drawing_ply);
+        }
+        let Some(shape) = self.take_drawn_shape(window_parameters, ShapeColor::Green) else {
+            return;
+        };
+        let shapes = self.drawings.entry(self.drawing_ply).or_default();
+        game_record::toggle_shape(shapes, shape);
+        if shapes.is_empty() {
+            self.drawings.remove(&self.drawing_ply);
+        }
+    }
+
+    /// Draws the engine arrows, the annotated and drawn shapes and the arrow being drawn with the right mouse button.
+    fn render_arrows(&self, window_parameters: &WindowParameters) {
+        let center = |square: &Square| (square.x + square.width / 2.0, square.y + square.height / 2.0);
+        for shape in self.shapes.iter().chain(self.drawings().iter()).filter(|shape| !shape.is_arrow()) {
+            if let Some(square) = self.squares.get(&(shape.from.x, shape.from.y)) {
+                let (x, y) = center(square);
+                window_parameters.render_circle_line(x, y, square.width * (0.5 - CIRCLE_THICKNESS / 2.0), square.width * CIRCLE_THICKNESS, shape_color(shape.color));
+            }
+        }
+        let shape_arrows = self.shapes.iter().chain(self.drawings().iter()).filter(|shape| shape.is_arrow()).map(|shape| Arrow { from: (shape.from.x, shape.from.y), to: (shape.to.x, shape.to.y), color: shape_color(shape.color) });
+        let drawn_arrow = self.drawing_from.zip(self.hovered_square(window_parameters)).filter(|(from, to)| from != to).map(|(from, to)| Arrow { from, to, color: shape_color(modifier_shape_color().unwrap_or(ShapeColor::Green)) });
+        for arrow in self.arrows.iter().copied().chain(shape_arrows).chain(drawn_arrow) {
+            if let (Some(from), Some(to)) = (self.squares.get(&arrow.from), self.squares.get(&arrow.to)) {
+                let ((from_x, from_y), (to_x, to_y)) = (center(from), center(to));
+                window_parameters.render_arrow(from_x, from_y, to_x, to_y, from.width * ARROW_THICKNESS, arrow.color);
+            }
+        }
     }
 
     pub fn render(&mut self, window_parameters: &WindowParameters) {
@@ -239,12 +510,33 @@ impl UIChessBoard {
         for ((_i, _j), square) in &self.squares {
             window_parameters.render_rectangle(square.x, square.y, square.width, square.height, square.color);
         }
+        if let Some((from, to)) = self.last_move {
+            for square in [from, to].iter().filter_map(|position| self.squares.get(position)) {
+                window_parameters.render_rectangle(square.x, square.y, square.width, square.height, LAST_MOVE_COLOR);
+            }
+        }
+        if let Some(square) = self.king_in_check.and_then(|king| self.squares.get(&king)) {
+            for step in 0..CHECK_GLOW_STEPS {
+                let radius = square.width * 0.55 * (CHECK_GLOW_STEPS - step) as f32 / CHECK_GLOW_STEPS as f32;
+                window_parameters.render_circle(square.x + square.width / 2.0, square.y + square.height / 2.0, radius, CHECK_GLOW_COLOR);
+            }
+        }
+        if let Some((from, to)) = self.hint {
+            for square in [from, to].iter().filter_map(|position| self.squares.get(position)) {
+                window_parameters.render_rectangle(square.x, square.y, square.width, square.height, HINT_COLOR);
+            }
+        }
+        if let Some(square) = self.selected.filter(|_| self.interactive).and_then(|selected| self.squares.get(&selected)) {
+            window_parameters.render_rectangle(square.x, square.y, square.width, square.height, SELECTED_COLOR);
+        }
 
         for ((_i, _j), square) in &self.squares {
             if let Some(ref piece) = square.graphics_piece {
                 piece.render(window_parameters, square);
             }
         }
+        self.render_legal_moves(window_parameters);
+        self.render_arrows(window_parameters);
         if let Some(promotion) = self.promotion.clone() {
             window_parameters.render_rectangle(self.x, self.y, self.width, self.height, Color::new(0.0, 0.0, 0.0, 0.6));
             let mut i = 0;
@@ -342,7 +634,10 @@ impl UIChessBoard {
         None
     }
 
-    pub fn request_move(&mut self, window_parameters: &WindowParameters) -> (Option<((usize, usize), (usize, usize))>, Option<PromotionPiece>) {
+    pub fn request_move(&mut self, window_parameters: &WindowParameters) -> MoveProposal {
+        if !self.interactive {
+            return (None, None);
+        }
         if let Some(promotion) = self.promotion.clone() {
             if let Some(piece_to_promote_to) = self.handle_promotion_click(window_parameters) {
                 return (Some((promotion.from, promotion.to)), Some(piece_to_promote_to));
@@ -351,59 +646,82 @@ impl UIChessBoard {
                 return (Some((promotion.from, promotion.from)), None);
             }
         }
-        let (mouse_x, mouse_y) = window_parameters.mouse_position();
-        if let Some((i, j)) = self.held_piece {
-            if let Some(_) = self.squares.get_mut(&(i, j)) {
-                if !is_mouse_button_down(MouseButton::Left) {
-                    self.held_piece = None;
-                    let new_square = self.squares.iter().find_map(|((new_i, new_j), new_square)| {
-                        let is_hovered = mouse_x >= new_square.x && mouse_x <= new_square.x + new_square.width && mouse_y >= new_square.y && mouse_y <= new_square.y + new_square.height;
-                        if mouse_x > self.x + self.width || mouse_x < self.x || mouse_y > self.y + self.height || mouse_y < self.y {
-                            return Some((i, j));
-                        }
-                        if is_hovered {
-                            Some((*new_i, *new_j))
-                        } else {
-                            None
-                        }
-                    });
-
-                    if let Some(new_square) = new_square {
-                        if let Some(ref piece) = self.squares.get(&(i, j)).unwrap().graphics_piece {
-                            if let PieceType::Pawn(color) = piece.piece_type {
-                                if color == ChessColor::White {
-                                    if new_square.1 == 7 {
-                                        let is_square_occupied = self.squares.get(&new_square).unwrap().graphics_piece.is_some();
-                                        let is_capture_move = (new_square.0 as i32 - i as i32).abs() == 1;
-
-                                        if !is_square_occupied || is_capture_move {
-                                            self.promotion = Some(self.create_promotion_pieces_popup(new_square.0, true, (i, j), new_square));
-                                            return (None, None);
-                                        }
-                                    }
-                                } else {
-                                    if new_square.1 == 0 {
-                                        let is_square_occupied = self.squares.get(&new_square).unwrap().graphics_piece.is_some();
-                                        let is_capture_move = (new_square.0 as i32 - i as i32).abs() == 1;
-
-                                        if !is_square_occupied || is_capture_move {
-                                            self.promotion = Some(self.create_promotion_pieces_popup(new_square.0, false, (i, j), new_square));
-                                            return (None, None);
-                                        }
-                                    }
-                                }
-                            }
-                        }
+        if let Some(pressed) = self.held_piece {
+            if !is_mouse_button_down(MouseButton::Left) {
+                self.held_piece = None;
+                self.center_piece(pressed);
+                return match self.hovered_square(window_parameters) {
+                    Some(released) if released != pressed => {
+                        self.selected = None;
+                        self.propose_move(pressed, released)
                     }
+                    Some(clicked) => self.click_square(clicked),
+                    None => (None, None),
+                };
+            }
+        }
+        (None, None)
+    }
 
-                    return (new_square.map(|new_square| ((i, j), new_square)), None);
-                }
+    fn piece_color_at(&self, position: (usize, usize)) -> Option<ChessColor> {
+        self.squares.get(&position).and_then(|square| square.graphics_piece.as_ref()).map(|piece| piece.piece_type.color())
+    }
+
+    /// Whether the piece on `position` belongs to the side to move and, when set, to `player_color`.
+    fn can_select(&self, position: (usize, usize)) -> bool {
+        self.piece_color_at(position).is_some_and(|color| color == self.side_to_move && self.player_color.is_none_or(|player_color| player_color == color))
+    }
+
+    /// Click-to-move: the first click selects a piece of the side allowed to move, a click on another of its pieces selects
+    /// that one instead, a second click on the selected piece deselects it and a click anywhere else moves the selected
+    /// piece there.
+    fn click_square(&mut self, clicked: (usize, usize)) -> MoveProposal {
+        match self.selected {
+            Some(selected) if selected == clicked => self.selected = None,
+            Some(_) if self.can_select(clicked) => self.selected = Some(clicked),
+            Some(selected) => {
+                self.selected = None;
+                return self.propose_move(selected, clicked);
             }
+            None => self.selected = self.can_select(clicked).then_some(clicked),
         }
         (None, None)
     }
 
+    /// Returns the move, or opens the promotion popup first when a pawn reaches the last rank.
+    fn propose_move(&mut self, from: (usize, usize), to: (usize, usize)) -> MoveProposal {
+        if let Some(PieceType::Pawn(color)) = self.squares.get(&from).and_then(|square| square.graphics_piece.as_ref()).map(|piece| piece.piece_type.clone()) {
+            let last_rank = if color == ChessColor::White { 7 } else { 0 };
+            if to.1 == last_rank {
+                let is_square_occupied = self.squares.get(&to).unwrap().graphics_piece.is_some();
+                let is_capture_move = (to.0 as i32 - from.0 as i32).abs() == 1;
+                if !is_square_occupied || is_capture_move {
+                    self.promotion = Some(self.create_promotion_pieces_popup(to.0, color == ChessColor::White, from, to));
+                    return (None, None);
+                }
+            }
+        }
+        (Some((from, to)), None)
+    }
+
+    /// Puts a piece that was dragged back in the middle of its square.
+    fn center_piece(&mut self, position: (usize, usize)) {
+        if let Some(square) = self.squares.get_mut(&position) {
+            let (x, y) = (square.x + square.width / 2.0, square.y + square.height / 2.0);
+            if let Some(piece) = square.graphics_piece.as_mut() {
+                piece.x = x;
+                piece.y = y;
+            }
+        }
+    }
+
     pub fn update_assume_logic(&mut self, window_parameters: &WindowParameters) {
+        if self.keep_drawings && self.promotion.is_none() {
+            self.update_drawings(window_parameters);
+        }
+        if !self.interactive {
+            return;
+        }
         if self.promotion.is_some() {
             let promotion_squares = self.promotion.clone().unwrap().squares;
             let (mouse_x, mouse_y) = window_parameters.mouse_position();
@@ -418,6 +736,9 @@ impl UIChessBoard {
         }
         let (mouse_x, mouse_y) = window_parameters.mouse_position();
 
+        if is_key_pressed(KeyCode::Escape) {
+            self.selected = None;
+        }
         if is_mouse_button_pressed(MouseButton::Left) {
             for ((i, j), square) in &self.squares {
                 let is_hovered = mouse_x >= square.x && mouse_x <= square.x + square.width && mouse_y >= square.y && mouse_y <= square.y + square.height;
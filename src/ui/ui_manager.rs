@@ -1,6 +1,6 @@
 use crate::draw::WindowParameters;
 use macroquad::{
-    color::{Color, BLACK}, input::{is_mouse_button_pressed, MouseButton}, math::Vec2, text::get_text_center
+    color::{Color, BLACK, DARKGRAY, SKYBLUE, WHITE}, input::{get_char_pressed, is_key_pressed, is_mouse_button_pressed, mouse_wheel, KeyCode, MouseButton}, math::Vec2, text::{get_text_center, measure_text}
 };
 use std::collections::HashMap;
 
@@ -70,20 +70,282 @@ impl Title {
     }
 }
 
+pub struct TextInput {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub text: String,
+    pub placeholder: String,
+    pub max_length: usize,
+    pub is_focused: bool,
+    /// Whether Enter empties the input, as for chat messages, or leaves the text in place, as for search fields.
+    pub clear_on_submit: bool,
+    submitted: Option<String>,
+}
+
+impl TextInput {
+    pub fn new(x: f32, y: f32, width: f32, height: f32, placeholder: &str, max_length: usize) -> Self {
+        TextInput { x, y, width, height, text: String::new(), placeholder: placeholder.to_string(), max_length, is_focused: false, clear_on_submit: true, submitted: None }
+    }
+
+    pub fn render(&mut self, window_parameters: &WindowParameters) {
+        window_parameters.render_rectangle(self.x, self.y, self.width, self.height, WHITE);
+        let border = if self.is_focused { BLACK } else { DARKGRAY };
+        window_parameters.render_rectangle_line(self.x, self.y, self.width, self.height, 0.001, border);
+
+        let font_size = 30.0;
+        let text_y = self.y + self.height / 2.0 + window_parameters.get_text_center("Ag", font_size as u16).y.abs();
+        if self.text.is_empty() && !self.is_focused {
+            window_parameters.render_text(&self.placeholder, self.x + 0.005, text_y, font_size, DARKGRAY);
+            return;
+        }
+
+        // Only the end of the text is shown when it does not fit, so the caret stays visible
+        let mut visible_start = 0;
+        let caret = if self.is_focused { "|" } else { "" };
+        while visible_start < self.text.len() && measure_text(format!("{}{}", &self.text[visible_start..], caret), None, font_size as u16, 1.0).width / window_parameters.width > self.width - 0.01 {
+            visible_start += self.text[visible_start..].chars().next().map(|character| character.len_utf8()).unwrap_or(1);
+        }
+        window_parameters.render_text(&format!("{}{}", &self.text[visible_start..], caret), self.x + 0.005, text_y, font_size, BLACK);
+    }
+
+    /// `typed` are the characters typed since the last frame, which only the focused input takes.
+    pub fn update(&mut self, window_parameters: &WindowParameters, typed: &[char]) {
+        let (mouse_x, mouse_y) = window_parameters.mouse_position();
+        if is_mouse_button_pressed(MouseButton::Left) {
+            self.is_focused = mouse_x >= self.x && mouse_x <= self.x + self.width && mouse_y >= self.y && mouse_y <= self.y + self.height;
+        }
+
+        for character in typed {
+            if self.is_focused && !character.is_control() && self.text.chars().count() < self.max_length {
+                self.text.push(*character);
+            }
+        }
+        if !self.is_focused {
+            return;
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.text.pop();
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            self.is_focused = false;
+        }
+        if is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::KpEnter) {
+            let text = if self.clear_on_submit { std::mem::take(&mut self.text) } else { self.text.clone() };
+            if !text.trim().is_empty() {
+                self.submitted = Some(text.trim().to_string());
+            }
+        }
+    }
+}
+
+/// A box of text lines, newest at the bottom, that can be scrolled with the mouse wheel while hovered.
+pub struct MessageList {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub font_size: f32,
+    pub messages: Vec<String>,
+    /// Number of lines scrolled up from the newest line.
+    scroll: usize,
+    is_hovered: bool,
+}
+
+impl MessageList {
+    pub fn new(x: f32, y: f32, width: f32, height: f32, font_size: f32) -> Self {
+        MessageList { x, y, width, height, font_size, messages: Vec::new(), scroll: 0, is_hovered: false }
+    }
+
+    fn wrapped_lines(&self, window_parameters: &WindowParameters) -> Vec<String> {
+        let max_width = (self.width - 0.01) * window_parameters.width;
+        let mut lines = Vec::new();
+        for message in self.messages.iter() {
+            let mut line = String::new();
+            for word in message.split_whitespace() {
+                let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+                if !line.is_empty() && measure_text(&candidate, None, self.font_size as u16, 1.0).width > max_width {
+                    lines.push(std::mem::take(&mut line));
+                    line = word.to_string();
+                } else {
+                    line = candidate;
+                }
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    pub fn render(&mut self, window_parameters: &WindowParameters) {
+        window_parameters.render_rectangle(self.x, self.y, self.width, self.height, Color::new(1.0, 1.0, 1.0, 0.6));
+        window_parameters.render_rectangle_line(self.x, self.y, self.width, self.height, 0.001, DARKGRAY);
+
+        let lines = self.wrapped_lines(window_parameters);
+        let line_height = self.font_size * 1.1 / window_parameters.height;
+        let visible_lines = ((self.height - 0.01) / line_height).floor().max(1.0) as usize;
+        self.scroll = self.scroll.min(lines.len().saturating_sub(visible_lines));
+
+        let end = lines.len() - self.scroll;
+        let start = end.saturating_sub(visible_lines);
+        for (i, line) in lines[start..end].iter().enumerate() {
+            window_parameters.render_text(line, self.x + 0.005, self.y + 0.005 + line_height * (i + 1) as f32, self.font_size, BLACK);
+        }
+    }
+
+    pub fn update(&mut self, window_parameters: &WindowParameters) {
+        let (mouse_x, mouse_y) = window_parameters.mouse_position();
+        self.is_hovered = mouse_x >= self.x && mouse_x <= self.x + self.width && mouse_y >= self.y && mouse_y <= self.y + self.height;
+        if self.is_hovered {
+            let wheel = mouse_wheel().1;
+            if wheel > 0.0 {
+                self.scroll += 1;
+            } else if wheel < 0.0 {
+                self.scroll = self.scroll.saturating_sub(1);
+            }
+        }
+    }
+
+    pub fn push(&mut self, message: &str) {
+        self.messages.push(message.to_string());
+        self.scroll = 0;
+    }
+}
+
+/// The moves of a game in SAN, numbered, with white's and black's moves side by side. The move that led to the shown
+/// position is highlighted, the mouse wheel scrolls the list and clicked moves are kept until they are taken.
+pub struct MoveList {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    rows: usize,
+    moves: Vec<String>,
+    first_move_number: u16,
+    /// When the game started with black to move the first white cell stays empty.
+    black_moves_first: bool,
+    /// The index of the highlighted move.
+    current: Option<usize>,
+    /// The first row that is shown.
+    scroll: usize,
+    hovered: Option<usize>,
+    clicked: Option<usize>,
+}
+
+impl MoveList {
+    /// A list showing `rows` moves of each side at a time.
+    pub fn new(x: f32, y: f32, width: f32, height: f32, rows: usize) -> Self {
+        MoveList { x, y, width, height, rows, moves: Vec::new(), first_move_number: 1, black_moves_first: false, current: None, scroll: 0, hovered: None, clicked: None }
+    }
+
+    fn row_height(&self) -> f32 {
+        self.height / self.rows as f32
+    }
+
+    /// Where the move number, white's move and black's move of a row start.
+    fn columns(&self) -> [f32; 3] {
+        [self.x + 0.004, self.x + self.width * 0.22, self.x + self.width * 0.61]
+    }
+
+    fn row_count(&self) -> usize {
+        (self.moves.len() + self.black_moves_first as usize).div_ceil(2)
+    }
+
+    /// Replaces the moves and highlights the last one.
+    pub fn set_moves(&mut self, moves: Vec<String>, first_move_number: u16, black_moves_first: bool) {
+        self.moves = moves;
+        self.first_move_number = first_move_number;
+        self.black_moves_first = black_moves_first;
+        self.set_current(self.moves.len().checked_sub(1));
+    }
+
+    /// Highlights the move at `index`, or none, and scrolls it into view.
+    pub fn set_current(&mut self, index: Option<usize>) {
+        self.current = index.filter(|index| *index < self.moves.len());
+        let row = self.current.map_or(0, |index| (index + self.black_moves_first as usize) / 2);
+        if row < self.scroll {
+            self.scroll = row;
+        } else if row >= self.scroll + self.rows {
+            self.scroll = row + 1 - self.rows;
+        }
+    }
+
+    fn move_at(&self, window_parameters: &WindowParameters) -> Option<usize> {
+        let (mouse_x, mouse_y) = window_parameters.mouse_position();
+        if !(self.y..self.y + self.height).contains(&mouse_y) || mouse_x > self.x + self.width {
+            return None;
+        }
+        let columns = self.columns();
+        let column = (1..3).rev().find(|column| mouse_x >= columns[*column])?;
+        let row = self.scroll + ((mouse_y - self.y) / self.row_height()) as usize;
+        let index = (row * 2 + column - 1).checked_sub(self.black_moves_first as usize)?;
+        (index < self.moves.len()).then_some(index)
+    }
+
+    pub fn render(&mut self, window_parameters: &WindowParameters) {
+        window_parameters.render_rectangle(self.x, self.y, self.width, self.height, Color::new(1.0, 1.0, 1.0, 0.6));
+        window_parameters.render_rectangle_line(self.x, self.y, self.width, self.height, 0.001, DARKGRAY);
+
+        let row_height = self.row_height();
+        let font_size = row_height * window_parameters.height * 0.8;
+        let columns = self.columns();
+        let cell_width = self.width * 0.39 - 0.002;
+        for row in self.scroll..(self.scroll + self.rows).min(self.row_count()) {
+            let y = self.y + (row - self.scroll) as f32 * row_height;
+            let text_y = y + row_height * 0.75;
+            window_parameters.render_text(&format!("{}.", self.first_move_number as usize + row), columns[0], text_y, font_size, DARKGRAY);
+            for (column, x) in columns.into_iter().enumerate().skip(1) {
+                let Some(index) = (row * 2 + column - 1).checked_sub(self.black_moves_first as usize).filter(|index| *index < self.moves.len()) else {
+                    continue;
+                };
+                if self.current == Some(index) {
+                    window_parameters.render_rectangle(x - 0.002, y + 0.002, cell_width, row_height - 0.004, SKYBLUE);
+                } else if self.hovered == Some(index) {
+                    window_parameters.render_rectangle(x - 0.002, y + 0.002, cell_width, row_height - 0.004, WHITE);
+                }
+                window_parameters.render_text(&self.moves[index], x, text_y, font_size, BLACK);
+            }
+        }
+    }
+
+    pub fn update(&mut self, window_parameters: &WindowParameters) {
+        self.hovered = self.move_at(window_parameters);
+        let (mouse_x, mouse_y) = window_parameters.mouse_position();
+        if (self.x..=self.x + self.width).contains(&mouse_x) && (self.y..=self.y + self.height).contains(&mouse_y) {
+            let wheel = mouse_wheel().1;
+            if wheel > 0.0 {
+                self.scroll = self.scroll.saturating_sub(1);
+            } else if wheel < 0.0 {
+                self.scroll = (self.scroll + 1).min(self.row_count().saturating_sub(self.rows));
+            }
+        }
+        if is_mouse_button_pressed(MouseButton::Left) && self.hovered.is_some() {
+            self.clicked = self.hovered;
+        }
+    }
+}
+
 pub struct UIManager {
     buttons: HashMap<String, Button>,
     titles: HashMap<String, Title>,
+    text_inputs: HashMap<String, TextInput>,
+    message_lists: HashMap<String, MessageList>,
+    move_lists: HashMap<String, MoveList>,
 }
 
 impl UIManager {
     pub fn new() -> Self {
-        UIManager { buttons: HashMap::new(), titles: HashMap::new()}
+        UIManager { buttons: HashMap::new(), titles: HashMap::new(), text_inputs: HashMap::new(), message_lists: HashMap::new(), move_lists: HashMap::new() }
     }
 
     pub fn add_button(&mut self, id: &str, button: Button) {
         self.buttons.insert(id.to_string(), button);
     }
 
+    pub fn remove_button(&mut self, id: &str) {
+        self.buttons.remove(id);
+    }
+
     pub fn add_title(&mut self, id: &str, title: Title) {
         self.titles.insert(id.to_string(), title);
     }
@@ -92,6 +354,73 @@ impl UIManager {
         self.titles.remove(id);
     }
 
+    pub fn add_text_input(&mut self, id: &str, text_input: TextInput) {
+        self.text_inputs.insert(id.to_string(), text_input);
+    }
+
+    pub fn remove_text_input(&mut self, id: &str) {
+        self.text_inputs.remove(id);
+    }
+
+    /// Whether a text input has the keyboard, so keys should not be read as shortcuts.
+    pub fn is_typing(&self) -> bool {
+        self.text_inputs.values().any(|text_input| text_input.is_focused)
+    }
+
+    /// The text currently in the input, submitted or not.
+    pub fn input_text(&self, id: &str) -> &str {
+        self.text_inputs.get(id).map(|text_input| text_input.text.as_str()).unwrap_or_default()
+    }
+
+    pub fn set_input_text(&mut self, id: &str, text: &str) {
+        if let Some(text_input) = self.text_inputs.get_mut(id) {
+            text_input.text = text.to_string();
+        }
+    }
+
+    /// Returns the text entered with Enter since the last call, if any.
+    pub fn take_submitted_text(&mut self, id: &str) -> Option<String> {
+        self.text_inputs.get_mut(id).and_then(|text_input| text_input.submitted.take())
+    }
+
+    pub fn add_message_list(&mut self, id: &str, message_list: MessageList) {
+        self.message_lists.insert(id.to_string(), message_list);
+    }
+
+    pub fn push_message(&mut self, id: &str, message: &str) {
+        if let Some(message_list) = self.message_lists.get_mut(id) {
+            message_list.push(message);
+        }
+    }
+
+    pub fn clear_messages(&mut self, id: &str) {
+        if let Some(message_list) = self.message_lists.get_mut(id) {
+            message_list.messages.clear();
+        }
+    }
+
+    pub fn add_move_list(&mut self, id: &str, move_list: MoveList) {
+        self.move_lists.insert(id.to_string(), move_list);
+    }
+
+    /// Replaces the moves of the list and highlights the last one. `first_move_number` is the number of the first move.
+    pub fn set_moves(&mut self, id: &str, moves: Vec<String>, first_move_number: u16, black_moves_first: bool) {
+        if let Some(move_list) = self.move_lists.get_mut(id) {
+            move_list.set_moves(moves, first_move_number, black_moves_first);
+        }
+    }
+
+    pub fn set_current_move(&mut self, id: &str, index: Option<usize>) {
+        if let Some(move_list) = self.move_lists.get_mut(id) {
+            move_list.set_current(index);
+        }
+    }
+
+    /// Returns the index of the move clicked since the last call, if any.
+    pub fn take_clicked_move(&mut self, id: &str) -> Option<usize> {
+        self.move_lists.get_mut(id).and_then(|move_list| move_list.clicked.take())
+    }
+
 
     pub fn render(&mut self, window_parameters: &WindowParameters) {
         for button in self.buttons.values_mut() {
@@ -100,12 +429,32 @@ impl UIManager {
         for title in self.titles.values_mut() {
             title.render(window_parameters);
         }
+        for message_list in self.message_lists.values_mut() {
+            message_list.render(window_parameters);
+        }
+        for move_list in self.move_lists.values_mut() {
+            move_list.render(window_parameters);
+        }
+        for text_input in self.text_inputs.values_mut() {
+            text_input.render(window_parameters);
+        }
     }
 
     pub fn update(&mut self, window_parameters: &WindowParameters) {
         for button in self.buttons.values_mut() {
             button.update(window_parameters);
         }
+        for message_list in self.message_lists.values_mut() {
+            message_list.update(window_parameters);
+        }
+        for move_list in self.move_lists.values_mut() {
+            move_list.update(window_parameters);
+        }
+        // Typed characters are always drained so keys pressed while no input is focused do not show up later
+        let typed: Vec<char> = std::iter::from_fn(get_char_pressed).collect();
+        for text_input in self.text_inputs.values_mut() {
+            text_input.update(window_parameters, &typed);
+        }
     }
 
     pub fn was_button_clicked(&self, id: &str) -> bool {